@@ -1,23 +1,162 @@
-use crossterm::event::KeyCode;
+use anyhow::Context;
+use crossterm::event::{KeyCode, KeyEvent, MouseButton, MouseEvent, MouseEventKind};
 
-use crate::component::{
-    self, menu,
-    packages::{PkgInfo, PkgInfoBuilder},
+use crate::{
+    build::queue::BuildQueue,
+    client::{Client, ParseError, StatusPage, WorkData},
+    component::{
+        self,
+        clusters::ClusterView,
+        logdiff::{DiffView, LogPicker},
+        logview::{Jump, LogView},
+        menu,
+        order::RebuildOrderView,
+        packages::{PkgFilter, PkgInfo, PkgInfoBuilder},
+        pkglist::PkgList,
+        search::SearchView,
+        textview::TextView,
+    },
+    config::Config,
+    control::{Command, Reply},
+    events::Events,
+    keymap::{self, Action, Key, Lookup},
+    logs::{
+        self,
+        classify::{Classification, Classifier},
+        cluster::Cluster,
+        fetch::DownloadProgress,
+        index::{Hit, LogIndex},
+        read::{read_log, LogText},
+        stages::StageOutline,
+    },
+    pacman::{
+        depgraph::DepGraph,
+        order::{self, RebuildOrder},
+        outdated, syncdb,
+    },
+    patches::{self, PatchInfo},
 };
 use std::{
     collections::HashMap,
     sync::{atomic::AtomicBool, Arc},
 };
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc,
+    time::{Duration, SystemTime},
+};
+use tui::{
+    style::{Modifier, Style},
+    text::{Span, Spans},
+};
+
+/// Most search results kept, more are rarely useful in a list
+const SEARCH_LIMIT: usize = 5000;
+
+/// Lines of text moved by one notch of the mouse wheel
+const SCROLL_LINES: usize = 3;
 
 pub enum DisplayMode {
     ViewingPackageStatusTable,
     /// Show menu for package status table
     PopUpPstMenu(menu::PopUpMenu),
+    /// Show packages that are blocked by the selected package
+    ViewingDependents(PkgList),
+    /// Show the suggested rebuild order of unclaimed FTBFS packages
+    ViewingRebuildOrder(RebuildOrderView),
+    /// Show the riscv64 patch of the selected package
+    ViewingPatch(TextView),
+    /// Show the rows of the status page that couldn't be read
+    ViewingWarnings(TextView),
+    /// Show the newest build log of the selected package
+    ViewingLog(LogView),
+    /// Choose an older log of the selected package to compare with its newest one
+    PickingLogDiff(LogPicker),
+    /// Show two build logs side by side
+    ViewingLogDiff(DiffView),
+    /// Show the FTBFS packages grouped by failure signature
+    ViewingClusters(ClusterView),
+    /// Show the log lines matching a search
+    ViewingSearch(SearchView),
+    /// Show the output of the local build with the given id
+    ViewingBuild(usize),
+    /// Show the local build queue
+    ViewingBuildQueue,
+}
+
+impl DisplayMode {
+    /// Name of the display in the keymap sections of the config
+    pub fn name(&self) -> &'static str {
+        match self {
+            DisplayMode::ViewingPackageStatusTable => "table",
+            DisplayMode::PopUpPstMenu(_) => "menu",
+            DisplayMode::ViewingDependents(_) => "dependents",
+            DisplayMode::ViewingRebuildOrder(_) => "rebuild-order",
+            DisplayMode::ViewingPatch(_) => "patch",
+            DisplayMode::ViewingWarnings(_) => "warnings",
+            DisplayMode::ViewingLog(_) => "log",
+            DisplayMode::PickingLogDiff(_) => "log-picker",
+            DisplayMode::ViewingLogDiff(_) => "log-diff",
+            DisplayMode::ViewingClusters(_) => "clusters",
+            DisplayMode::ViewingSearch(_) => "search",
+            DisplayMode::ViewingBuild(_) => "build",
+            DisplayMode::ViewingBuildQueue => "build-queue",
+        }
+    }
+
+    /// Actions worth a reminder in the hints bar of the display
+    pub fn hints(&self) -> &'static [Action] {
+        use Action::*;
+        match self {
+            DisplayMode::ViewingPackageStatusTable => &[
+                Select,
+                Search,
+                ToggleMark,
+                Build,
+                ToggleOutdated,
+                Clusters,
+                Help,
+                Quit,
+            ],
+            DisplayMode::PopUpPstMenu(_) => &[Down, Up, Select, Back],
+            DisplayMode::ViewingRebuildOrder(_) => &[ExportOrder, Back, Help],
+            DisplayMode::ViewingLog(_) => &[
+                Next,
+                Previous,
+                ToggleFold,
+                ToggleFoldAll,
+                JumpToFailure,
+                Back,
+                Help,
+            ],
+            DisplayMode::PickingLogDiff(_) => &[Select, Back, Help],
+            DisplayMode::ViewingLogDiff(_) => &[Next, Previous, Back, Help],
+            DisplayMode::ViewingClusters(_) | DisplayMode::ViewingSearch(_) => {
+                &[Select, Back, Help]
+            }
+            DisplayMode::ViewingBuild(_) => &[CancelBuild, Back, Help],
+            DisplayMode::ViewingBuildQueue => &[Select, CancelBuild, RetryBuild, Back, Help],
+            DisplayMode::ViewingDependents(_)
+            | DisplayMode::ViewingPatch(_)
+            | DisplayMode::ViewingWarnings(_) => &[Down, Up, Back, Help],
+        }
+    }
 }
 
 pub enum InputMode {
     Normal,
-    HasPrefix(Vec<KeyCode>),
+    /// Keys typed so far of a multi-key binding
+    Pending(Vec<Key>),
+    /// Typing a log search pattern
+    Search(String),
+}
+
+/// What is read out of a build log, kept until the log is modified
+#[derive(Debug, Clone, Default)]
+struct LogSummary {
+    classification: Option<Classification>,
+    /// Normalized first error, to cluster the packages failing the same way
+    signature: Option<String>,
 }
 
 /// A data collection of current running status, data resources...etc. Each modification will
@@ -25,19 +164,41 @@ pub enum InputMode {
 pub struct App {
     current_display: DisplayMode,
     pub is_running: Arc<AtomicBool>,
+    /// Stop reading terminal events while another program owns the terminal
+    pub is_suspended: Arc<AtomicBool>,
+    /// Package waiting for the main loop to release the terminal and start the patch workflow
+    pending_patch: Option<Box<str>>,
 
     pub input_mode: InputMode,
     pub pkg_info_table: component::packages::PkgInfoTable,
+
+    pub config: Config,
+    pub depgraph: DepGraph,
+
+    /// Sender of the main loop channel, for background tasks to report back
+    event_sender: Option<mpsc::Sender<Events>>,
+    pub build_queue: BuildQueue,
+    pub classifier: Classifier,
+    /// Summary of each log by path, with the modification time of the log it is for
+    log_summaries: HashMap<PathBuf, (SystemTime, LogSummary)>,
+    /// Id of the last background log read
+    last_read_id: usize,
+    /// Progress of the running or last bulk log download
+    pub log_download: Option<DownloadProgress>,
+    /// Rows of the status page skipped by the last update
+    pub status_warnings: Vec<ParseError>,
+    /// Key bindings overlay, drawn over the current display while it is open
+    pub help: Option<TextView>,
+    /// Time of the last successful fetch of each data source, like `felix` or `syncdb`
+    pub last_fetch: HashMap<&'static str, SystemTime>,
+    /// Why the last update failed, shown in place of the key hints until an update succeeds
+    pub update_error: Option<String>,
+    client: Client,
 }
 
 impl std::default::Default for App {
     fn default() -> Self {
-        Self {
-            current_display: DisplayMode::ViewingPackageStatusTable,
-            input_mode: InputMode::Normal,
-            pkg_info_table: component::packages::PkgInfoTable::default(),
-            is_running: Arc::new(AtomicBool::new(true)),
-        }
+        Self::new(Config::default())
     }
 }
 
@@ -54,16 +215,49 @@ macro_rules! async_eval {
 }
 
 impl App {
+    pub fn new(config: Config) -> Self {
+        let build_queue = BuildQueue::new(config.build.dir.clone());
+        Self {
+            current_display: DisplayMode::ViewingPackageStatusTable,
+            input_mode: InputMode::Normal,
+            pkg_info_table: component::packages::PkgInfoTable::default(),
+            is_running: Arc::new(AtomicBool::new(true)),
+            is_suspended: Arc::new(AtomicBool::new(false)),
+            pending_patch: None,
+            classifier: Classifier::new(&config.logs.rules),
+            log_summaries: HashMap::new(),
+            last_read_id: 0,
+            log_download: None,
+            status_warnings: Vec::new(),
+            help: None,
+            last_fetch: HashMap::new(),
+            update_error: None,
+            client: Client::new(),
+            config,
+            depgraph: DepGraph::default(),
+            event_sender: None,
+            build_queue,
+        }
+    }
+
+    pub fn set_event_sender(&mut self, tx: mpsc::Sender<Events>) {
+        self.event_sender = Some(tx);
+    }
+
     pub fn reset_input_mode(&mut self) {
         self.input_mode = InputMode::Normal;
     }
 
-    async fn fetch_data() -> Vec<PkgInfo> {
-        use crate::req;
-
+    /// Download the package status and the work list. Rows of the status page that couldn't be
+    /// read are returned as warnings. Each successful download is recorded in `last_fetch`, even
+    /// when the other one fails.
+    async fn fetch_data(
+        client: Client,
+        last_fetch: &mut HashMap<&'static str, SystemTime>,
+    ) -> anyhow::Result<(Vec<PkgInfo>, Vec<ParseError>)> {
         enum Message {
-            Felix(anyhow::Result<Vec<req::felix::PackageStatus>>),
-            Melon(anyhow::Result<req::melon::Response>),
+            Felix(anyhow::Result<StatusPage>),
+            Melon(anyhow::Result<WorkData>),
         }
 
         struct DownloadStatus {
@@ -85,18 +279,21 @@ impl App {
         let (tx, rx) = std::sync::mpsc::channel();
 
         let tx1 = tx.clone();
+        let melon = client.clone();
         tokio::spawn(async move {
-            let pkgs = req::melon::fetch().await;
+            let pkgs = melon.work_data().await;
             tx1.send(Message::Melon(pkgs))
         });
 
         tokio::spawn(async move {
-            let pkgs = req::felix::PackageStatus::download().await;
+            let pkgs = client.status().await;
             tx.send(Message::Felix(pkgs))
-                .expect("Unexpected closed channel during sending download message");
         });
 
         let mut buffer: HashMap<Box<str>, PkgInfoBuilder> = HashMap::new();
+        let mut warnings = Vec::new();
+        // returned once both downloads have ended, so the other one is still recorded
+        let mut error = None;
 
         loop {
             if status.is_done() {
@@ -109,22 +306,41 @@ impl App {
             match msg {
                 Message::Felix(pkgs) => {
                     status.felix = true;
-                    let pkgs = pkgs.unwrap_or_else(|err| {
-                        panic!("fail to download package information from felixc page: {err}")
-                    });
+                    let page = match pkgs
+                        .with_context(|| "fail to download package information from felixc page")
+                    {
+                        Ok(page) => page,
+                        Err(err) => {
+                            error.get_or_insert(err);
+                            continue;
+                        }
+                    };
+                    last_fetch.insert("felix", SystemTime::now());
+                    warnings = page.warnings;
 
-                    for pkg in pkgs {
-                        if buffer.get_mut(&pkg.pkgname).is_none() {
-                            let builder = PkgInfoBuilder::default().name(pkg.pkgname.clone());
-                            buffer.insert(pkg.pkgname, builder);
+                    for pkg in page.packages {
+                        let builder = buffer
+                            .remove(&pkg.pkgname)
+                            .unwrap_or_else(|| PkgInfoBuilder::default().name(pkg.pkgname.clone()));
+                        let mut builder = builder.process(pkg.status).repo(pkg.repo);
+                        if let Some(log) = pkg.log {
+                            builder = builder.log_url(log);
                         }
+                        buffer.insert(pkg.pkgname, builder);
                     }
                 }
                 Message::Melon(pkgs) => {
                     status.melon = true;
-                    let pkgs = pkgs.unwrap_or_else(|err| {
-                        panic!("fail to download package information from melon bot: {err}")
-                    });
+                    let pkgs = match pkgs
+                        .with_context(|| "fail to download package information from melon bot")
+                    {
+                        Ok(pkgs) => pkgs,
+                        Err(err) => {
+                            error.get_or_insert(err);
+                            continue;
+                        }
+                    };
+                    last_fetch.insert("melon", SystemTime::now());
 
                     if pkgs.marklist.is_empty() && pkgs.worklist.is_empty() {
                         continue;
@@ -151,26 +367,160 @@ impl App {
             }
         }
 
-        buffer
+        if let Some(err) = error {
+            return Err(err);
+        }
+
+        let pkgs = buffer
             .into_values()
             .map(|builder| builder.build().unwrap())
             .filter(|pkg| pkg.has_assignee() || pkg.has_process() || pkg.has_marks())
-            .collect::<Vec<_>>()
+            .collect::<Vec<_>>();
+        Ok((pkgs, warnings))
     }
 
     pub fn update(&mut self) -> anyhow::Result<()> {
-        let new_data = async_eval!(App::fetch_data());
+        let client = self.client.clone();
+        let last_fetch = &mut self.last_fetch;
+        let (mut new_data, warnings) = async_eval!(App::fetch_data(client, last_fetch))?;
+        self.pkg_info_table.warnings = warnings.len();
+        self.status_warnings = warnings;
+
+        let riscv64_pkgs = match self.config.syncdb.dir {
+            Some(ref dir) => {
+                let pkgs = syncdb::load_repos(dir, &self.config.syncdb.repos)?;
+                self.last_fetch.insert("syncdb", SystemTime::now());
+                pkgs
+            }
+            None => Vec::new(),
+        };
+        self.depgraph = DepGraph::from_packages(&riscv64_pkgs);
+
+        if let Some(ref dir) = self.config.x86_64_syncdb.dir {
+            let x86_64_pkgs = syncdb::load_repos(dir, &self.config.x86_64_syncdb.repos)?;
+            self.last_fetch.insert("x86_64_syncdb", SystemTime::now());
+            let mut outdated = outdated::find_outdated(&riscv64_pkgs, &x86_64_pkgs);
+
+            for pkg in new_data.iter_mut() {
+                pkg.outdated = outdated.remove(pkg.name());
+            }
+            // outdated packages are rebuild candidates even if they don't fail yet
+            new_data.extend(outdated.into_iter().map(|(name, outdated)| {
+                PkgInfoBuilder::default()
+                    .name(name)
+                    .outdated(outdated)
+                    .build()
+                    .unwrap()
+            }));
+        }
+
+        if let Some(ref repo) = self.config.patches.repo {
+            let mut patches = patches::scan(repo)?;
+            self.last_fetch.insert("patches", SystemTime::now());
+            for pkg in new_data.iter_mut() {
+                pkg.patch = patches.remove(pkg.name());
+            }
+        }
+
+        for pkg in new_data.iter_mut() {
+            pkg.blocks = self.depgraph.blocks(pkg.name());
+            pkg.local = self.build_queue.results.get(pkg.name()).cloned();
+            pkg.reason = self.classify(pkg.name());
+        }
 
         self.pkg_info_table.data = new_data;
+        self.pkg_info_table.sort_rows();
 
         Ok(())
     }
 
+    /// Update the package data, keeping the error for the status bar rather than leaving the TUI
+    pub fn refresh(&mut self) {
+        self.update_error = self.update().err().map(|err| format!("{err:#}"));
+    }
+
     /// Mutate self data based on the input
-    pub fn handle_input(&mut self, keycode: KeyCode) {
-        match &self.input_mode {
-            InputMode::Normal => self.handle_normal_input(keycode),
-            InputMode::HasPrefix(prefix) => self.handle_input_with_prefix(keycode, prefix.to_vec()),
+    pub fn handle_input(&mut self, key: KeyEvent) {
+        match self.input_mode {
+            InputMode::Normal | InputMode::Pending(_) => self.handle_key(Key::from(key)),
+            InputMode::Search(_) => self.handle_search_input(key.code),
+        }
+    }
+
+    /// Scroll with the wheel, select a row or menu item with a left click, or sort the package
+    /// table with a click on a column title
+    pub fn handle_mouse(&mut self, mouse: MouseEvent) {
+        // motion is reported too, it must not cancel a half typed key sequence
+        let is_click_or_scroll = matches!(
+            mouse.kind,
+            MouseEventKind::Down(_) | MouseEventKind::ScrollDown | MouseEventKind::ScrollUp
+        );
+        if !is_click_or_scroll || matches!(self.input_mode, InputMode::Search(_)) {
+            return;
+        }
+        self.input_mode = InputMode::Normal;
+        match mouse.kind {
+            MouseEventKind::ScrollDown => self.scroll(1),
+            MouseEventKind::ScrollUp => self.scroll(-1),
+            MouseEventKind::Down(MouseButton::Left) if self.help.is_none() => {
+                self.click(mouse.column, mouse.row)
+            }
+            _ => (),
+        }
+    }
+
+    /// Move by one row in lists, or by a few lines in text
+    fn scroll(&mut self, direction: isize) {
+        use DisplayMode::*;
+        let lines = match (&self.help, &self.current_display) {
+            (Some(_), _) => {
+                let action = if direction > 0 {
+                    Action::Down
+                } else {
+                    Action::Up
+                };
+                for _ in 0..SCROLL_LINES {
+                    self.run_help_action(action);
+                }
+                return;
+            }
+            (None, ViewingPackageStatusTable) => {
+                self.pkg_info_table.step(direction);
+                return;
+            }
+            (
+                None,
+                ViewingPatch(_) | ViewingWarnings(_) | ViewingLog(_) | ViewingLogDiff(_)
+                | ViewingBuild(_),
+            ) => SCROLL_LINES,
+            _ => 1,
+        };
+        for _ in 0..lines {
+            if direction > 0 {
+                self.key_down();
+            } else {
+                self.key_up();
+            }
+        }
+    }
+
+    fn click(&mut self, x: u16, y: u16) {
+        match self.current_display {
+            DisplayMode::ViewingPackageStatusTable => {
+                let table = &mut self.pkg_info_table;
+                if let Some(column) = table.column_at(x, y) {
+                    table.sort_by(column);
+                } else if let Some(row) = table.row_at(x, y) {
+                    table.cursor.select(Some(row));
+                }
+            }
+            DisplayMode::PopUpPstMenu(ref mut menu) => {
+                if let Some(item) = menu.item_at(x, y) {
+                    menu.state.select(Some(item));
+                    self.select_pst_menu();
+                }
+            }
+            _ => (),
         }
     }
 
@@ -183,40 +533,185 @@ impl App {
         self.is_running.load(std::sync::atomic::Ordering::SeqCst)
     }
 
-    fn handle_normal_input(&mut self, keycode: KeyCode) {
-        match keycode {
-            KeyCode::Char('q') => self.shutdown(),
-            KeyCode::Char('G') => self.key_end(),
-            KeyCode::Char('g') => {
-                self.input_mode = InputMode::HasPrefix(vec![KeyCode::Char('g')]);
-            }
-            KeyCode::Up | KeyCode::Char('j') => self.key_up(),
-            KeyCode::Down | KeyCode::Char('k') => self.key_down(),
-            #[allow(clippy::single_match)]
-            KeyCode::Enter => match self.current_display() {
+    /// Run the action bound to the keys typed so far followed by `key`, or wait for the next key
+    /// when they are the start of a longer sequence. An unbound key cancels the sequence.
+    fn handle_key(&mut self, key: Key) {
+        let mut keys = match std::mem::replace(&mut self.input_mode, InputMode::Normal) {
+            InputMode::Pending(keys) => keys,
+            _ => Vec::new(),
+        };
+        keys.push(key);
+        match self.config.keys.lookup(self.keymap_mode(), &keys) {
+            Lookup::Action(action) if self.help.is_some() => self.run_help_action(action),
+            Lookup::Action(action) => self.run_action(action),
+            Lookup::Pending => self.input_mode = InputMode::Pending(keys),
+            Lookup::Unbound => (),
+        }
+    }
+
+    /// Keymap section of what the keys apply to, the help overlay or the current display
+    fn keymap_mode(&self) -> &'static str {
+        match self.help {
+            Some(_) => "help",
+            None => self.current_display.name(),
+        }
+    }
+
+    /// Scroll or close the help overlay, the other actions wait until it is closed
+    fn run_help_action(&mut self, action: Action) {
+        let help = match self.help {
+            Some(ref mut help) => help,
+            None => return,
+        };
+        match action {
+            Action::Down => help.scroll_down(),
+            Action::Up => help.scroll_up(),
+            Action::Top => help.top(),
+            Action::Bottom => help.bottom(),
+            Action::Back | Action::Help | Action::Quit => self.help = None,
+            _ => (),
+        }
+    }
+
+    /// Open the help overlay, listing the bindings of each keymap section
+    pub fn show_help(&mut self) {
+        let mut lines = Vec::new();
+        for mode in keymap::MODES {
+            let bindings = self.config.keys.bindings(mode);
+            if bindings.is_empty() {
+                continue;
+            }
+            if !lines.is_empty() {
+                lines.push(Spans::from(""));
+            }
+            lines.push(Spans::from(Span::styled(
+                mode,
+                Style::default().add_modifier(Modifier::BOLD),
+            )));
+            // keys running the same action share a line
+            let mut actions: Vec<(Vec<String>, Action)> = Vec::new();
+            for (keys, action) in bindings {
+                match actions.last_mut() {
+                    Some((all, last)) if *last == action => all.push(keys),
+                    _ => actions.push((vec![keys], action)),
+                }
+            }
+            for (keys, action) in actions {
+                lines.push(Spans::from(format!(
+                    "  {:<12}{:<17}{}",
+                    keys.join(" "),
+                    action.name(),
+                    action.description()
+                )));
+            }
+        }
+        self.help = Some(TextView::new("Key bindings", lines));
+    }
+
+    /// Keys of the most useful actions of the current display, like `<Enter> select  / search`
+    pub fn hints(&self) -> String {
+        let (mode, actions): (_, &[Action]) = match self.help {
+            Some(_) => ("help", &[Action::Down, Action::Up, Action::Back]),
+            None => (self.current_display.name(), self.current_display.hints()),
+        };
+        actions
+            .iter()
+            .filter_map(|action| {
+                let keys = self.config.keys.keys_for(mode, *action)?;
+                Some(format!("{keys} {}", action.name()))
+            })
+            .collect::<Vec<_>>()
+            .join("  ")
+    }
+
+    /// Keys typed so far of a longer key sequence, like `g` before `gg`
+    pub fn pending_keys(&self) -> Option<String> {
+        match self.input_mode {
+            InputMode::Pending(ref keys) => Some(keymap::format_keys(keys)),
+            _ => None,
+        }
+    }
+
+    pub fn run_action(&mut self, action: Action) {
+        match action {
+            Action::Quit => self.shutdown(),
+            Action::Up => self.key_up(),
+            Action::Down => self.key_down(),
+            Action::Top => self.key_begining(),
+            Action::Bottom => self.key_end(),
+            Action::RebuildOrder => self.show_rebuild_order(),
+            Action::ExportOrder => self.export_rebuild_order(),
+            Action::ToggleOutdated => self.toggle_outdated_filter(),
+            Action::ShowPatch => self.show_patch(),
+            Action::ShowWarnings => self.show_warnings(),
+            Action::PreparePatch => self.request_patch_scaffold(),
+            Action::ToggleMark => self.pkg_info_table.toggle_mark(),
+            Action::Build => self.queue_builds(),
+            Action::BuildQueue => self.show_build_queue(),
+            Action::CancelBuild => self.cancel_build(),
+            Action::RetryBuild => self.retry_build(),
+            Action::DownloadLogs => self.download_logs(),
+            Action::Clusters => self.show_clusters(),
+            Action::Search => self.input_mode = InputMode::Search(String::new()),
+            Action::ToggleFold => self.with_log_view(LogView::toggle_fold),
+            Action::ToggleFoldAll => self.with_log_view(LogView::toggle_fold_all),
+            Action::JumpToFailure => self.with_log_view(LogView::jump_to_failure),
+            Action::Next => match self.current_display {
+                DisplayMode::ViewingLog(ref mut view) => view.next_stage(),
+                DisplayMode::ViewingLogDiff(ref mut view) => view.next_hunk(),
+                _ => (),
+            },
+            Action::Previous => match self.current_display {
+                DisplayMode::ViewingLog(ref mut view) => view.previous_stage(),
+                DisplayMode::ViewingLogDiff(ref mut view) => view.previous_hunk(),
+                _ => (),
+            },
+            Action::Back => self.current_display = DisplayMode::ViewingPackageStatusTable,
+            Action::Help => self.show_help(),
+            Action::Select => match self.current_display() {
                 DisplayMode::ViewingPackageStatusTable => {
                     self.show_pst_menu();
                 }
+                DisplayMode::PopUpPstMenu(_) => self.select_pst_menu(),
+                DisplayMode::ViewingBuildQueue => {
+                    if let Some(job) = self.build_queue.selected() {
+                        self.current_display = DisplayMode::ViewingBuild(job.id);
+                    }
+                }
+                DisplayMode::ViewingClusters(_) => self.mark_cluster(),
+                DisplayMode::ViewingSearch(_) => self.show_search_hit(),
+                DisplayMode::PickingLogDiff(_) => self.show_log_diff(),
                 _ => (),
             },
-            _ => (),
         }
     }
 
-    fn handle_input_with_prefix(&mut self, keycode: KeyCode, prefix: Vec<KeyCode>) {
-        if prefix.is_empty() {
-            panic!("some logical error occurs for keys");
+    /// Apply `action` to the log view, if it is the current display
+    fn with_log_view(&mut self, action: fn(&mut LogView)) {
+        if let DisplayMode::ViewingLog(ref mut view) = self.current_display {
+            action(view);
         }
-        match prefix[0] {
-            KeyCode::Char('g') => match keycode {
-                // handle key 'gg'
-                KeyCode::Char('g') => {
-                    self.key_begining();
-                    self.reset_input_mode();
+    }
+
+    fn handle_search_input(&mut self, keycode: KeyCode) {
+        let query = match self.input_mode {
+            InputMode::Search(ref mut query) => query,
+            _ => return,
+        };
+        match keycode {
+            KeyCode::Char(c) => query.push(c),
+            KeyCode::Backspace => {
+                query.pop();
+            }
+            KeyCode::Enter => {
+                let pattern = std::mem::take(query);
+                self.reset_input_mode();
+                if !pattern.is_empty() {
+                    self.show_search(&pattern);
                 }
-                _ => self.reset_input_mode(),
-            },
-            _ => self.reset_input_mode(),
+            }
+            KeyCode::Esc => self.reset_input_mode(),
+            _ => (),
         }
     }
 
@@ -224,10 +719,13 @@ impl App {
         &self.current_display
     }
 
+    pub fn current_display_mut(&mut self) -> &mut DisplayMode {
+        &mut self.current_display
+    }
+
     /// Set current display to a menu that showing available option for current selection
     pub fn show_pst_menu(&mut self) {
-        let current_selection = self.pkg_info_table.cursor.selected().unwrap_or(0);
-        let data = self.pkg_info_table.data.get(current_selection);
+        let data = self.pkg_info_table.selected();
         if data.is_none() {
             return;
         }
@@ -243,14 +741,583 @@ impl App {
             menu_items.push("Check Marks")
         }
 
+        if !self.depgraph.is_empty() {
+            menu_items.push("View dependents");
+        }
+
+        if data.has_patch() {
+            menu_items.push("View riscv64 patch");
+        }
+
+        if self.config.patches.repo.is_some() {
+            menu_items.push("Prepare riscv64 patch");
+        }
+
+        menu_items.push("Build locally");
+
         // TODO: Add drop menu when assigness is user
 
         menu_items.push("View package details");
         menu_items.push("View package build log");
+        menu_items.push("Compare build logs");
 
         self.current_display = DisplayMode::PopUpPstMenu(menu::PopUpMenu::from(&menu_items));
     }
 
+    /// Run the action of the highlighted menu item
+    fn select_pst_menu(&mut self) {
+        let selected = match self.current_display() {
+            DisplayMode::PopUpPstMenu(menu) => menu.selected().map(|s| s.to_string()),
+            _ => return,
+        };
+
+        match selected.as_deref() {
+            Some("View dependents") => self.show_dependents(),
+            Some("View riscv64 patch") => {
+                self.current_display = DisplayMode::ViewingPackageStatusTable;
+                self.show_patch();
+            }
+            Some("Prepare riscv64 patch") => {
+                self.current_display = DisplayMode::ViewingPackageStatusTable;
+                self.request_patch_scaffold();
+            }
+            Some("Build locally") => {
+                self.current_display = DisplayMode::ViewingPackageStatusTable;
+                self.queue_builds();
+            }
+            Some("View package build log") => {
+                self.current_display = DisplayMode::ViewingPackageStatusTable;
+                self.show_log();
+            }
+            Some("Compare build logs") => self.pick_log_diff(),
+            _ => self.current_display = DisplayMode::ViewingPackageStatusTable,
+        }
+    }
+
+    /// Set current display to the list of packages blocked by current selection
+    pub fn show_dependents(&mut self) {
+        let name = match self.pkg_info_table.selected() {
+            Some(pkg) => pkg.name(),
+            None => return,
+        };
+
+        let dependents = self.depgraph.dependents(name);
+        let list = PkgList::new(format!("Packages blocked by {name}"), dependents);
+        self.current_display = DisplayMode::ViewingDependents(list);
+    }
+
+    /// Set current display to the riscv64 patch of current selection, if any
+    pub fn show_patch(&mut self) {
+        if !matches!(self.current_display, DisplayMode::ViewingPackageStatusTable) {
+            return;
+        }
+        let patch = match self
+            .pkg_info_table
+            .selected()
+            .and_then(|pkg| pkg.patch.as_ref())
+        {
+            Some(patch) => patch,
+            None => return,
+        };
+
+        let title = patch.path.display().to_string();
+        let view = match std::fs::read_to_string(&patch.path) {
+            Ok(content) => TextView::diff(title, &content),
+            Err(err) => TextView::plain(title, &format!("fail to read patch: {err}")),
+        };
+        self.current_display = DisplayMode::ViewingPatch(view);
+    }
+
+    /// Run a command received on the control socket
+    pub fn handle_control(&mut self, command: Command) -> Reply {
+        let table = &mut self.pkg_info_table;
+        match command {
+            Command::Select { package } => {
+                if !table.select_name(&package) {
+                    return Err(format!("no package {package} in the table"));
+                }
+                self.current_display = DisplayMode::ViewingPackageStatusTable;
+            }
+            Command::Filter { query } => table.set_query(query),
+            Command::Refresh => {
+                self.refresh();
+                if let Some(ref err) = self.update_error {
+                    return Err(err.clone());
+                }
+            }
+            Command::GetSelection => {
+                let mut marked = table.marked.iter().collect::<Vec<_>>();
+                marked.sort();
+                return Ok(serde_json::json!({
+                    "package": table.selected(),
+                    "marked": marked,
+                }));
+            }
+        }
+        Ok(serde_json::Value::Null)
+    }
+
+    /// Set current display to the rows of the status page that were skipped, if any
+    pub fn show_warnings(&mut self) {
+        if !matches!(self.current_display, DisplayMode::ViewingPackageStatusTable)
+            || self.status_warnings.is_empty()
+        {
+            return;
+        }
+        let content = self
+            .status_warnings
+            .iter()
+            .map(|warning| warning.to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let view = TextView::plain("Status page rows that couldn't be read", &content);
+        self.current_display = DisplayMode::ViewingWarnings(view);
+    }
+
+    /// Summarize the newest build log of `pkgname`, if there is one. A log is only read again
+    /// once it has been modified.
+    fn summarize_log(&mut self, pkgname: &str) -> Option<&LogSummary> {
+        let path = logs::find_log(&self.config.logs, &self.build_queue, pkgname)?;
+        let modified = path.metadata().and_then(|meta| meta.modified()).ok()?;
+        let fresh = self
+            .log_summaries
+            .get(&path)
+            .is_some_and(|(time, _)| *time == modified);
+        if !fresh {
+            let summary = match read_log(&path) {
+                Ok(log) => LogSummary {
+                    classification: self.classifier.classify(&log),
+                    signature: logs::cluster::signature(&log),
+                },
+                Err(_) => LogSummary::default(),
+            };
+            self.log_summaries.insert(path.clone(), (modified, summary));
+        }
+        self.log_summaries.get(&path).map(|(_, summary)| summary)
+    }
+
+    /// Classify the newest build log of `pkgname`, if there is one
+    fn classify(&mut self, pkgname: &str) -> Option<Classification> {
+        self.summarize_log(pkgname)?.classification.clone()
+    }
+
+    /// View of the log at `path`, read in the background when the main loop is running
+    fn open_log(&mut self, title: String, path: &Path, jump: Jump) -> LogView {
+        let tx = match self.event_sender {
+            Some(ref tx) => tx.clone(),
+            None => {
+                let mut view = match read_log(path) {
+                    Ok(content) => LogView::from_text(title, LogText::new(content)),
+                    Err(err) => return LogView::new(title, &format!("fail to read log: {err}")),
+                };
+                view.jump(jump);
+                return view;
+            }
+        };
+
+        self.last_read_id += 1;
+        let cancel = logs::read::spawn_read(self.last_read_id, path.to_path_buf(), tx);
+        LogView::reading(title, self.last_read_id, cancel, jump)
+    }
+
+    pub fn handle_log_chunk(&mut self, id: usize, text: String) {
+        if let DisplayMode::ViewingLog(ref mut view) = self.current_display {
+            if view.reading_id() == Some(id) {
+                view.push_chunk(&text);
+            }
+        }
+    }
+
+    pub fn handle_log_read(&mut self, id: usize, outline: StageOutline, error: Option<String>) {
+        if let DisplayMode::ViewingLog(ref mut view) = self.current_display {
+            if view.reading_id() == Some(id) {
+                view.finish_reading(outline, error);
+            }
+        }
+    }
+
+    /// Set current display to the newest build log of current selection, at the line explaining
+    /// the failure when it is recognized or else at the failing stage
+    pub fn show_log(&mut self) {
+        if !matches!(self.current_display, DisplayMode::ViewingPackageStatusTable) {
+            return;
+        }
+        let pkg = match self.pkg_info_table.selected() {
+            Some(pkg) => pkg,
+            None => return,
+        };
+
+        let view = match logs::find_log(&self.config.logs, &self.build_queue, pkg.name()) {
+            Some(path) => {
+                let title = match pkg.reason {
+                    Some(ref c) => format!("{} ({})", path.display(), c.reason),
+                    None => path.display().to_string(),
+                };
+                let jump = match pkg.reason {
+                    Some(ref c) => Jump::Line(c.line),
+                    None => Jump::Failure,
+                };
+                self.open_log(title, &path, jump)
+            }
+            None => LogView::new(
+                format!("Build log of {}", pkg.name()),
+                "no log found, download it or build the package locally first",
+            ),
+        };
+        self.current_display = DisplayMode::ViewingLog(view);
+    }
+
+    /// Set current display to the list of logs of current selection to compare with its newest
+    pub fn pick_log_diff(&mut self) {
+        let pkgname = match self.pkg_info_table.selected() {
+            Some(pkg) => pkg.name(),
+            None => return,
+        };
+        let sources = logs::log_sources(&self.config.logs, &self.build_queue, pkgname);
+        self.current_display = DisplayMode::PickingLogDiff(LogPicker::new(pkgname, sources));
+    }
+
+    /// Show the selected log side by side with the newest one
+    fn show_log_diff(&mut self) {
+        let (left, right) = match self.current_display {
+            DisplayMode::PickingLogDiff(ref picker) => match picker.selected() {
+                Some((left, right)) => (left.clone(), right.clone()),
+                None => return,
+            },
+            _ => return,
+        };
+
+        let read = |path: &std::path::Path| {
+            read_log(path).unwrap_or_else(|err| format!("fail to read log: {err}"))
+        };
+        let titles = (
+            format!("{} ({})", left.label, left.path.display()),
+            format!("{} ({})", right.label, right.path.display()),
+        );
+        let mut view = DiffView::new(titles, &read(&left.path), &read(&right.path));
+        view.next_hunk();
+        self.current_display = DisplayMode::ViewingLogDiff(view);
+    }
+
+    /// Ask the main loop to run the patch workflow for current selection. It needs the terminal,
+    /// so it can't run inside the input handler.
+    pub fn request_patch_scaffold(&mut self) {
+        if !matches!(self.current_display, DisplayMode::ViewingPackageStatusTable)
+            || self.config.patches.repo.is_none()
+        {
+            return;
+        }
+        if let Some(pkg) = self.pkg_info_table.selected() {
+            self.pending_patch = Some(pkg.name.clone());
+        }
+    }
+
+    pub fn take_pending_patch(&mut self) -> Option<Box<str>> {
+        self.pending_patch.take()
+    }
+
+    /// Show the outcome of the patch workflow and record the new patch in the table
+    pub fn finish_patch_scaffold(
+        &mut self,
+        pkgname: &str,
+        result: anyhow::Result<Option<PathBuf>>,
+    ) {
+        let view = match result {
+            Ok(Some(path)) => {
+                let content = std::fs::read_to_string(&path).unwrap_or_default();
+                let view = TextView::diff(format!("Generated {}", path.display()), &content);
+                if let Some(pkg) = self
+                    .pkg_info_table
+                    .data
+                    .iter_mut()
+                    .find(|pkg| pkg.name() == pkgname)
+                {
+                    pkg.patch = Some(PatchInfo {
+                        path,
+                        last_commit: None,
+                    });
+                }
+                view
+            }
+            Ok(None) => TextView::plain(pkgname, "PKGBUILD is unchanged, no patch is written"),
+            Err(err) => TextView::plain(pkgname, &format!("fail to prepare patch: {err:?}")),
+        };
+        self.current_display = DisplayMode::ViewingPatch(view);
+    }
+
+    /// Queue builds of the marked packages, or of current selection when nothing is
+    /// marked, then show the queue.
+    pub fn queue_builds(&mut self) {
+        if !matches!(self.current_display, DisplayMode::ViewingPackageStatusTable) {
+            return;
+        }
+        let table = &mut self.pkg_info_table;
+        let mut pkgnames = table.marked.drain().collect::<Vec<_>>();
+        if pkgnames.is_empty() {
+            pkgnames.extend(table.selected().map(|pkg| pkg.name.clone()));
+        }
+        pkgnames.sort_unstable();
+
+        for pkgname in pkgnames {
+            let needs_board = table
+                .data
+                .iter()
+                .find(|pkg| pkg.name == pkgname)
+                .is_some_and(|pkg| pkg.marks().contains(&"noqemu"));
+            self.build_queue.push(&pkgname, needs_board);
+        }
+        self.schedule_builds();
+        self.show_build_queue();
+    }
+
+    fn schedule_builds(&mut self) {
+        if let Some(ref tx) = self.event_sender {
+            self.build_queue.schedule(&self.config, tx);
+        }
+    }
+
+    pub fn show_build_queue(&mut self) {
+        if matches!(
+            self.current_display,
+            DisplayMode::ViewingPackageStatusTable | DisplayMode::ViewingBuild(_)
+        ) {
+            self.current_display = DisplayMode::ViewingBuildQueue;
+        }
+    }
+
+    fn cancel_build(&mut self) {
+        if let DisplayMode::ViewingBuildQueue = self.current_display {
+            self.build_queue.cancel_selected();
+            self.schedule_builds();
+        }
+    }
+
+    fn retry_build(&mut self) {
+        if let DisplayMode::ViewingBuildQueue = self.current_display {
+            self.build_queue.retry_selected();
+            self.schedule_builds();
+        }
+    }
+
+    pub fn handle_build_output(&mut self, id: usize, line: String) {
+        self.build_queue.output(id, line);
+    }
+
+    /// Record a build that can't be started and give its slot to the next queued build
+    pub fn handle_build_failed(&mut self, id: usize, error: String) {
+        self.build_queue.fail(id, error);
+        self.schedule_builds();
+    }
+
+    /// Record the build result in the table and start the next queued builds
+    pub fn handle_build_finished(&mut self, id: usize, code: Option<i32>, duration: Duration) {
+        if let Some((pkgname, record)) = self.build_queue.finish(id, code, duration) {
+            let reason = self.classify(&pkgname);
+            if let Some(pkg) = self
+                .pkg_info_table
+                .data
+                .iter_mut()
+                .find(|pkg| pkg.name == pkgname)
+            {
+                pkg.local = Some(record);
+                pkg.reason = reason;
+            }
+        }
+        self.schedule_builds();
+    }
+
+    /// Download the build log of every FTBFS package in the background. The link from the status
+    /// page is preferred over the configured URL.
+    pub fn download_logs(&mut self) {
+        if !matches!(
+            self.current_display,
+            DisplayMode::ViewingPackageStatusTable | DisplayMode::ViewingClusters(_)
+        ) || self
+            .log_download
+            .is_some_and(|progress| !progress.is_done())
+        {
+            return;
+        }
+        let tx = match self.event_sender {
+            Some(ref tx) => tx.clone(),
+            None => return,
+        };
+
+        let jobs = self
+            .pkg_info_table
+            .data
+            .iter()
+            .filter(|pkg| pkg.is_ftbfs())
+            .filter_map(|pkg| {
+                let url = match (&pkg.log_url, &self.config.logs.url) {
+                    (Some(url), _) => url.to_string(),
+                    (None, Some(template)) => template.replace("{pkgname}", pkg.name()),
+                    (None, None) => return None,
+                };
+                Some((pkg.name.clone(), url))
+            })
+            .collect::<Vec<_>>();
+
+        let progress = DownloadProgress {
+            total: jobs.len(),
+            ..Default::default()
+        };
+        self.log_download = Some(progress);
+        self.set_cluster_message(progress.summary());
+        logs::fetch::spawn_download(
+            self.config.logs.dir.clone(),
+            jobs,
+            self.config.logs.parallel_downloads,
+            tx,
+        );
+    }
+
+    /// Classify the new log, and refresh the clusters once every log is there
+    pub fn handle_log_downloaded(&mut self, pkgname: &str, error: Option<String>) {
+        let progress = match self.log_download {
+            Some(ref mut progress) => progress,
+            None => return,
+        };
+        progress.done += 1;
+        if error.is_some() {
+            progress.failed += 1;
+        }
+        let progress = *progress;
+
+        if error.is_none() {
+            let reason = self.classify(pkgname);
+            if let Some(pkg) = self
+                .pkg_info_table
+                .data
+                .iter_mut()
+                .find(|pkg| pkg.name() == pkgname)
+            {
+                pkg.reason = reason;
+            }
+        }
+
+        if progress.is_done() {
+            if let DisplayMode::ViewingClusters(_) = self.current_display {
+                self.current_display =
+                    DisplayMode::ViewingClusters(ClusterView::new(self.clusters()));
+            }
+        }
+        self.set_cluster_message(progress.summary());
+    }
+
+    fn set_cluster_message(&mut self, message: String) {
+        if let DisplayMode::ViewingClusters(ref mut view) = self.current_display {
+            view.message = Some(message);
+        }
+    }
+
+    /// Group the FTBFS packages having a log by the signature of their first error. The
+    /// signatures are computed along with the classifications, only new logs are read here.
+    pub fn clusters(&mut self) -> Vec<Cluster> {
+        let pkgnames = self
+            .pkg_info_table
+            .data
+            .iter()
+            .filter(|pkg| pkg.is_ftbfs())
+            .map(|pkg| pkg.name.clone())
+            .collect::<Vec<_>>();
+        let signatures = pkgnames
+            .into_iter()
+            .filter_map(|pkgname| {
+                let signature = self.summarize_log(&pkgname)?.signature.clone()?;
+                Some((pkgname, signature))
+            })
+            .collect::<Vec<_>>();
+        logs::cluster::cluster(signatures)
+    }
+
+    pub fn show_clusters(&mut self) {
+        if let DisplayMode::ViewingPackageStatusTable = self.current_display {
+            let mut view = ClusterView::new(self.clusters());
+            view.message = self.log_download.map(|progress| progress.summary());
+            self.current_display = DisplayMode::ViewingClusters(view);
+        }
+    }
+
+    /// Mark every package of the highlighted cluster and go back to the table, ready for a bulk
+    /// action
+    fn mark_cluster(&mut self) {
+        let pkgnames = match self.current_display {
+            DisplayMode::ViewingClusters(ref view) => match view.selected() {
+                Some(cluster) => cluster.pkgnames.clone(),
+                None => return,
+            },
+            _ => return,
+        };
+        self.pkg_info_table.marked.extend(pkgnames);
+        self.current_display = DisplayMode::ViewingPackageStatusTable;
+    }
+
+    /// Search the downloaded and local build logs for lines matching the `pattern` regex. The
+    /// index is refreshed first if some logs changed.
+    pub fn search_logs(&self, pattern: &str, limit: usize) -> anyhow::Result<Vec<Hit>> {
+        let dir = &self.config.logs.dir;
+        let index = LogIndex::open(&dir.join("index"), &[dir, self.build_queue.dir()])?;
+        index.search(pattern, limit)
+    }
+
+    pub fn show_search(&mut self, pattern: &str) {
+        let view = SearchView::new(pattern, self.search_logs(pattern, SEARCH_LIMIT));
+        self.current_display = DisplayMode::ViewingSearch(view);
+    }
+
+    /// Open the log of the selected search result at the matching line
+    fn show_search_hit(&mut self) {
+        let hit = match self.current_display {
+            DisplayMode::ViewingSearch(ref view) => match view.selected() {
+                Some(hit) => hit.clone(),
+                None => return,
+            },
+            _ => return,
+        };
+
+        let title = hit.path.display().to_string();
+        let view = self.open_log(title, &hit.path, Jump::Line(hit.line));
+        self.current_display = DisplayMode::ViewingLog(view);
+    }
+
+    /// Suggested rebuild order of the FTBFS packages that nobody has claimed yet
+    pub fn rebuild_order(&self) -> RebuildOrder {
+        let names = self
+            .pkg_info_table
+            .data
+            .iter()
+            .filter(|pkg| pkg.is_ftbfs() && !pkg.has_assignee())
+            .map(|pkg| pkg.name())
+            .collect::<Vec<_>>();
+
+        order::rebuild_order(&self.depgraph, &names)
+    }
+
+    pub fn show_rebuild_order(&mut self) {
+        if let DisplayMode::ViewingPackageStatusTable = self.current_display {
+            let view = RebuildOrderView::new(self.rebuild_order());
+            self.current_display = DisplayMode::ViewingRebuildOrder(view);
+        }
+    }
+
+    fn export_rebuild_order(&mut self) {
+        if let DisplayMode::ViewingRebuildOrder(ref mut view) = self.current_display {
+            view.export(&self.config.order.export_path);
+        }
+    }
+
+    /// Switch the package table between showing all packages and outdated packages only
+    pub fn toggle_outdated_filter(&mut self) {
+        if let DisplayMode::ViewingPackageStatusTable = self.current_display {
+            let table = &mut self.pkg_info_table;
+            match table.filter {
+                PkgFilter::Outdated => table.set_filter(PkgFilter::All),
+                PkgFilter::All => table.set_filter(PkgFilter::Outdated),
+            }
+        }
+    }
+
     pub fn key_down(&mut self) {
         use DisplayMode::*;
         let table = &mut self.pkg_info_table;
@@ -259,6 +1326,20 @@ impl App {
             PopUpPstMenu(ref mut menu) => {
                 menu.next();
             }
+            ViewingDependents(ref mut list) => list.next(),
+            ViewingRebuildOrder(ref mut view) => view.list.next(),
+            ViewingClusters(ref mut view) => view.list.next(),
+            ViewingSearch(ref mut view) => view.list.next(),
+            ViewingPatch(ref mut view) | ViewingWarnings(ref mut view) => view.scroll_down(),
+            ViewingLog(ref mut view) => view.scroll_down(),
+            PickingLogDiff(ref mut picker) => picker.list.next(),
+            ViewingLogDiff(ref mut view) => view.scroll_down(),
+            ViewingBuild(id) => {
+                if let Some(build) = self.build_queue.get_mut(*id) {
+                    build.scroll_down()
+                }
+            }
+            ViewingBuildQueue => self.build_queue.next(),
         }
     }
 
@@ -270,20 +1351,125 @@ impl App {
             PopUpPstMenu(ref mut menu) => {
                 menu.previous();
             }
+            ViewingDependents(ref mut list) => list.previous(),
+            ViewingRebuildOrder(ref mut view) => view.list.previous(),
+            ViewingClusters(ref mut view) => view.list.previous(),
+            ViewingSearch(ref mut view) => view.list.previous(),
+            ViewingPatch(ref mut view) | ViewingWarnings(ref mut view) => view.scroll_up(),
+            ViewingLog(ref mut view) => view.scroll_up(),
+            PickingLogDiff(ref mut picker) => picker.list.previous(),
+            ViewingLogDiff(ref mut view) => view.scroll_up(),
+            ViewingBuild(id) => {
+                if let Some(build) = self.build_queue.get_mut(*id) {
+                    build.scroll_up()
+                }
+            }
+            ViewingBuildQueue => self.build_queue.previous(),
         }
     }
 
     pub fn key_begining(&mut self) {
         let table = &mut self.pkg_info_table;
-        if let DisplayMode::ViewingPackageStatusTable = self.current_display {
-            table.beginning()
+        match &mut self.current_display {
+            DisplayMode::ViewingPackageStatusTable => table.beginning(),
+            DisplayMode::ViewingDependents(list) => list.beginning(),
+            DisplayMode::ViewingRebuildOrder(view) => view.list.beginning(),
+            DisplayMode::ViewingClusters(view) => view.list.beginning(),
+            DisplayMode::ViewingSearch(view) => view.list.beginning(),
+            DisplayMode::ViewingPatch(view) | DisplayMode::ViewingWarnings(view) => view.top(),
+            DisplayMode::ViewingLog(view) => view.top(),
+            DisplayMode::PickingLogDiff(picker) => picker.list.beginning(),
+            DisplayMode::ViewingLogDiff(view) => view.top(),
+            DisplayMode::ViewingBuild(id) => {
+                if let Some(build) = self.build_queue.get_mut(*id) {
+                    build.top()
+                }
+            }
+            _ => (),
         }
     }
 
     pub fn key_end(&mut self) {
         let table = &mut self.pkg_info_table;
-        if let DisplayMode::ViewingPackageStatusTable = self.current_display {
-            table.end()
+        match &mut self.current_display {
+            DisplayMode::ViewingPackageStatusTable => table.end(),
+            DisplayMode::ViewingDependents(list) => list.end(),
+            DisplayMode::ViewingRebuildOrder(view) => view.list.end(),
+            DisplayMode::ViewingClusters(view) => view.list.end(),
+            DisplayMode::ViewingSearch(view) => view.list.end(),
+            DisplayMode::ViewingPatch(view) | DisplayMode::ViewingWarnings(view) => view.bottom(),
+            DisplayMode::ViewingLog(view) => view.bottom(),
+            DisplayMode::PickingLogDiff(picker) => picker.list.end(),
+            DisplayMode::ViewingLogDiff(view) => view.bottom(),
+            DisplayMode::ViewingBuild(id) => {
+                if let Some(build) = self.build_queue.get_mut(*id) {
+                    build.bottom()
+                }
+            }
+            _ => (),
         }
     }
 }
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_fetch_data_records_healthy_source() {
+    use crate::req::mock::{MockResponse, MockServer};
+
+    let status = MockServer::start(MockResponse::status(500));
+    // melon answers after felix has failed
+    let melon = MockServer::start(
+        MockResponse::ok(include_str!("../tests/fixtures/melon_pkg.json"))
+            .delayed(Duration::from_millis(200)),
+    );
+    let client = Client::new()
+        .status_url(status.url("/status.htm"))
+        .melon_url(melon.url("/pkg"));
+
+    let mut last_fetch = HashMap::new();
+    assert!(App::fetch_data(client, &mut last_fetch).await.is_err());
+    assert!(last_fetch.contains_key("melon"));
+    assert!(!last_fetch.contains_key("felix"));
+}
+
+#[test]
+fn test_clusters_reuse_log_summaries() {
+    let dir = std::env::temp_dir().join(format!("lzr-test-clusters-{}", std::process::id()));
+    let mut config = Config::default();
+    config.logs.dir = dir.join("logs");
+    config.build.dir = dir.join("builds");
+    std::fs::create_dir_all(&config.logs.dir).unwrap();
+    for pkgname in ["bear", "ghc"] {
+        std::fs::write(
+            logs::cached_log_path(&config.logs.dir, pkgname),
+            "foo.c:12:5: error: implicit declaration of function 'bar'\n",
+        )
+        .unwrap();
+    }
+
+    let mut app = App::new(config);
+    app.pkg_info_table.data = ["bear", "ghc"]
+        .into_iter()
+        .map(|name| {
+            PkgInfoBuilder::default()
+                .name(name.into())
+                .process("FTBFS".into())
+                .build()
+                .unwrap()
+        })
+        .collect();
+    assert_eq!(app.clusters()[0].pkgnames.len(), 2);
+
+    // a log with the same modification time is not read again
+    let path = logs::cached_log_path(&app.config.logs.dir, "bear");
+    let modified = path.metadata().unwrap().modified().unwrap();
+    std::fs::write(&path, "error: something else\n").unwrap();
+    std::fs::File::options()
+        .write(true)
+        .open(&path)
+        .unwrap()
+        .set_modified(modified)
+        .unwrap();
+    assert_eq!(app.clusters()[0].pkgnames.len(), 2);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -1,4 +1,21 @@
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    lazyarchbuild::run().await
+    match std::env::args().nth(1).as_deref() {
+        Some("order") => lazyarchbuild::print_rebuild_order().await,
+        Some("grep") => match std::env::args().nth(2) {
+            Some(pattern) => lazyarchbuild::grep_logs(&pattern),
+            None => anyhow::bail!("usage: lzr grep <pattern>"),
+        },
+        Some("serve") => {
+            let args = std::env::args().skip(2).collect::<Vec<_>>();
+            let listen = match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+                [] => None,
+                ["--listen", listen] => Some(listen),
+                _ => anyhow::bail!("usage: lzr serve [--listen <address:port>]"),
+            };
+            lazyarchbuild::serve(listen).await
+        }
+        Some("watch") => lazyarchbuild::watch().await,
+        _ => lazyarchbuild::run().await,
+    }
 }
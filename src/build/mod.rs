@@ -0,0 +1,355 @@
+pub mod queue;
+pub mod remote;
+
+use crate::{
+    config::{BuildHost, Config},
+    events::Events,
+    types::check_pkgname,
+};
+use std::{
+    os::unix::process::CommandExt,
+    path::PathBuf,
+    process::Stdio,
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, BufReader},
+    sync::oneshot,
+};
+
+type BoxStr = Box<str>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuildStatus {
+    Queued,
+    Running,
+    /// Exit code, `None` when the process is killed
+    Finished(Option<i32>),
+    /// The build could not be prepared or started, the error is the last output line
+    Failed,
+    Cancelled,
+}
+
+/// A build of one package and everything it has printed so far
+#[derive(Debug)]
+pub struct Build {
+    pub id: usize,
+    pub pkgname: BoxStr,
+    /// The package is marked `noqemu` and must be built on a real board
+    pub needs_board: bool,
+    /// Name of the remote host running the build, `None` for this machine
+    pub host: Option<BoxStr>,
+    pub command: String,
+    pub lines: Vec<String>,
+    pub status: BuildStatus,
+    pub started: Instant,
+    pub duration: Option<Duration>,
+    /// Kill the process when fired, only set while running
+    pub cancel: Option<oneshot::Sender<()>>,
+    /// The process is started and hasn't exited yet. A cancelled build keeps its slot until then.
+    pub alive: bool,
+    /// Index of the first visible line when not following the output
+    pub offset: usize,
+    /// Keep the newest line in sight
+    pub follow: bool,
+}
+
+impl Build {
+    pub fn new(id: usize, pkgname: &str, needs_board: bool) -> Self {
+        Self {
+            id,
+            pkgname: pkgname.into(),
+            needs_board,
+            host: None,
+            command: String::new(),
+            lines: Vec::new(),
+            status: BuildStatus::Queued,
+            started: Instant::now(),
+            duration: None,
+            cancel: None,
+            alive: false,
+            offset: 0,
+            follow: true,
+        }
+    }
+
+    pub fn finish(&mut self, code: Option<i32>, duration: Duration) {
+        self.status = BuildStatus::Finished(code);
+        self.duration = Some(duration);
+        self.cancel = None;
+        self.alive = false;
+    }
+
+    /// Record an error that kept the build from starting
+    pub fn fail(&mut self, error: String) {
+        self.lines.push(error);
+        self.status = BuildStatus::Failed;
+        self.duration = Some(Duration::ZERO);
+        self.cancel = None;
+        self.alive = false;
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.status == BuildStatus::Finished(Some(0))
+    }
+
+    /// One line summary like `running 3m on unmatched` or `exit 1 after 12m`
+    pub fn summary(&self) -> String {
+        let summary = self.status_summary();
+        match self.host {
+            Some(ref host) if self.status != BuildStatus::Queued => format!("{summary} on {host}"),
+            _ => summary,
+        }
+    }
+
+    fn status_summary(&self) -> String {
+        match self.status {
+            BuildStatus::Queued => "queued".to_string(),
+            BuildStatus::Running => format!("running {}", format_duration(self.started.elapsed())),
+            BuildStatus::Cancelled => "cancelled".to_string(),
+            BuildStatus::Failed => "failed to start".to_string(),
+            BuildStatus::Finished(code) => {
+                let duration = format_duration(self.duration.unwrap_or_default());
+                match code {
+                    Some(code) => format!("exit {code} after {duration}"),
+                    None => format!("killed after {duration}"),
+                }
+            }
+        }
+    }
+
+    pub fn scroll_down(&mut self) {
+        if self.offset + 1 < self.lines.len() {
+            self.offset += 1;
+        }
+    }
+
+    pub fn scroll_up(&mut self) {
+        self.follow = false;
+        self.offset = self.offset.saturating_sub(1);
+    }
+
+    pub fn top(&mut self) {
+        self.follow = false;
+        self.offset = 0;
+    }
+
+    pub fn bottom(&mut self) {
+        self.follow = true;
+    }
+}
+
+/// Expand the configured build command and working directory for `pkgname`. The name is pasted
+/// into a shell command, so anything outside the Arch package name charset is refused.
+pub fn prepare_command(config: &Config, pkgname: &str) -> anyhow::Result<(String, PathBuf)> {
+    check_pkgname(pkgname)?;
+    let command = config.build.command.replace("{pkgname}", pkgname);
+    let workdir = match config.build.workdir {
+        Some(ref dir) => PathBuf::from(dir.replace("{pkgname}", pkgname)),
+        None => config.patches.scratch_dir.join(pkgname),
+    };
+
+    if !workdir.is_dir() {
+        anyhow::bail!(
+            "build directory {} doesn't exist, prepare a patch for {pkgname} first",
+            workdir.display()
+        );
+    }
+
+    Ok((command, workdir))
+}
+
+/// Expand the configured build command for `pkgname` and wrap it to run on `host`. The process
+/// still runs locally, in the directory uploaded to the host.
+pub fn prepare_remote_command(
+    config: &Config,
+    host: &BuildHost,
+    pkgname: &str,
+) -> anyhow::Result<(String, PathBuf)> {
+    let (command, workdir) = prepare_command(config, pkgname)?;
+    let command =
+        remote::remote_command(&config.build.ssh_program, host, pkgname, &command, &workdir);
+    Ok((command, workdir))
+}
+
+/// Run `command` with `sh -c` in `workdir` and stream its stdout and stderr line by line to the
+/// main loop. A [`Events::BuildFinished`] is sent at the end, or a [`Events::BuildFailed`] when
+/// the process can't be started. The process is killed when
+/// the returned sender is fired or dropped.
+pub fn spawn(
+    id: usize,
+    command: String,
+    workdir: PathBuf,
+    tx: mpsc::Sender<Events>,
+) -> oneshot::Sender<()> {
+    let (cancel, cancelled) = oneshot::channel::<()>();
+    tokio::spawn(async move {
+        let started = Instant::now();
+        // the build gets its own process group, so cancelling it also kills every process the
+        // shell has started
+        let mut process = std::process::Command::new("sh");
+        process
+            .arg("-c")
+            .arg(&command)
+            .current_dir(&workdir)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .process_group(0);
+        let child = tokio::process::Command::from(process)
+            .kill_on_drop(true)
+            .spawn();
+
+        let mut child = match child {
+            Ok(child) => child,
+            Err(err) => {
+                let error = format!("fail to execute `{command}`: {err}");
+                let _ = tx.send(Events::BuildFailed { id, error });
+                return;
+            }
+        };
+
+        let stdout = forward_lines(id, child.stdout.take(), tx.clone());
+        let stderr = forward_lines(id, child.stderr.take(), tx.clone());
+        let code = tokio::select! {
+            status = child.wait() => status.ok().and_then(|status| status.code()),
+            _ = cancelled => {
+                if let Some(pid) = child.id() {
+                    kill_group(pid);
+                }
+                let _ = child.kill().await;
+                None
+            }
+        };
+        let _ = tokio::join!(stdout, stderr);
+
+        let _ = tx.send(Events::BuildFinished {
+            id,
+            code,
+            duration: started.elapsed(),
+        });
+    });
+
+    cancel
+}
+
+/// Send SIGKILL to the process group led by `pid`
+fn kill_group(pid: u32) {
+    // SAFETY: kill(2) only takes plain integers
+    unsafe {
+        libc::kill(-(pid as libc::pid_t), libc::SIGKILL);
+    }
+}
+
+fn forward_lines<R>(
+    id: usize,
+    reader: Option<R>,
+    tx: mpsc::Sender<Events>,
+) -> tokio::task::JoinHandle<()>
+where
+    R: AsyncRead + Unpin + Send + 'static,
+{
+    tokio::spawn(async move {
+        let reader = match reader {
+            Some(reader) => reader,
+            None => return,
+        };
+        let mut lines = BufReader::new(reader).lines();
+        while let Ok(Some(mut line)) = lines.next_line().await {
+            // remote builds print through a terminal
+            if line.ends_with('\r') {
+                line.pop();
+            }
+            if tx.send(Events::BuildOutput { id, line }).is_err() {
+                break;
+            }
+        }
+    })
+}
+
+/// Format a duration the short way humans read it: `45s`, `12m`, `1h 3m`
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match secs {
+        0..=59 => format!("{secs}s"),
+        60..=3599 => format!("{}m", secs / 60),
+        _ => format!("{}h {}m", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+#[test]
+fn test_format_duration() {
+    assert_eq!(format_duration(Duration::from_secs(45)), "45s");
+    assert_eq!(format_duration(Duration::from_secs(12 * 60 + 5)), "12m");
+    assert_eq!(format_duration(Duration::from_secs(3600 + 3 * 60)), "1h 3m");
+}
+
+#[test]
+fn test_prepare_command_rejects_invalid_name() {
+    let mut config = Config::default();
+    config.build.workdir = Some(std::env::temp_dir().to_string_lossy().into());
+
+    assert!(prepare_command(&config, "bash").is_ok());
+    assert!(prepare_command(&config, "x;rm -rf ~").is_err());
+    assert!(prepare_command(&config, "$(id)").is_err());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_spawn_streams_output() {
+    let (tx, rx) = mpsc::channel();
+    let _cancel = spawn(
+        7,
+        "echo out; echo err >&2; exit 3".to_string(),
+        std::env::temp_dir(),
+        tx,
+    );
+
+    let mut lines = Vec::new();
+    let code = loop {
+        match rx.recv_timeout(Duration::from_secs(10)).unwrap() {
+            Events::BuildOutput { id, line } => {
+                assert_eq!(id, 7);
+                lines.push(line);
+            }
+            Events::BuildFinished { id, code, .. } => {
+                assert_eq!(id, 7);
+                break code;
+            }
+            _ => unreachable!(),
+        }
+    };
+
+    lines.sort();
+    assert_eq!(lines, ["err", "out"]);
+    assert_eq!(code, Some(3));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_cancel_kills_pipeline() {
+    let (tx, rx) = mpsc::channel();
+    let cancel = spawn(
+        3,
+        "sh -c 'echo $$; exec sleep 30' | cat".to_string(),
+        std::env::temp_dir(),
+        tx,
+    );
+
+    let pid = match rx.recv_timeout(Duration::from_secs(10)).unwrap() {
+        Events::BuildOutput { line, .. } => line,
+        _ => unreachable!(),
+    };
+    cancel.send(()).unwrap();
+
+    // the output pipe is only closed once every process of the pipeline is gone
+    loop {
+        match rx.recv_timeout(Duration::from_secs(10)).unwrap() {
+            Events::BuildFinished { code, .. } => break assert_eq!(code, None),
+            Events::BuildOutput { .. } => continue,
+            _ => unreachable!(),
+        }
+    }
+    let alive = std::fs::read_to_string(format!("/proc/{pid}/stat"))
+        .is_ok_and(|stat| !stat.contains(") Z "));
+    assert!(!alive, "sleep {pid} still runs");
+}
@@ -0,0 +1,439 @@
+use super::{format_duration, remote::BOARD_LABEL, Build, BuildStatus};
+use crate::{config::Config, events::Events};
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::mpsc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tui::widgets::ListState;
+
+type BoxStr = Box<str>;
+
+/// Outcome of the latest build of a package, kept across restarts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BuildRecord {
+    pub success: bool,
+    /// Build duration in seconds
+    pub duration: u64,
+    /// Unix timestamp of the end of the build
+    pub finished_at: u64,
+    /// Remote host the package was built on, `None` for this machine
+    #[serde(default)]
+    pub host: Option<BoxStr>,
+}
+
+impl BuildRecord {
+    /// Short form for the package table, like `local: OK 12m` or `unmatched: FAIL 2h 5m`
+    pub fn summary(&self) -> String {
+        let result = if self.success { "OK" } else { "FAIL" };
+        let duration = format_duration(Duration::from_secs(self.duration));
+        let host = self.host.as_deref().unwrap_or("local");
+        format!("{host}: {result} {duration}")
+    }
+}
+
+/// Where a queued job may start
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Slot {
+    Local,
+    /// Index into `build.hosts`
+    Host(usize),
+}
+
+/// Builds waiting, running or done in this session, plus the recorded results of previous
+/// sessions.
+pub struct BuildQueue {
+    pub jobs: Vec<Build>,
+    pub cursor: ListState,
+    pub results: HashMap<BoxStr, BuildRecord>,
+    /// Directory holding `results.json` and the log of each finished build
+    dir: PathBuf,
+    next_id: usize,
+}
+
+impl BuildQueue {
+    /// Create an empty queue and load previous results from `dir`. A missing or broken results
+    /// file is treated as empty.
+    pub fn new(dir: PathBuf) -> Self {
+        let results = std::fs::read(dir.join("results.json"))
+            .ok()
+            .and_then(|content| serde_json::from_slice(&content).ok())
+            .unwrap_or_default();
+
+        Self {
+            jobs: Vec::new(),
+            cursor: ListState::default(),
+            results,
+            dir,
+            next_id: 0,
+        }
+    }
+
+    /// Queue a build of `pkgname` and return the job id. `needs_board` keeps the job away from
+    /// qemu when a board host is configured.
+    pub fn push(&mut self, pkgname: &str, needs_board: bool) -> usize {
+        self.next_id += 1;
+        self.jobs
+            .push(Build::new(self.next_id, pkgname, needs_board));
+        if self.cursor.selected().is_none() {
+            self.cursor.select(Some(0));
+        }
+        self.next_id
+    }
+
+    pub fn get(&self, id: usize) -> Option<&Build> {
+        self.jobs.iter().find(|job| job.id == id)
+    }
+
+    pub fn get_mut(&mut self, id: usize) -> Option<&mut Build> {
+        self.jobs.iter_mut().find(|job| job.id == id)
+    }
+
+    pub fn selected(&self) -> Option<&Build> {
+        self.cursor.selected().and_then(|i| self.jobs.get(i))
+    }
+
+    pub fn running(&self) -> usize {
+        self.jobs
+            .iter()
+            .filter(|job| job.status == BuildStatus::Running)
+            .count()
+    }
+
+    /// Start queued jobs in order on every free slot: up to `build.max_parallel` jobs on this
+    /// machine, then up to `jobs` on each of `build.hosts`.
+    pub fn schedule(&mut self, config: &Config, tx: &mpsc::Sender<Events>) {
+        let hosts = &config.build.hosts;
+        // a cancelled job holds its slot until the process has exited, a remote build may still
+        // be winding down on the host
+        let is_running_on = |job: &Build, host: Option<&str>| {
+            (job.status == BuildStatus::Running || job.alive) && job.host.as_deref() == host
+        };
+        let mut local = self
+            .jobs
+            .iter()
+            .filter(|job| is_running_on(job, None))
+            .count();
+        let mut remote = hosts
+            .iter()
+            .map(|host| {
+                self.jobs
+                    .iter()
+                    .filter(|job| is_running_on(job, Some(&host.name)))
+                    .count()
+            })
+            .collect::<Vec<_>>();
+
+        for job in self.jobs.iter_mut() {
+            if job.status != BuildStatus::Queued {
+                continue;
+            }
+            let slot = match pick_slot(config, job.needs_board, local, &remote) {
+                Some(slot) => slot,
+                None => continue,
+            };
+
+            job.started = std::time::Instant::now();
+            let prepared = match slot {
+                Slot::Local => {
+                    job.host = None;
+                    super::prepare_command(config, &job.pkgname)
+                }
+                Slot::Host(i) => {
+                    job.host = Some(hosts[i].name.clone());
+                    super::prepare_remote_command(config, &hosts[i], &job.pkgname)
+                }
+            };
+            match prepared {
+                Ok((command, workdir)) => {
+                    job.command = command.clone();
+                    job.status = BuildStatus::Running;
+                    job.alive = true;
+                    job.cancel = Some(super::spawn(job.id, command, workdir, tx.clone()));
+                    match slot {
+                        Slot::Local => local += 1,
+                        Slot::Host(i) => remote[i] += 1,
+                    }
+                }
+                Err(err) => job.fail(format!("{err:#}")),
+            }
+        }
+    }
+
+    pub fn output(&mut self, id: usize, line: String) {
+        if let Some(job) = self.get_mut(id) {
+            if job.status == BuildStatus::Running {
+                job.lines.push(line);
+            }
+        }
+    }
+
+    /// Record a running job whose process can't be started
+    pub fn fail(&mut self, id: usize, error: String) {
+        if let Some(job) = self.get_mut(id) {
+            job.alive = false;
+            if job.status == BuildStatus::Running {
+                job.fail(error);
+            }
+        }
+    }
+
+    /// Record the end of a job. Return the package name and its new result, if the job was
+    /// still running.
+    pub fn finish(
+        &mut self,
+        id: usize,
+        code: Option<i32>,
+        duration: Duration,
+    ) -> Option<(BoxStr, BuildRecord)> {
+        let job = self.get_mut(id)?;
+        job.alive = false;
+        if job.status != BuildStatus::Running {
+            return None;
+        }
+        job.finish(code, duration);
+
+        let record = BuildRecord {
+            success: job.is_success(),
+            duration: duration.as_secs(),
+            host: job.host.clone(),
+            finished_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        };
+        let pkgname = job.pkgname.clone();
+        let log = job.lines.join("\n");
+
+        // losing the history is not worth interrupting the user
+        let _ = self.save_log(&pkgname, &log);
+        self.results.insert(pkgname.clone(), record.clone());
+        let _ = self.save_results();
+
+        Some((pkgname, record))
+    }
+
+    /// Cancel the selected job. Queued jobs are dropped from the schedule and running jobs are
+    /// killed.
+    pub fn cancel_selected(&mut self) {
+        let idx = match self.cursor.selected() {
+            Some(idx) => idx,
+            None => return,
+        };
+        if let Some(job) = self.jobs.get_mut(idx) {
+            if matches!(job.status, BuildStatus::Queued | BuildStatus::Running) {
+                if let Some(cancel) = job.cancel.take() {
+                    let _ = cancel.send(());
+                }
+                job.duration = Some(job.started.elapsed());
+                job.status = BuildStatus::Cancelled;
+            }
+        }
+    }
+
+    /// Put the selected job back in the queue if it has ended and its process has exited
+    pub fn retry_selected(&mut self) {
+        let idx = match self.cursor.selected() {
+            Some(idx) => idx,
+            None => return,
+        };
+        let ended = self.jobs.get(idx).map(|job| {
+            !job.alive
+                && matches!(
+                    job.status,
+                    BuildStatus::Finished(_) | BuildStatus::Failed | BuildStatus::Cancelled
+                )
+        });
+        if ended == Some(true) {
+            self.next_id += 1;
+            let job = &self.jobs[idx];
+            self.jobs[idx] = Build::new(self.next_id, &job.pkgname.clone(), job.needs_board);
+        }
+    }
+
+    pub fn next(&mut self) {
+        if self.jobs.is_empty() {
+            return;
+        }
+        let i = match self.cursor.selected() {
+            Some(i) if i + 1 < self.jobs.len() => i + 1,
+            _ => 0,
+        };
+        self.cursor.select(Some(i));
+    }
+
+    pub fn previous(&mut self) {
+        if self.jobs.is_empty() {
+            return;
+        }
+        let i = match self.cursor.selected() {
+            Some(0) | None => self.jobs.len() - 1,
+            Some(i) => i - 1,
+        };
+        self.cursor.select(Some(i));
+    }
+
+    /// Directory holding the saved logs
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Path of the saved log of the latest local build of `pkgname`
+    pub fn log_path(&self, pkgname: &str) -> PathBuf {
+        self.dir.join(format!("{pkgname}.log"))
+    }
+
+    fn save_log(&self, pkgname: &str, log: &str) -> anyhow::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let path = self.log_path(pkgname);
+        std::fs::write(&path, log).with_context(|| format!("fail to write {}", path.display()))
+    }
+
+    fn save_results(&self) -> anyhow::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let path = self.dir.join("results.json");
+        let content = serde_json::to_vec_pretty(&self.results)?;
+        std::fs::write(&path, content).with_context(|| format!("fail to write {}", path.display()))
+    }
+}
+
+/// Choose where a queued job starts given the number of jobs running on this machine and on each
+/// host. Jobs needing a board skip this machine and the unlabelled hosts, unless no board host is
+/// configured at all.
+fn pick_slot(config: &Config, needs_board: bool, local: usize, remote: &[usize]) -> Option<Slot> {
+    let hosts = &config.build.hosts;
+    let board_only = needs_board && hosts.iter().any(|host| host.has_label(BOARD_LABEL));
+
+    if !board_only && local < config.build.max_parallel.max(1) {
+        return Some(Slot::Local);
+    }
+    hosts
+        .iter()
+        .zip(remote)
+        .position(|(host, &running)| {
+            running < host.jobs.max(1) && (!board_only || host.has_label(BOARD_LABEL))
+        })
+        .map(Slot::Host)
+}
+
+#[test]
+fn test_results_persist() {
+    let dir = std::env::temp_dir().join(format!("lzr-test-queue-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let mut queue = BuildQueue::new(dir.clone());
+    let id = queue.push("bash", false);
+    queue.get_mut(id).unwrap().status = BuildStatus::Running;
+    queue.output(id, "==> Finished making: bash".to_string());
+    let (pkgname, record) = queue
+        .finish(id, Some(0), Duration::from_secs(12 * 60))
+        .unwrap();
+    assert_eq!(pkgname.as_ref(), "bash");
+    assert_eq!(record.summary(), "local: OK 12m");
+
+    let reloaded = BuildQueue::new(dir.clone());
+    assert!(reloaded.results["bash"].success);
+    assert_eq!(
+        std::fs::read_to_string(reloaded.log_path("bash")).unwrap(),
+        "==> Finished making: bash"
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_cancel_and_retry() {
+    let dir = std::env::temp_dir().join(format!("lzr-test-retry-{}", std::process::id()));
+    let mut queue = BuildQueue::new(dir);
+    let id = queue.push("zlib", true);
+
+    queue.cancel_selected();
+    assert_eq!(queue.get(id).unwrap().status, BuildStatus::Cancelled);
+    // a late exit notification of a cancelled job is ignored
+    assert!(queue.finish(id, None, Duration::ZERO).is_none());
+
+    queue.retry_selected();
+    assert!(queue.get(id).is_none());
+    assert_eq!(queue.selected().unwrap().status, BuildStatus::Queued);
+    assert!(queue.selected().unwrap().needs_board);
+}
+
+#[test]
+fn test_prepare_error_fails_job() {
+    let dir = std::env::temp_dir().join(format!("lzr-test-fail-{}", std::process::id()));
+    let mut config = Config::default();
+    config.build.workdir = Some(dir.join("missing").to_string_lossy().into());
+    let (tx, _rx) = mpsc::channel();
+
+    let mut queue = BuildQueue::new(dir);
+    let id = queue.push("bash", false);
+    queue.schedule(&config, &tx);
+
+    let job = queue.get(id).unwrap();
+    assert_eq!(job.status, BuildStatus::Failed);
+    assert_eq!(job.summary(), "failed to start");
+    assert!(job.lines[0].contains("doesn't exist"));
+}
+
+#[test]
+fn test_cancelled_job_holds_slot_until_exit() {
+    let dir = std::env::temp_dir().join(format!("lzr-test-slot-{}", std::process::id()));
+    let config = Config::default();
+    let (tx, _rx) = mpsc::channel();
+
+    let mut queue = BuildQueue::new(dir);
+    let first = queue.push("bash", false);
+    let second = queue.push("zlib", false);
+    let job = queue.get_mut(first).unwrap();
+    job.status = BuildStatus::Running;
+    job.alive = true;
+
+    queue.cancel_selected();
+    queue.schedule(&config, &tx);
+    assert_eq!(queue.get(second).unwrap().status, BuildStatus::Queued);
+    // not retried while the old process may still run
+    queue.retry_selected();
+    assert!(queue.get(first).is_some());
+
+    assert!(queue.finish(first, None, Duration::ZERO).is_none());
+    queue.schedule(&config, &tx);
+    assert_ne!(queue.get(second).unwrap().status, BuildStatus::Queued);
+}
+
+#[test]
+fn test_pick_slot() {
+    let mut config: Config = toml::from_str(
+        r#"
+        [build]
+        max_parallel = 1
+
+        [[build.hosts]]
+        name = "qemu-box"
+        ssh = "qemu-box"
+        workdir = "/srv/build"
+
+        [[build.hosts]]
+        name = "unmatched"
+        ssh = "unmatched"
+        workdir = "/srv/build"
+        labels = ["board"]
+        "#,
+    )
+    .unwrap();
+
+    assert_eq!(pick_slot(&config, false, 0, &[0, 0]), Some(Slot::Local));
+    assert_eq!(pick_slot(&config, false, 1, &[0, 0]), Some(Slot::Host(0)));
+    assert_eq!(pick_slot(&config, false, 1, &[1, 0]), Some(Slot::Host(1)));
+    assert_eq!(pick_slot(&config, false, 1, &[1, 1]), None);
+
+    // noqemu packages wait for the board
+    assert_eq!(pick_slot(&config, true, 0, &[0, 0]), Some(Slot::Host(1)));
+    assert_eq!(pick_slot(&config, true, 0, &[0, 1]), None);
+
+    // and are built anywhere when there is no board to wait for
+    config.build.hosts.pop();
+    assert_eq!(pick_slot(&config, true, 0, &[0]), Some(Slot::Local));
+}
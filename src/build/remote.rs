@@ -0,0 +1,123 @@
+use crate::config::BuildHost;
+use std::path::Path;
+
+/// Label of the hosts that are real RISC-V boards
+pub const BOARD_LABEL: &str = "board";
+
+/// Quote `s` for a POSIX shell
+pub fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+/// Build a local shell command that uploads `local_workdir` to the host, then runs `command` there
+/// and streams the output back through `ssh_program`. The build step gets a remote terminal with
+/// `-tt`, so the remote build is hung up and stops once the local ssh process is killed.
+pub fn remote_command(
+    ssh_program: &str,
+    host: &BuildHost,
+    pkgname: &str,
+    command: &str,
+    local_workdir: &Path,
+) -> String {
+    let remote_workdir = format!("{}/{}", host.workdir.trim_end_matches('/'), pkgname);
+    let upload_script = format!(
+        "mkdir -p {dir} && tar -C {dir} -xf -",
+        dir = shell_quote(&remote_workdir),
+    );
+    let build_script = format!("cd {dir} && {command}", dir = shell_quote(&remote_workdir));
+
+    format!(
+        "tar -C {local} -cf - . | {ssh_program} {target} {upload} && \
+         exec {ssh_program} -tt {target} {build}",
+        local = shell_quote(&local_workdir.to_string_lossy()),
+        target = shell_quote(&host.ssh),
+        upload = shell_quote(&upload_script),
+        build = shell_quote(&build_script),
+    )
+}
+
+#[cfg(test)]
+fn host(ssh: &str, workdir: &str) -> BuildHost {
+    BuildHost {
+        name: "test".into(),
+        ssh: ssh.into(),
+        workdir: workdir.into(),
+        labels: vec![BOARD_LABEL.into()],
+        jobs: 1,
+    }
+}
+
+#[test]
+fn test_shell_quote() {
+    assert_eq!(shell_quote("plain"), "'plain'");
+    assert_eq!(shell_quote("it's"), r"'it'\''s'");
+}
+
+/// Run the whole upload, build and stream pipeline with a stand-in for ssh that executes the
+/// remote script locally.
+#[test]
+fn test_remote_command_pipeline() {
+    let root = std::env::temp_dir().join(format!("lzr-test-remote-{}", std::process::id()));
+    let local = root.join("local");
+    let remote = root.join("remote");
+    std::fs::create_dir_all(&local).unwrap();
+    std::fs::write(local.join("PKGBUILD"), "pkgname=foo\n").unwrap();
+
+    let fake_ssh = root.join("fake-ssh");
+    std::fs::write(
+        &fake_ssh,
+        "#!/bin/sh\n[ \"$1\" = -tt ] && shift\nshift\nexec sh -c \"$1\"\n",
+    )
+    .unwrap();
+    std::process::Command::new("chmod")
+        .arg("+x")
+        .arg(&fake_ssh)
+        .status()
+        .unwrap();
+
+    let command = remote_command(
+        &fake_ssh.to_string_lossy(),
+        &host("builder@localhost", &remote.to_string_lossy()),
+        "foo",
+        "cat PKGBUILD",
+        &local,
+    );
+    let output = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(&command)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "pkgname=foo\n");
+    assert!(remote.join("foo").join("PKGBUILD").is_file());
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+/// Same as above with the real ssh binary, needs a running sshd accepting key login on localhost
+#[test]
+#[ignore]
+fn test_remote_command_localhost() {
+    let root = std::env::temp_dir().join(format!("lzr-test-ssh-{}", std::process::id()));
+    let local = root.join("local");
+    std::fs::create_dir_all(&local).unwrap();
+    std::fs::write(local.join("PKGBUILD"), "pkgname=foo\n").unwrap();
+
+    let command = remote_command(
+        "ssh",
+        &host("localhost", &root.join("remote").to_string_lossy()),
+        "foo",
+        "cat PKGBUILD",
+        &local,
+    );
+    let output = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(&command)
+        .output()
+        .unwrap();
+
+    // the remote terminal ends lines with \r\n
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "pkgname=foo\r\n");
+    std::fs::remove_dir_all(&root).unwrap();
+}
@@ -8,7 +8,13 @@ use tui::{
     Frame,
 };
 
-use crate::component::{self, menu::PopUpMenu};
+use crate::{
+    build::{queue::BuildQueue, Build},
+    component::{
+        self, clusters::ClusterView, logdiff::DiffView, logview::LogView, menu::PopUpMenu,
+        order::RebuildOrderView, packages::PkgColumn, pkglist::PkgList, textview::TextView,
+    },
+};
 
 pub fn draw_welcome_page<B: Backend>(terminal: &mut terminal::Terminal<B>) -> anyhow::Result<()> {
     terminal.draw(|frame| {
@@ -69,6 +75,20 @@ pub fn draw_welcome_page<B: Backend>(terminal: &mut terminal::Terminal<B>) -> an
     Ok(())
 }
 
+const HIGHLIGHT_SYMBOL: &str = ">> ";
+
+const PKG_COLUMN_WIDTHS: [Constraint; 9] = [
+    Constraint::Percentage(15),
+    Constraint::Percentage(10),
+    Constraint::Percentage(15),
+    Constraint::Length(16),
+    Constraint::Min(12),
+    Constraint::Percentage(15),
+    Constraint::Length(6),
+    Constraint::Percentage(10),
+    Constraint::Length(10),
+];
+
 pub fn draw_pkg_table_frame<B: Backend>(
     frame: &mut Frame<B>,
     data: &mut component::packages::PkgInfoTable,
@@ -78,21 +98,80 @@ pub fn draw_pkg_table_frame<B: Backend>(
         .margin(1)
         .split(frame.size());
 
-    let title = vec![
-        widgets::Cell::from("Pkgname").style(data.style.title),
-        widgets::Cell::from("Assignee").style(data.style.title),
-        widgets::Cell::from("Marks").style(data.style.title),
-    ];
+    let title = PkgColumn::ALL
+        .iter()
+        .map(|column| widgets::Cell::from(data.header(*column)).style(data.style.title))
+        .collect::<Vec<_>>();
     let header = widgets::Row::new(title).style(data.style.row).height(1);
 
-    let rows = data.data.iter().map(|pkg| {
-        let pkg = vec![
-            widgets::Cell::from(pkg.name()),
-            widgets::Cell::from(pkg.assignee()),
-            widgets::Cell::from(pkg.marks().join(" ")),
-        ];
-        widgets::Row::new(pkg).height(1)
-    });
+    // resolve the column widths here, so a click can be mapped back to the column under it
+    let inner = widgets::Block::default()
+        .borders(widgets::Borders::ALL)
+        .inner(layout[0]);
+    let highlight = if data.cursor.selected().is_some() {
+        HIGHLIGHT_SYMBOL.len() as u16
+    } else {
+        0
+    };
+    let mut constraints = vec![Constraint::Length(highlight)];
+    for constraint in PKG_COLUMN_WIDTHS {
+        constraints.push(constraint);
+        constraints.push(Constraint::Length(1));
+    }
+    constraints.pop();
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(constraints)
+        .split(Rect::new(0, 0, inner.width, 1));
+    let widths = chunks[1..]
+        .iter()
+        .step_by(2)
+        .map(|chunk| Constraint::Length(chunk.width))
+        .collect::<Vec<_>>();
+
+    let mut x = inner.x + highlight.min(inner.width);
+    data.layout.columns.clear();
+    for (column, chunk) in PkgColumn::ALL.iter().zip(chunks[1..].iter().step_by(2)) {
+        data.layout.columns.push((*column, x, chunk.width));
+        x += chunk.width + 1;
+    }
+    data.layout.header = inner.y;
+    data.layout.rows = Rect::new(
+        inner.x,
+        inner.y + 1,
+        inner.width,
+        inner.height.saturating_sub(1),
+    );
+    data.offset = component::follow_cursor(
+        data.offset,
+        data.cursor.selected(),
+        data.visible_len(),
+        data.layout.rows.height as usize,
+    );
+
+    // rows own their content so the cursor can be borrowed mutably while rendering
+    let rows = data
+        .visible()
+        .map(|pkg| {
+            let name = if data.is_marked(pkg) {
+                format!("* {}", pkg.name())
+            } else {
+                pkg.name().to_string()
+            };
+            let pkg = vec![
+                widgets::Cell::from(name),
+                widgets::Cell::from(pkg.current_process().to_string()),
+                widgets::Cell::from(pkg.reason()),
+                widgets::Cell::from(pkg.local_build()),
+                widgets::Cell::from(pkg.assignee().to_string()),
+                widgets::Cell::from(pkg.marks().join(" ")),
+                widgets::Cell::from(pkg.blocks().to_string()),
+                widgets::Cell::from(pkg.outdated()),
+                widgets::Cell::from(pkg.patch_status().to_string()),
+            ];
+            widgets::Row::new(pkg).height(1)
+        })
+        .collect::<Vec<_>>();
 
     let table = widgets::Table::new(rows)
         .header(header)
@@ -102,12 +181,8 @@ pub fn draw_pkg_table_frame<B: Backend>(
                 .title(data.title()),
         )
         .highlight_style(data.style.selected)
-        .highlight_symbol(">> ")
-        .widths(&[
-            Constraint::Percentage(35),
-            Constraint::Min(20),
-            Constraint::Percentage(45),
-        ]);
+        .highlight_symbol(HIGHLIGHT_SYMBOL)
+        .widths(&widths);
 
     frame.render_stateful_widget(table, layout[0], &mut data.cursor);
 }
@@ -139,9 +214,414 @@ fn build_centered_rect(x: u16, y: u16, r: Rect) -> Rect {
         .split(popup[1])[1]
 }
 
-pub fn draw_popup_menu_frame<B: Backend>(frame: &mut Frame<B>, menu: &PopUpMenu) {
-    let menu = menu.to_renderable();
+pub fn draw_popup_menu_frame<B: Backend>(frame: &mut Frame<B>, menu: &mut PopUpMenu) {
+    let list = menu.to_renderable();
     let draw_area = build_centered_rect(60, 20, frame.size());
+    menu.set_area(draw_area);
     frame.render_widget(Clear, draw_area);
-    frame.render_widget(menu, draw_area);
+    frame.render_stateful_widget(list, draw_area, &mut menu.state);
+}
+
+pub fn draw_pkg_list_frame<B: Backend>(frame: &mut Frame<B>, list: &mut PkgList) {
+    let layout = Layout::default()
+        .constraints([Constraint::Percentage(100)].as_ref())
+        .margin(1)
+        .split(frame.size());
+
+    let widget = list.to_renderable();
+    frame.render_stateful_widget(widget, layout[0], &mut list.state);
+}
+
+pub fn draw_rebuild_order_frame<B: Backend>(frame: &mut Frame<B>, view: &mut RebuildOrderView) {
+    let mut lines = view
+        .cycles
+        .iter()
+        .map(|cycle| Spans::from(format!("Cycle: {}", cycle.join(" <-> "))))
+        .collect::<Vec<_>>();
+    if let Some(ref message) = view.message {
+        lines.push(Spans::from(Span::styled(
+            message.clone(),
+            Style::default().add_modifier(Modifier::BOLD),
+        )));
+    }
+
+    let layout = Layout::default()
+        .constraints(
+            [
+                Constraint::Min(3),
+                Constraint::Length(lines.len() as u16 + 2),
+            ]
+            .as_ref(),
+        )
+        .margin(1)
+        .split(frame.size());
+
+    let widget = view.list.to_renderable();
+    frame.render_stateful_widget(widget, layout[0], &mut view.list.state);
+
+    let title = format!("Dependency cycles ({})", view.cycles.len());
+    let paragraph = Paragraph::new(lines)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .wrap(widgets::Wrap { trim: true });
+    frame.render_widget(paragraph, layout[1]);
+}
+
+pub fn draw_clusters_frame<B: Backend>(frame: &mut Frame<B>, view: &mut ClusterView) {
+    let message_height = if view.message.is_some() { 3 } else { 0 };
+    let rows = Layout::default()
+        .constraints([Constraint::Min(3), Constraint::Length(message_height)].as_ref())
+        .margin(1)
+        .split(frame.size());
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(70), Constraint::Percentage(30)].as_ref())
+        .split(rows[0]);
+
+    let widget = view.list.to_renderable();
+    frame.render_stateful_widget(widget, columns[0], &mut view.list.state);
+
+    let members = view
+        .selected()
+        .map(|cluster| {
+            cluster
+                .pkgnames
+                .iter()
+                .map(|name| ListItem::new(name.to_string()))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    let list = List::new(members).block(Block::default().borders(Borders::ALL).title("Packages"));
+    frame.render_widget(list, columns[1]);
+
+    if let Some(ref message) = view.message {
+        let paragraph =
+            Paragraph::new(message.clone()).block(Block::default().borders(Borders::ALL));
+        frame.render_widget(paragraph, rows[1]);
+    }
+}
+
+/// Draw the search pattern being typed on the last line of the screen
+pub fn draw_search_prompt<B: Backend>(frame: &mut Frame<B>, query: &str) {
+    let size = frame.size();
+    if size.height == 0 {
+        return;
+    }
+    let area = Rect::new(size.x, size.y + size.height - 1, size.width, 1);
+    let prompt = Paragraph::new(format!("/{query}"))
+        .style(Style::default().add_modifier(Modifier::REVERSED));
+    frame.render_widget(widgets::Clear, area);
+    frame.render_widget(prompt, area);
+}
+
+/// Draw the keys of the most useful actions on the last line
+pub fn draw_hints_bar<B: Backend>(frame: &mut Frame<B>, hints: &str) {
+    let size = frame.size();
+    if size.height == 0 {
+        return;
+    }
+    let area = Rect::new(size.x, size.y + size.height - 1, size.width, 1);
+    let hints =
+        Paragraph::new(format!(" {hints}")).style(Style::default().add_modifier(Modifier::DIM));
+    frame.render_widget(hints, area);
+}
+
+/// Draw an error on the last line, in place of the key hints
+pub fn draw_error_bar<B: Backend>(frame: &mut Frame<B>, error: &str) {
+    let size = frame.size();
+    if size.height == 0 {
+        return;
+    }
+    let area = Rect::new(size.x, size.y + size.height - 1, size.width, 1);
+    let error =
+        Paragraph::new(format!(" {error}")).style(Style::default().fg(tui::style::Color::Red));
+    frame.render_widget(error, area);
+}
+
+/// Draw the key bindings over the current display
+pub fn draw_help_overlay<B: Backend>(frame: &mut Frame<B>, help: &TextView) {
+    let area = build_centered_rect(80, 80, frame.size());
+    frame.render_widget(Clear, area);
+    frame.render_widget(help.to_renderable(), area);
+}
+
+/// Draw the keys typed so far of a longer key sequence at the right of the last line
+pub fn draw_pending_keys<B: Backend>(frame: &mut Frame<B>, keys: &str) {
+    let size = frame.size();
+    let width = (keys.chars().count() as u16 + 2).min(size.width);
+    if size.height == 0 || width == 0 {
+        return;
+    }
+    let area = Rect::new(
+        size.x + size.width - width,
+        size.y + size.height - 1,
+        width,
+        1,
+    );
+    let keys = Paragraph::new(format!(" {keys} "))
+        .style(Style::default().add_modifier(Modifier::REVERSED));
+    frame.render_widget(widgets::Clear, area);
+    frame.render_widget(keys, area);
+}
+
+pub fn draw_log_frame<B: Backend>(frame: &mut Frame<B>, view: &mut LogView) {
+    let layout = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(25), Constraint::Percentage(75)].as_ref())
+        .margin(1)
+        .split(frame.size());
+
+    let (outline, mut state) = view.outline_renderable();
+    frame.render_stateful_widget(outline, layout[0], &mut state);
+
+    // minus the borders
+    let height = layout[1].height.saturating_sub(2) as usize;
+    frame.render_widget(view.render(height), layout[1]);
+}
+
+pub fn draw_log_diff_frame<B: Backend>(frame: &mut Frame<B>, view: &DiffView) {
+    let layout = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+        .margin(1)
+        .split(frame.size());
+
+    // minus the borders
+    let height = layout[0].height.saturating_sub(2) as usize;
+    let (left, right) = view.render(height);
+    frame.render_widget(left, layout[0]);
+    frame.render_widget(right, layout[1]);
+}
+
+pub fn draw_text_view_frame<B: Backend>(frame: &mut Frame<B>, view: &TextView) {
+    let layout = Layout::default()
+        .constraints([Constraint::Percentage(100)].as_ref())
+        .margin(1)
+        .split(frame.size());
+
+    frame.render_widget(view.to_renderable(), layout[0]);
+}
+
+pub fn draw_build_frame<B: Backend>(frame: &mut Frame<B>, build: Option<&mut Build>) {
+    let build = match build {
+        Some(build) => build,
+        None => return,
+    };
+
+    let layout = Layout::default()
+        .constraints([Constraint::Percentage(100)].as_ref())
+        .margin(1)
+        .split(frame.size());
+
+    // two rows are taken by the borders
+    let height = layout[0].height.saturating_sub(2) as usize;
+    if build.follow {
+        build.offset = build.lines.len().saturating_sub(height);
+    }
+
+    let lines = build
+        .lines
+        .iter()
+        .skip(build.offset)
+        .take(height)
+        .map(|line| Spans::from(line.clone()))
+        .collect::<Vec<_>>();
+    let title = format!(
+        "Build {} ({}) `{}`",
+        build.pkgname,
+        build.summary(),
+        build.command
+    );
+    let paragraph =
+        Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(title));
+    frame.render_widget(paragraph, layout[0]);
+}
+
+pub fn draw_build_queue_frame<B: Backend>(frame: &mut Frame<B>, queue: &mut BuildQueue) {
+    let layout = Layout::default()
+        .constraints([Constraint::Percentage(100)].as_ref())
+        .margin(1)
+        .split(frame.size());
+
+    let items = queue
+        .jobs
+        .iter()
+        .map(|job| ListItem::new(format!("{:<40} {}", job.pkgname, job.summary())))
+        .collect::<Vec<_>>();
+    let title = format!(
+        "Builds ({} running, {} total)",
+        queue.running(),
+        queue.jobs.len()
+    );
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+        .highlight_symbol(">> ");
+    frame.render_stateful_widget(list, layout[0], &mut queue.cursor);
+}
+
+/// Draw on a `width` x `height` test terminal and return the screen
+#[cfg(test)]
+pub(crate) fn draw_test(
+    width: u16,
+    height: u16,
+    draw: impl FnOnce(&mut Frame<tui::backend::TestBackend>),
+) -> tui::buffer::Buffer {
+    let mut terminal = terminal::Terminal::new(tui::backend::TestBackend::new(width, height))
+        .expect("fail to create test terminal");
+    terminal.draw(draw).expect("fail to draw on test terminal");
+    terminal.backend().buffer().clone()
+}
+
+/// Compare the text of the screen with `tests/snapshots/<name>.txt`. Run the tests with
+/// `UPDATE_SNAPSHOTS=1` to record the snapshots again after an intended change.
+#[cfg(test)]
+pub(crate) fn assert_snapshot(name: &str, buffer: &tui::buffer::Buffer) {
+    let area = buffer.area();
+    let screen = (area.top()..area.bottom())
+        .map(|y| {
+            let line = (area.left()..area.right())
+                .map(|x| buffer.get(x, y).symbol.as_str())
+                .collect::<String>();
+            format!("{}\n", line.trim_end())
+        })
+        .collect::<String>();
+
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/snapshots")
+        .join(format!("{name}.txt"));
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, &screen).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(&path).unwrap_or_else(|err| {
+        panic!(
+            "fail to read snapshot {}: {err}, record it with UPDATE_SNAPSHOTS=1",
+            path.display()
+        )
+    });
+    assert!(
+        screen == expected,
+        "screen differs from snapshot {}\n--- expected\n{expected}--- actual\n{screen}",
+        path.display()
+    );
+}
+
+#[cfg(test)]
+fn test_packages() -> component::packages::PkgInfoTable {
+    use crate::{
+        component::packages::PkgInfoBuilder, logs::classify::Classification,
+        pacman::outdated::Outdated, types::Mark,
+    };
+
+    let mut table = component::packages::PkgInfoTable::default();
+    table.data = vec![
+        PkgInfoBuilder::default()
+            .name("bear".into())
+            .process("FTBFS".into())
+            .assignee("Moody".into())
+            .marks(vec![Mark {
+                name: "noqemu".into(),
+                by: "Moody".into(),
+                comment: "".into(),
+            }])
+            .blocks(3)
+            .reason(Classification {
+                reason: "test timeout".into(),
+                mark: Some("noqemu".into()),
+                line: 0,
+            })
+            .build()
+            .unwrap(),
+        PkgInfoBuilder::default()
+            .name("ghc".into())
+            .process("FTBFS".into())
+            .outdated(Outdated {
+                riscv64: "9.0.2-3".into(),
+                x86_64: "9.0.2-4".into(),
+            })
+            .build()
+            .unwrap(),
+        PkgInfoBuilder::default()
+            .name("nodejs".into())
+            .process("Leaf package".into())
+            .build()
+            .unwrap(),
+    ];
+    table.cursor.select(Some(0));
+    table
+}
+
+#[test]
+fn test_draw_welcome_page() {
+    let mut terminal = terminal::Terminal::new(tui::backend::TestBackend::new(100, 30)).unwrap();
+    draw_welcome_page(&mut terminal).unwrap();
+    assert_snapshot("welcome_page", terminal.backend().buffer());
+}
+
+#[test]
+fn test_draw_pkg_table() {
+    let mut table = test_packages();
+    table.next();
+    table.toggle_mark();
+    let buffer = draw_test(120, 8, |frame| draw_pkg_table_frame(frame, &mut table));
+    assert_snapshot("pkg_table", &buffer);
+
+    // the selected row is highlighted
+    let selected = buffer.get(2, 4);
+    assert_eq!(selected.symbol, ">");
+    assert!(selected.modifier.contains(Modifier::REVERSED));
+    assert!(!buffer.get(2, 3).modifier.contains(Modifier::REVERSED));
+}
+
+#[test]
+fn test_draw_popup_menu() {
+    let mut table = test_packages();
+    let mut menu = PopUpMenu::from(&["Assign", "View dependents", "Build locally"]);
+    menu.next();
+    let buffer = draw_test(80, 24, |frame| {
+        draw_pkg_table_frame(frame, &mut table);
+        draw_popup_menu_frame(frame, &mut menu);
+    });
+    assert_snapshot("popup_menu", &buffer);
+}
+
+#[test]
+fn test_draw_log_frame() {
+    let log = "==> Starting build()...\nmake\n\x1b[1;31mcc foo.c\x1b[0m\n\
+               ==> Starting check()...\nFAIL: test_io\n\
+               ==> ERROR: A failure occurred in check().";
+    let mut view = LogView::new("foo.log", log);
+    view.jump_to_failure();
+    let buffer = draw_test(80, 10, |frame| draw_log_frame(frame, &mut view));
+    assert_snapshot("log_frame", &buffer);
+}
+
+#[test]
+fn test_draw_log_diff_frame() {
+    let view = DiffView::new(
+        ("old".to_string(), "new".to_string()),
+        "==> Starting build()...\ncc /tmp/a/foo.c\nerror: x\n",
+        "==> Starting build()...\ncc /build/foo.c\nerror: y\nok\n",
+    );
+    let buffer = draw_test(60, 8, |frame| draw_log_diff_frame(frame, &view));
+    assert_snapshot("log_diff_frame", &buffer);
+}
+
+#[test]
+fn test_draw_clusters_frame() {
+    use crate::logs::cluster::Cluster;
+
+    let mut view = ClusterView::new(vec![
+        Cluster {
+            signature: "error: unknown architecture riscv<n>".to_string(),
+            pkgnames: vec!["bear".into(), "ghc".into()],
+        },
+        Cluster {
+            signature: "undefined reference to __atomic_load".to_string(),
+            pkgnames: vec!["nodejs".into()],
+        },
+    ]);
+    let buffer = draw_test(80, 8, |frame| draw_clusters_frame(frame, &mut view));
+    assert_snapshot("clusters_frame", &buffer);
 }
@@ -0,0 +1,158 @@
+use crate::req::{felix, melon};
+use anyhow::Result;
+use std::time::Duration;
+
+pub use crate::{
+    build::queue::BuildRecord,
+    component::packages::PkgInfo,
+    logs::classify::Classification,
+    pacman::outdated::Outdated,
+    patches::PatchInfo,
+    req::{
+        felix::{Column, PackageStatus, ParseError, StatusPage},
+        melon::{Response as WorkData, WorkList},
+    },
+    types::{Mark, MarkList},
+};
+
+/// Where the status page of the riscv64 port is published
+pub const STATUS_URL: &str = felix::END_POINT;
+/// Where the melon bot publishes the work and mark lists
+pub const MELON_URL: &str = melon::END_POINT;
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Client of the riscv64 port status page and of the melon bot. It is cheap to clone, the
+/// clones share their connection pool.
+///
+/// ```no_run
+/// # async fn run() -> anyhow::Result<()> {
+/// let client = lazyarchbuild::client::Client::new();
+/// let page = client.status().await?;
+/// for pkg in page.packages {
+///     println!("{} {}", pkg.pkgname, pkg.status);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    status_url: String,
+    melon_url: String,
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Client {
+    /// Client of the public services, giving up on requests after 30 seconds
+    pub fn new() -> Self {
+        let http = reqwest::Client::builder()
+            .timeout(TIMEOUT)
+            .build()
+            .expect("Fail to create HTTP client");
+        Self::with_http(http)
+    }
+
+    /// Client sending its requests through `http`, to share it with other code or to set
+    /// another timeout or proxy
+    pub fn with_http(http: reqwest::Client) -> Self {
+        Self {
+            http,
+            status_url: STATUS_URL.to_string(),
+            melon_url: MELON_URL.to_string(),
+        }
+    }
+
+    /// Read the status page at `url` instead, like a mirror or a local copy
+    pub fn status_url(mut self, url: impl ToString) -> Self {
+        self.status_url = url.to_string();
+        self
+    }
+
+    /// Read the work and mark lists at `url` instead
+    pub fn melon_url(mut self, url: impl ToString) -> Self {
+        self.melon_url = url.to_string();
+        self
+    }
+
+    /// FTBFS and leaf packages of the status page. Rows that can't be read are returned as
+    /// warnings rather than failing the whole page.
+    pub async fn status(&self) -> Result<StatusPage> {
+        PackageStatus::download_from(&self.http, &self.status_url).await
+    }
+
+    /// Work and mark lists in one request
+    pub async fn work_data(&self) -> Result<WorkData> {
+        melon::fetch_from(&self.http, &self.melon_url).await
+    }
+
+    /// Packages claimed by each packager
+    pub async fn work_lists(&self) -> Result<Vec<WorkList>> {
+        Ok(self.work_data().await?.worklist)
+    }
+
+    /// Marks of each package having some
+    pub async fn marks(&self) -> Result<Vec<MarkList>> {
+        Ok(self.work_data().await?.marklist)
+    }
+}
+
+#[tokio::test]
+async fn test_client() {
+    use crate::req::mock::{MockResponse, MockServer};
+
+    let status = MockServer::start(MockResponse::ok(include_str!(
+        "../tests/fixtures/felix_status.htm"
+    )));
+    let melon = MockServer::start(MockResponse::ok(include_str!(
+        "../tests/fixtures/melon_pkg.json"
+    )));
+    let client = Client::new()
+        .status_url(status.url("/status.htm"))
+        .melon_url(melon.url("/pkg"));
+
+    let page = client.status().await.unwrap();
+    assert_eq!(page.packages.len(), 3);
+    assert_eq!(
+        client.work_lists().await.unwrap()[0].alias.as_ref(),
+        "Moody"
+    );
+    let marks = client.marks().await.unwrap();
+    assert_eq!(marks[0].marks[1].by.as_ref(), "Moody");
+
+    // models go through JSON unchanged
+    let json = serde_json::to_string(&page).unwrap();
+    let parsed: StatusPage = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed.packages, page.packages);
+    let json = serde_json::to_string(&marks).unwrap();
+    let parsed: Vec<MarkList> = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed[1].marks[0].name, marks[1].marks[0].name);
+}
+
+#[test]
+fn test_pkginfo_json() {
+    let pkg = crate::component::packages::PkgInfoBuilder::default()
+        .name("bash".into())
+        .outdated(Outdated {
+            riscv64: "5.1.016-1".into(),
+            x86_64: "5.2.015-1".into(),
+        })
+        .reason(Classification {
+            reason: "missing -latomic".into(),
+            mark: Some("upstreamable".into()),
+            line: 12,
+        })
+        .build()
+        .unwrap();
+
+    let json = serde_json::to_string(&pkg).unwrap();
+    let parsed: PkgInfo = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed.reason, pkg.reason);
+    assert_eq!(parsed.outdated, pkg.outdated);
+    assert_eq!(serde_json::to_string(&parsed).unwrap(), json);
+}
@@ -0,0 +1,168 @@
+use tui::{
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+};
+
+/// Turn a line with SGR escape sequences into styled spans on top of `base`. Other escape
+/// sequences are dropped.
+pub fn to_spans(line: &str, base: Style) -> Spans<'static> {
+    let mut spans = Vec::new();
+    let mut style = base;
+    let mut rest = line;
+
+    while let Some(start) = rest.find('\x1b') {
+        if start > 0 {
+            spans.push(Span::styled(rest[..start].to_string(), style));
+        }
+        rest = &rest[start + 1..];
+        if let Some(osc) = rest.strip_prefix(']') {
+            // an OSC sequence like a window title, ended by BEL or ST
+            let bel = osc.find('\x07').map(|end| end + 1);
+            let st = osc.find("\x1b\\").map(|end| end + 2);
+            rest = match bel.into_iter().chain(st).min() {
+                Some(end) => &osc[end..],
+                None => "",
+            };
+            continue;
+        }
+        if !rest.starts_with('[') {
+            // intermediate bytes then a final byte, like the `(B` charset reset of `tput sgr0`
+            let end = rest
+                .find(|c: char| !(' '..='/').contains(&c))
+                .map_or(rest.len(), |end| {
+                    end + rest[end..].chars().next().map_or(0, char::len_utf8)
+                });
+            rest = &rest[end..];
+            continue;
+        }
+        // a CSI sequence: parameters then a final byte in '@'..='~'
+        let end = match rest[1..].find(|c: char| ('@'..='~').contains(&c)) {
+            Some(end) => end + 1,
+            None => {
+                rest = "";
+                break;
+            }
+        };
+        if rest[end..].starts_with('m') {
+            style = apply_sgr(style, base, &rest[1..end]);
+        }
+        rest = &rest[end + 1..];
+    }
+    if !rest.is_empty() {
+        spans.push(Span::styled(rest.to_string(), style));
+    }
+
+    Spans::from(spans)
+}
+
+/// Apply the `;` separated SGR parameters to `style`, a reset going back to `base`
+fn apply_sgr(mut style: Style, base: Style, params: &str) -> Style {
+    let mut codes = params
+        .split(';')
+        .map(|code| code.parse::<u8>().unwrap_or(0));
+
+    while let Some(code) = codes.next() {
+        style = match code {
+            0 => base,
+            1 => style.add_modifier(Modifier::BOLD),
+            2 => style.add_modifier(Modifier::DIM),
+            3 => style.add_modifier(Modifier::ITALIC),
+            4 => style.add_modifier(Modifier::UNDERLINED),
+            5 => style.add_modifier(Modifier::SLOW_BLINK),
+            7 => style.add_modifier(Modifier::REVERSED),
+            9 => style.add_modifier(Modifier::CROSSED_OUT),
+            22 => style.remove_modifier(Modifier::BOLD | Modifier::DIM),
+            23 => style.remove_modifier(Modifier::ITALIC),
+            24 => style.remove_modifier(Modifier::UNDERLINED),
+            25 => style.remove_modifier(Modifier::SLOW_BLINK),
+            27 => style.remove_modifier(Modifier::REVERSED),
+            29 => style.remove_modifier(Modifier::CROSSED_OUT),
+            30..=37 => style.fg(basic_color(code - 30)),
+            39 => Style {
+                fg: base.fg,
+                ..style
+            },
+            40..=47 => style.bg(basic_color(code - 40)),
+            49 => Style {
+                bg: base.bg,
+                ..style
+            },
+            90..=97 => style.fg(bright_color(code - 90)),
+            100..=107 => style.bg(bright_color(code - 100)),
+            38 | 48 => {
+                let color = match codes.next() {
+                    Some(5) => codes.next().map(Color::Indexed),
+                    Some(2) => match (codes.next(), codes.next(), codes.next()) {
+                        (Some(r), Some(g), Some(b)) => Some(Color::Rgb(r, g, b)),
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                match color {
+                    Some(color) if code == 38 => style.fg(color),
+                    Some(color) => style.bg(color),
+                    None => style,
+                }
+            }
+            _ => style,
+        };
+    }
+    style
+}
+
+fn basic_color(i: u8) -> Color {
+    match i {
+        0 => Color::Black,
+        1 => Color::Red,
+        2 => Color::Green,
+        3 => Color::Yellow,
+        4 => Color::Blue,
+        5 => Color::Magenta,
+        6 => Color::Cyan,
+        _ => Color::Gray,
+    }
+}
+
+fn bright_color(i: u8) -> Color {
+    match i {
+        0 => Color::DarkGray,
+        1 => Color::LightRed,
+        2 => Color::LightGreen,
+        3 => Color::LightYellow,
+        4 => Color::LightBlue,
+        5 => Color::LightMagenta,
+        6 => Color::LightCyan,
+        _ => Color::White,
+    }
+}
+
+#[test]
+fn test_to_spans() {
+    // as printed by makepkg, whose resets are `tput sgr0`
+    let line = "\x1b[1m\x1b[32m==>\x1b(B\x1b[m\x1b[1m Making package\x1b(B\x1b[m done\
+                \x1b[K\x1b]0;title\x07";
+    let spans = to_spans(line, Style::default());
+    let spans = spans
+        .0
+        .iter()
+        .map(|span| (span.content.as_ref(), span.style))
+        .collect::<Vec<_>>();
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    assert_eq!(
+        spans,
+        [
+            ("==>", bold.fg(Color::Green)),
+            (" Making package", bold),
+            (" done", Style::default()),
+        ]
+    );
+}
+
+#[test]
+fn test_extended_colors() {
+    let base = Style::default().fg(Color::Red);
+    let style = apply_sgr(base, base, "38;5;208;48;2;1;2;3");
+    assert_eq!(style.fg, Some(Color::Indexed(208)));
+    assert_eq!(style.bg, Some(Color::Rgb(1, 2, 3)));
+    assert_eq!(apply_sgr(style, base, "39;49"), base);
+}
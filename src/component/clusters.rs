@@ -0,0 +1,30 @@
+use super::pkglist::PkgList;
+use crate::logs::cluster::Cluster;
+
+/// Failure clusters sorted by size, with the packages of the highlighted cluster listed aside
+pub struct ClusterView {
+    pub list: PkgList,
+    pub clusters: Vec<Cluster>,
+    /// Progress of the log download, shown under the list
+    pub message: Option<String>,
+}
+
+impl ClusterView {
+    pub fn new(clusters: Vec<Cluster>) -> Self {
+        let items = clusters
+            .iter()
+            .map(|cluster| format!("{:>4}  {}", cluster.pkgnames.len(), cluster.signature));
+        Self {
+            list: PkgList::new("Failure clusters", items),
+            clusters,
+            message: None,
+        }
+    }
+
+    pub fn selected(&self) -> Option<&Cluster> {
+        self.list
+            .state
+            .selected()
+            .and_then(|i| self.clusters.get(i))
+    }
+}
@@ -0,0 +1,164 @@
+use super::pkglist::PkgList;
+use crate::logs::{
+    diff::{self, LogDiff},
+    stages, LogSource,
+};
+use tui::{
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, Paragraph},
+};
+
+/// List of the logs of a package to compare with its newest one
+pub struct LogPicker {
+    pub list: PkgList,
+    pub base: Option<LogSource>,
+    pub others: Vec<LogSource>,
+}
+
+impl LogPicker {
+    /// `sources` are sorted newest first, the first one is compared with the selected one
+    pub fn new(pkgname: &str, mut sources: Vec<LogSource>) -> Self {
+        let base = (!sources.is_empty()).then(|| sources.remove(0));
+        let title = match base {
+            Some(ref base) if !sources.is_empty() => {
+                format!("Compare the {} log of {pkgname} with", base.label)
+            }
+            _ => format!("No other log of {pkgname} to compare with"),
+        };
+        let items = sources
+            .iter()
+            .map(|source| format!("{} ({})", source.label, source.path.display()));
+        Self {
+            list: PkgList::new(title, items),
+            base,
+            others: sources,
+        }
+    }
+
+    /// The logs to compare, the older one on the left
+    pub fn selected(&self) -> Option<(&LogSource, &LogSource)> {
+        let other = self
+            .list
+            .state
+            .selected()
+            .and_then(|i| self.others.get(i))?;
+        Some((other, self.base.as_ref()?))
+    }
+}
+
+/// Two logs side by side, with the differing lines highlighted
+pub struct DiffView {
+    pub titles: (String, String),
+    left: Vec<String>,
+    right: Vec<String>,
+    pub diff: LogDiff,
+    /// Index of the first visible row
+    pub offset: usize,
+    /// Index of the hunk last jumped to
+    pub hunk: Option<usize>,
+}
+
+impl DiffView {
+    pub fn new(titles: (String, String), left: &str, right: &str) -> Self {
+        let left = left.lines().map(str::to_string).collect::<Vec<_>>();
+        let right = right.lines().map(str::to_string).collect::<Vec<_>>();
+        let diff = diff::diff(&left, &right);
+        Self {
+            titles,
+            left,
+            right,
+            diff,
+            offset: 0,
+            hunk: None,
+        }
+    }
+
+    pub fn scroll_down(&mut self) {
+        if self.offset + 1 < self.diff.rows.len() {
+            self.offset += 1;
+        }
+    }
+
+    pub fn scroll_up(&mut self) {
+        self.offset = self.offset.saturating_sub(1);
+    }
+
+    pub fn top(&mut self) {
+        self.offset = 0;
+    }
+
+    pub fn bottom(&mut self) {
+        self.offset = self.diff.rows.len().saturating_sub(1);
+    }
+
+    pub fn next_hunk(&mut self) {
+        if let Some(i) = self.diff.hunks.iter().position(|&row| row > self.offset) {
+            self.hunk = Some(i);
+            self.offset = self.diff.hunks[i];
+        }
+    }
+
+    pub fn previous_hunk(&mut self) {
+        if let Some(i) = self.diff.hunks.iter().rposition(|&row| row < self.offset) {
+            self.hunk = Some(i);
+            self.offset = self.diff.hunks[i];
+        }
+    }
+
+    /// Render `height` rows from the offset, one paragraph for each side
+    pub fn render(&self, height: usize) -> (Paragraph<'static>, Paragraph<'static>) {
+        let rows = self.diff.rows.iter().skip(self.offset).take(height);
+        let side = |lines: &[String], line: Option<usize>, changed: bool, color: Color| {
+            let text = line
+                .map(|i| stages::strip_ansi(&lines[i]).into_owned())
+                .unwrap_or_default();
+            let style = if changed {
+                Style::default().fg(color)
+            } else {
+                Style::default()
+            };
+            Spans::from(Span::styled(text, style))
+        };
+        let (left, right): (Vec<_>, Vec<_>) = rows
+            .map(|row| {
+                (
+                    side(&self.left, row.left, row.changed, Color::Red),
+                    side(&self.right, row.right, row.changed, Color::Green),
+                )
+            })
+            .unzip();
+
+        let hunks = match self.hunk {
+            Some(i) => format!("hunk {}/{}", i + 1, self.diff.hunks.len()),
+            None => format!("{} hunks", self.diff.hunks.len()),
+        };
+        let block = |title: String| {
+            Block::default().borders(Borders::ALL).title(Span::styled(
+                title,
+                Style::default().add_modifier(Modifier::BOLD),
+            ))
+        };
+        (
+            Paragraph::new(left).block(block(format!("{} - {hunks}", self.titles.0))),
+            Paragraph::new(right).block(block(self.titles.1.clone())),
+        )
+    }
+}
+
+#[test]
+fn test_hunk_navigation() {
+    let left = "a\nb\nc\nd\ne\nf\ng";
+    let right = "a\nB\nc\nd\ne\nF\ng";
+    let mut view = DiffView::new(("old".to_string(), "new".to_string()), left, right);
+    assert_eq!(view.diff.hunks, [1, 5]);
+
+    view.next_hunk();
+    assert_eq!((view.offset, view.hunk), (1, Some(0)));
+    view.next_hunk();
+    assert_eq!((view.offset, view.hunk), (5, Some(1)));
+    view.next_hunk();
+    assert_eq!(view.offset, 5);
+    view.previous_hunk();
+    assert_eq!((view.offset, view.hunk), (1, Some(0)));
+}
@@ -0,0 +1,384 @@
+use super::ansi;
+use crate::{
+    build::format_duration,
+    logs::{
+        read::LogText,
+        stages::{self, StageKind, StageOutline},
+    },
+};
+use tokio::sync::oneshot;
+use tui::{
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Row {
+    Line(usize),
+    /// Header line of the stage with the given index
+    Header(usize),
+}
+
+/// Where to put the cursor once the log is read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Jump {
+    Line(usize),
+    Failure,
+}
+
+/// A build log being read in the background
+struct Reading {
+    id: usize,
+    /// Stop reading when dropped
+    _cancel: oneshot::Sender<()>,
+    jump: Jump,
+}
+
+/// A build log split into foldable makepkg stages, with a cursor row
+pub struct LogView {
+    pub title: String,
+    lines: LogText,
+    reading: Option<Reading>,
+    pub outline: StageOutline,
+    folded: Vec<bool>,
+    rows: Vec<Row>,
+    /// Index of the highlighted row
+    pub cursor: usize,
+    /// Index of the first visible row
+    pub offset: usize,
+}
+
+impl LogView {
+    pub fn new(title: impl ToString, content: &str) -> Self {
+        Self::from_text(title, LogText::new(content.to_string()))
+    }
+
+    pub fn from_text(title: impl ToString, lines: LogText) -> Self {
+        let outline = stages::parse(&lines.lines().collect::<Vec<_>>());
+        let mut view = Self {
+            title: title.to_string(),
+            folded: vec![false; outline.stages.len()],
+            lines,
+            reading: None,
+            outline,
+            rows: Vec::new(),
+            cursor: 0,
+            offset: 0,
+        };
+        view.refresh_rows();
+        view
+    }
+
+    /// An empty view filled by the [`Events::LogChunk`](crate::events::Events::LogChunk) of the
+    /// read `id`. The stages show up and the cursor moves to `jump` once the log is read.
+    pub fn reading(
+        title: impl ToString,
+        id: usize,
+        cancel: oneshot::Sender<()>,
+        jump: Jump,
+    ) -> Self {
+        let mut view = Self::from_text(title, LogText::default());
+        view.reading = Some(Reading {
+            id,
+            _cancel: cancel,
+            jump,
+        });
+        view
+    }
+
+    /// Id of the background read filling the view
+    pub fn reading_id(&self) -> Option<usize> {
+        self.reading.as_ref().map(|reading| reading.id)
+    }
+
+    /// Show the next lines of the log, not split into stages yet
+    pub fn push_chunk(&mut self, chunk: &str) {
+        let first = self.lines.len();
+        self.lines.push_str(chunk);
+        if self.outline.stages.is_empty() {
+            self.rows.extend((first..self.lines.len()).map(Row::Line));
+        }
+    }
+
+    /// Split the read log into its stages and move the cursor as asked
+    pub fn finish_reading(&mut self, outline: StageOutline, error: Option<String>) {
+        let reading = match self.reading.take() {
+            Some(reading) => reading,
+            None => return,
+        };
+        // chunks end with a newline, so the error gets its own line
+        if let Some(err) = error {
+            self.lines.push_str(&format!("fail to read log: {err}"));
+        }
+        self.folded = vec![false; outline.stages.len()];
+        self.outline = outline;
+        self.refresh_rows();
+        self.jump(reading.jump);
+    }
+
+    pub fn jump(&mut self, jump: Jump) {
+        match jump {
+            Jump::Line(line) => self.jump_to_line(line),
+            Jump::Failure => self.jump_to_failure(),
+        }
+    }
+
+    fn refresh_rows(&mut self) {
+        let first = self
+            .outline
+            .stages
+            .first()
+            .map_or(self.lines.len(), |stage| stage.start);
+        self.rows = (0..first).map(Row::Line).collect();
+        for (i, stage) in self.outline.stages.iter().enumerate() {
+            self.rows.push(Row::Header(i));
+            if !self.folded[i] {
+                self.rows
+                    .extend((stage.start + 1..stage.end).map(Row::Line));
+            }
+        }
+        self.cursor = self.cursor.min(self.rows.len().saturating_sub(1));
+    }
+
+    /// Index of the stage holding the cursor
+    pub fn current_stage(&self) -> Option<usize> {
+        match self.rows.get(self.cursor)? {
+            Row::Header(i) => Some(*i),
+            Row::Line(line) => self
+                .outline
+                .stages
+                .iter()
+                .position(|stage| (stage.start..stage.end).contains(line)),
+        }
+    }
+
+    fn move_to_row(&mut self, row: Row) {
+        if let Some(i) = self.rows.iter().position(|r| *r == row) {
+            self.cursor = i;
+        }
+    }
+
+    /// Unfold the stage holding `line` and move the cursor to it
+    pub fn jump_to_line(&mut self, line: usize) {
+        let stage = self
+            .outline
+            .stages
+            .iter()
+            .position(|stage| (stage.start..stage.end).contains(&line));
+        match stage {
+            Some(i) if line == self.outline.stages[i].start => self.move_to_row(Row::Header(i)),
+            Some(i) => {
+                self.folded[i] = false;
+                self.refresh_rows();
+                self.move_to_row(Row::Line(line));
+            }
+            None => self.move_to_row(Row::Line(line)),
+        }
+    }
+
+    /// Fold every stage but the failing one, and put the cursor on the error
+    pub fn jump_to_failure(&mut self) {
+        let failure = match self.outline.failure {
+            Some(failure) => failure,
+            None => return,
+        };
+        for (i, folded) in self.folded.iter_mut().enumerate() {
+            *folded = i != failure.stage;
+        }
+        self.refresh_rows();
+        self.jump_to_line(failure.line);
+    }
+
+    pub fn toggle_fold(&mut self) {
+        if let Some(i) = self.current_stage() {
+            self.folded[i] = !self.folded[i];
+            self.refresh_rows();
+            self.move_to_row(Row::Header(i));
+        }
+    }
+
+    /// Fold every stage, or unfold them all when they are already folded
+    pub fn toggle_fold_all(&mut self) {
+        let stage = self.current_stage();
+        let fold = !self.folded.iter().all(|folded| *folded);
+        self.folded.iter_mut().for_each(|folded| *folded = fold);
+        self.refresh_rows();
+        if let Some(i) = stage {
+            self.move_to_row(Row::Header(i));
+        }
+    }
+
+    pub fn next_stage(&mut self) {
+        if let Some(i) =
+            (self.cursor + 1..self.rows.len()).find(|&i| matches!(self.rows[i], Row::Header(_)))
+        {
+            self.cursor = i;
+        }
+    }
+
+    pub fn previous_stage(&mut self) {
+        if let Some(i) = (0..self.cursor)
+            .rev()
+            .find(|&i| matches!(self.rows[i], Row::Header(_)))
+        {
+            self.cursor = i;
+        }
+    }
+
+    pub fn scroll_down(&mut self) {
+        if self.cursor + 1 < self.rows.len() {
+            self.cursor += 1;
+        }
+    }
+
+    pub fn scroll_up(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    pub fn top(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub fn bottom(&mut self) {
+        self.cursor = self.rows.len().saturating_sub(1);
+    }
+
+    /// One line explanation of the failure, telling test failures apart from build failures
+    pub fn failure_summary(&self) -> Option<&'static str> {
+        let summary = match self.outline.failure?.kind {
+            StageKind::Check => "check() failed, the tests may need fixing or skipping (nocheck)",
+            StageKind::Build => "build() failed, the sources likely need a patch",
+            StageKind::Prepare => "prepare() failed, an existing patch may not apply anymore",
+            StageKind::Package => "package() failed",
+            StageKind::Sources => "fail to get the sources",
+            StageKind::Other => "makepkg failed",
+        };
+        Some(summary)
+    }
+
+    /// Outline of the stages, with the current one selected
+    pub fn outline_renderable(&self) -> (List<'static>, ListState) {
+        let items = self
+            .outline
+            .stages
+            .iter()
+            .enumerate()
+            .map(|(i, stage)| {
+                let marker = if self.folded[i] { "▸" } else { "▾" };
+                let duration = stage.duration.map(format_duration).unwrap_or_default();
+                let style = if stage.failed {
+                    Style::default().fg(Color::Red)
+                } else {
+                    Style::default()
+                };
+                ListItem::new(format!("{marker} {} {duration}", stage.title)).style(style)
+            })
+            .collect::<Vec<_>>();
+
+        let mut state = ListState::default();
+        state.select(self.current_stage());
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title("Stages"))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        (list, state)
+    }
+
+    /// Render `height` rows around the cursor. Only those lines are styled, so the size of the
+    /// log doesn't matter.
+    pub fn render(&mut self, height: usize) -> Paragraph<'static> {
+        if self.cursor < self.offset {
+            self.offset = self.cursor;
+        } else if height > 0 && self.cursor >= self.offset + height {
+            self.offset = self.cursor + 1 - height;
+        }
+
+        let failure_line = self.outline.failure.map(|failure| failure.line);
+        let lines = self
+            .rows
+            .iter()
+            .enumerate()
+            .skip(self.offset)
+            .take(height)
+            .map(|(i, row)| {
+                let cursor = if i == self.cursor {
+                    Style::default().add_modifier(Modifier::REVERSED)
+                } else {
+                    Style::default()
+                };
+                match *row {
+                    Row::Header(s) => {
+                        let stage = &self.outline.stages[s];
+                        let text = if self.folded[s] {
+                            let hidden = stage.end - stage.start - 1;
+                            format!("▸ ==> {} [{hidden} lines]", stage.title)
+                        } else {
+                            format!("▾ ==> {}", stage.title)
+                        };
+                        let style = Style::default().add_modifier(Modifier::BOLD);
+                        Spans::from(Span::styled(text, style.patch(cursor)))
+                    }
+                    Row::Line(line) => {
+                        let style = if Some(line) == failure_line {
+                            Style::default().fg(Color::Red)
+                        } else {
+                            Style::default()
+                        };
+                        let mut spans = ansi::to_spans(self.lines.line(line), style);
+                        for span in spans.0.iter_mut() {
+                            span.style = span.style.patch(cursor);
+                        }
+                        spans
+                    }
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let title = match self.failure_summary() {
+            _ if self.reading.is_some() => format!("{} - reading...", self.title),
+            Some(summary) => format!("{} - {summary}", self.title),
+            None => self.title.clone(),
+        };
+        Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(title))
+    }
+}
+
+#[test]
+fn test_folding() {
+    let log = "==> Starting build()...\nmake\ncc foo.c\n==> Starting check()...\n\
+               FAIL: test_io\n==> ERROR: A failure occurred in check().";
+    let mut view = LogView::new("foo.log", log);
+    assert_eq!(view.rows.len(), 6);
+
+    view.jump_to_failure();
+    assert_eq!(view.current_stage(), Some(1));
+    assert_eq!(view.rows[view.cursor], Row::Line(5));
+    // build() is folded into its header
+    assert_eq!(view.rows.len(), 4);
+
+    view.previous_stage();
+    view.previous_stage();
+    assert_eq!(view.rows[view.cursor], Row::Header(0));
+    view.toggle_fold();
+    assert_eq!(view.rows.len(), 6);
+
+    view.toggle_fold_all();
+    assert_eq!(view.rows, [Row::Header(0), Row::Header(1)]);
+    assert!(view.failure_summary().unwrap().contains("nocheck"));
+}
+
+#[test]
+fn test_reading() {
+    let (cancel, _) = oneshot::channel();
+    let mut view = LogView::reading("foo.log", 1, cancel, Jump::Failure);
+    view.push_chunk("==> Starting build()...\nmake\n");
+    assert_eq!(view.rows.len(), 2);
+    assert_eq!(view.reading_id(), Some(1));
+
+    view.push_chunk("==> ERROR: A failure occurred in build().\n");
+    let outline = stages::parse(&view.lines.lines().collect::<Vec<_>>());
+    view.finish_reading(outline, None);
+    assert_eq!(view.reading_id(), None);
+    assert_eq!(view.rows[0], Row::Header(0));
+    assert_eq!(view.rows[view.cursor], Row::Line(2));
+}
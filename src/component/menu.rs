@@ -1,4 +1,5 @@
 use tui::{
+    layout::Rect,
     style::{Color, Modifier, Style},
     widgets::{Block, List, ListItem, ListState},
 };
@@ -12,9 +13,13 @@ pub struct MenuStyle {
 }
 
 pub struct PopUpMenu {
-    state: ListState,
+    pub state: ListState,
+    labels: Vec<String>,
     pub items: Vec<tui::text::Text<'static>>,
     pub style: MenuStyle,
+    /// Where the menu was last drawn, and its first visible item, to find what a click hits
+    pub area: Rect,
+    pub offset: usize,
 }
 
 impl PopUpMenu {
@@ -22,17 +27,21 @@ impl PopUpMenu {
     where
         T: ToString,
     {
-        let items = items
+        let labels = items.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let items = labels
             .iter()
-            .map(|s| {
-                let s = s.to_string();
-                tui::text::Text::from(s)
-            })
+            .map(|s| tui::text::Text::from(s.clone()))
             .collect::<Vec<_>>();
 
+        let mut state = ListState::default();
+        state.select(Some(0));
+
         Self {
             items,
-            state: ListState::default(),
+            labels,
+            state,
+            area: Rect::default(),
+            offset: 0,
             style: MenuStyle {
                 selected: Style::default()
                     .bg(Color::LightGreen)
@@ -44,6 +53,14 @@ impl PopUpMenu {
         }
     }
 
+    /// Return the label of the currently highlighted item
+    pub fn selected(&self) -> Option<&str> {
+        self.state
+            .selected()
+            .and_then(|i| self.labels.get(i))
+            .map(|s| s.as_str())
+    }
+
     pub fn next(&mut self) {
         let i = match self.state.selected() {
             Some(i) => {
@@ -72,7 +89,30 @@ impl PopUpMenu {
         self.state.select(Some(i));
     }
 
-    pub fn to_renderable(&self) -> List {
+    /// Record that the menu is drawn in `area`
+    pub fn set_area(&mut self, area: Rect) {
+        self.area = area;
+        let height = area.height.saturating_sub(2) as usize;
+        self.offset =
+            super::follow_cursor(self.offset, self.state.selected(), self.items.len(), height);
+    }
+
+    /// Index of the item drawn at the given screen position
+    pub fn item_at(&self, x: u16, y: u16) -> Option<usize> {
+        let inner = Rect::new(
+            self.area.x + 1,
+            self.area.y + 1,
+            self.area.width.saturating_sub(2),
+            self.area.height.saturating_sub(2),
+        );
+        if x < inner.left() || x >= inner.right() || y < inner.top() || y >= inner.bottom() {
+            return None;
+        }
+        let idx = self.offset + (y - inner.y) as usize;
+        (idx < self.items.len()).then_some(idx)
+    }
+
+    pub fn to_renderable(&self) -> List<'static> {
         let items: Vec<_> = self
             .items
             .iter()
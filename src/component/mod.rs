@@ -1,2 +1,44 @@
+pub(crate) mod ansi;
+pub(crate) mod clusters;
+pub(crate) mod logdiff;
+pub(crate) mod logview;
 pub(crate) mod menu;
+pub(crate) mod order;
 pub(crate) mod packages;
+pub(crate) mod pkglist;
+pub(crate) mod search;
+pub(crate) mod textview;
+
+/// Index of the first visible row once a list of `len` one-line rows, `height` of them visible,
+/// scrolled to show `selected`. It follows the rule of the tui list and table widgets, whose
+/// offset is private, so mouse clicks can be mapped back to rows.
+pub(crate) fn follow_cursor(
+    offset: usize,
+    selected: Option<usize>,
+    len: usize,
+    height: usize,
+) -> usize {
+    if len == 0 || height == 0 {
+        return offset;
+    }
+    let mut start = offset.min(len - 1);
+    let selected = selected.unwrap_or(0).min(len - 1);
+    if selected >= start + height {
+        start = selected + 1 - height;
+    }
+    if selected < start {
+        start = selected;
+    }
+    start
+}
+
+#[test]
+fn test_follow_cursor() {
+    assert_eq!(follow_cursor(0, Some(2), 10, 5), 0);
+    assert_eq!(follow_cursor(0, Some(7), 10, 5), 3);
+    assert_eq!(follow_cursor(3, Some(5), 10, 5), 3);
+    assert_eq!(follow_cursor(3, Some(1), 10, 5), 1);
+    // the offset is kept in range when rows are removed
+    assert_eq!(follow_cursor(8, Some(2), 3, 5), 2);
+    assert_eq!(follow_cursor(4, None, 0, 5), 4);
+}
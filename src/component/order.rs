@@ -0,0 +1,33 @@
+use super::pkglist::PkgList;
+use crate::pacman::order::RebuildOrder;
+use std::path::Path;
+
+/// Suggested rebuild order of the failing packages, with the dependency cycles listed apart
+pub struct RebuildOrderView {
+    pub list: PkgList,
+    pub cycles: Vec<Vec<Box<str>>>,
+    /// Result of the last export, shown under the list
+    pub message: Option<String>,
+}
+
+impl RebuildOrderView {
+    pub fn new(order: RebuildOrder) -> Self {
+        Self {
+            list: PkgList::new("Rebuild order", order.order),
+            cycles: order.cycles,
+            message: None,
+        }
+    }
+
+    /// Write the order to `path`, one package per line, so it can be fed to build scripts
+    pub fn export(&mut self, path: &Path) {
+        let mut content = self.list.items.join("\n");
+        content.push('\n');
+
+        let message = match std::fs::write(path, content) {
+            Ok(()) => format!("Exported to {}", path.display()),
+            Err(err) => format!("Fail to export to {}: {err}", path.display()),
+        };
+        self.message = Some(message);
+    }
+}
@@ -1,6 +1,12 @@
-use crate::types::Mark;
+use crate::{
+    build::queue::BuildRecord, logs::classify::Classification, pacman::outdated::Outdated,
+    patches::PatchInfo, types::Mark,
+};
 use derive_builder::Builder;
+use serde::{Deserialize, Serialize};
+use std::{cmp::Ordering, collections::HashSet};
 use tui::{
+    layout::Rect,
     style::{Modifier, Style},
     widgets,
 };
@@ -22,11 +28,14 @@ impl std::default::Default for PkgInfoTableStyle {
     }
 }
 
-#[derive(Debug, Builder)]
+#[derive(Debug, Clone, Builder, Serialize, Deserialize)]
 #[builder(pattern = "owned")]
 pub struct PkgInfo {
     #[builder(default = "\"\".into()")]
     pub name: Box<str>,
+    /// Repository of the package on the status page, like `core`
+    #[builder(setter(strip_option), default = "None")]
+    pub repo: Option<Box<str>>,
     #[builder(setter(strip_option), default = "None")]
     pub assignee: Option<Box<str>>,
     #[builder(default = "Vec::new()")]
@@ -35,6 +44,24 @@ pub struct PkgInfo {
     pub rotten: bool,
     #[builder(setter(strip_option), default = "None")]
     pub process: Option<Box<str>>,
+    /// Number of packages that depend on this package, directly or indirectly
+    #[builder(default = "0")]
+    pub blocks: usize,
+    /// Set when the riscv64 version is older than the x86_64 one
+    #[builder(setter(strip_option), default = "None")]
+    pub outdated: Option<Outdated>,
+    /// The riscv64 patch in the local archriscv-packages clone
+    #[builder(setter(strip_option), default = "None")]
+    pub patch: Option<PatchInfo>,
+    /// Result of the latest local build
+    #[builder(setter(strip_option), default = "None")]
+    pub local: Option<BuildRecord>,
+    /// Failure classification of the newest build log
+    #[builder(setter(strip_option), default = "None")]
+    pub reason: Option<Classification>,
+    /// Build log linked from the status page
+    #[builder(setter(strip_option), default = "None")]
+    pub log_url: Option<Box<str>>,
 }
 
 impl PkgInfo {
@@ -80,6 +107,158 @@ impl PkgInfo {
             ""
         }
     }
+
+    /// Return true if the status page reports this package fail to build from source
+    pub fn is_ftbfs(&self) -> bool {
+        self.current_process().contains("FTBFS")
+    }
+
+    /// Return true if the status page reports no other package depends on this package
+    pub fn is_leaf(&self) -> bool {
+        self.current_process().contains("Leaf package")
+    }
+
+    pub fn blocks(&self) -> usize {
+        self.blocks
+    }
+
+    pub fn is_outdated(&self) -> bool {
+        self.outdated.is_some()
+    }
+
+    /// Return the latest local build result like `local: OK 12m`
+    pub fn local_build(&self) -> String {
+        match self.local {
+            Some(ref record) => record.summary(),
+            None => String::new(),
+        }
+    }
+
+    /// Return the failure reason, followed by the suggested mark when the package doesn't have
+    /// it yet, like `missing -latomic [upstreamable?]`
+    pub fn reason(&self) -> String {
+        let classification = match self.reason {
+            Some(ref classification) => classification,
+            None => return String::new(),
+        };
+        match classification.mark {
+            Some(ref mark) if !self.marks().contains(&mark.as_ref()) => {
+                format!("{} [{mark}?]", classification.reason)
+            }
+            _ => classification.reason.to_string(),
+        }
+    }
+
+    pub fn has_patch(&self) -> bool {
+        self.patch.is_some()
+    }
+
+    /// Return the last commit date of the patch, or `yes` when the date is unknown
+    pub fn patch_status(&self) -> &str {
+        match self.patch {
+            Some(PatchInfo {
+                last_commit: Some(ref date),
+                ..
+            }) => date,
+            Some(_) => "yes",
+            None => "",
+        }
+    }
+
+    /// Return `<riscv64 version> -> <x86_64 version>` for outdated package
+    pub fn outdated(&self) -> String {
+        match self.outdated {
+            Some(ref o) => format!("{} -> {}", o.riscv64, o.x86_64),
+            None => String::new(),
+        }
+    }
+}
+
+/// Limit the rows shown in the package table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PkgFilter {
+    All,
+    /// Only show packages lagging behind x86_64
+    Outdated,
+}
+
+impl PkgFilter {
+    pub fn matches(&self, pkg: &PkgInfo) -> bool {
+        match self {
+            PkgFilter::All => true,
+            PkgFilter::Outdated => pkg.is_outdated(),
+        }
+    }
+}
+
+/// Columns of the package table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PkgColumn {
+    Name,
+    Status,
+    Reason,
+    Local,
+    Assignee,
+    Marks,
+    Blocks,
+    Outdated,
+    Patch,
+}
+
+impl PkgColumn {
+    /// Every column, in the order they are drawn
+    pub const ALL: [PkgColumn; 9] = [
+        PkgColumn::Name,
+        PkgColumn::Status,
+        PkgColumn::Reason,
+        PkgColumn::Local,
+        PkgColumn::Assignee,
+        PkgColumn::Marks,
+        PkgColumn::Blocks,
+        PkgColumn::Outdated,
+        PkgColumn::Patch,
+    ];
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            PkgColumn::Name => "Pkgname",
+            PkgColumn::Status => "Status",
+            PkgColumn::Reason => "Reason",
+            PkgColumn::Local => "Local",
+            PkgColumn::Assignee => "Assignee",
+            PkgColumn::Marks => "Marks",
+            PkgColumn::Blocks => "Blocks",
+            PkgColumn::Outdated => "Outdated",
+            PkgColumn::Patch => "Patch",
+        }
+    }
+
+    /// Order two packages by this column, packages with the same value are ordered by name
+    pub fn compare(&self, a: &PkgInfo, b: &PkgInfo) -> Ordering {
+        let order = match self {
+            PkgColumn::Name => Ordering::Equal,
+            PkgColumn::Status => a.current_process().cmp(b.current_process()),
+            PkgColumn::Reason => a.reason().cmp(&b.reason()),
+            PkgColumn::Local => a.local_build().cmp(&b.local_build()),
+            PkgColumn::Assignee => a.assignee().cmp(b.assignee()),
+            PkgColumn::Marks => a.marks().cmp(&b.marks()),
+            PkgColumn::Blocks => a.blocks().cmp(&b.blocks()),
+            PkgColumn::Outdated => a.outdated().cmp(&b.outdated()),
+            PkgColumn::Patch => a.patch_status().cmp(b.patch_status()),
+        };
+        order.then_with(|| a.name().cmp(b.name()))
+    }
+}
+
+/// Where the package table was last drawn, to find what a mouse click hits
+#[derive(Debug, Default)]
+pub struct PkgTableLayout {
+    /// Line of the column titles
+    pub header: u16,
+    /// Area of the package rows
+    pub rows: Rect,
+    /// Start and width of each column
+    pub columns: Vec<(PkgColumn, u16, u16)>,
 }
 
 #[derive(Debug)]
@@ -88,6 +267,18 @@ pub struct PkgInfoTable {
     pub cursor: widgets::TableState,
     pub data: Vec<PkgInfo>,
     pub style: PkgInfoTableStyle,
+    pub filter: PkgFilter,
+    /// Names of the packages marked for a bulk action
+    pub marked: HashSet<Box<str>>,
+    /// Number of status page rows that couldn't be read
+    pub warnings: usize,
+    /// Only show packages whose name contains this, empty to show all of them
+    pub query: String,
+    /// Column the rows are sorted by, and whether the order is descending
+    pub sort: Option<(PkgColumn, bool)>,
+    /// First visible row
+    pub offset: usize,
+    pub layout: PkgTableLayout,
 }
 
 impl std::default::Default for PkgInfoTable {
@@ -97,13 +288,127 @@ impl std::default::Default for PkgInfoTable {
             cursor: widgets::TableState::default(),
             data: Vec::new(),
             style: PkgInfoTableStyle::default(),
+            filter: PkgFilter::All,
+            marked: HashSet::new(),
+            warnings: 0,
+            query: String::new(),
+            sort: None,
+            offset: 0,
+            layout: PkgTableLayout::default(),
         }
     }
 }
 
 impl PkgInfoTable {
-    pub fn title<'a>(&self) -> &'a str {
-        self.title
+    pub fn title(&self) -> String {
+        let mut title = match self.filter {
+            PkgFilter::All => self.title.to_string(),
+            PkgFilter::Outdated => format!("{} [outdated]", self.title),
+        };
+        if !self.query.is_empty() {
+            title = format!("{title} [name: {}]", self.query);
+        }
+        match self.warnings {
+            0 => title,
+            n => format!("{title} (skipped rows: {n}, W to show)"),
+        }
+    }
+
+    /// Title of the column, after an arrow when the rows are sorted by it so narrow columns
+    /// still show it
+    pub fn header(&self, column: PkgColumn) -> String {
+        match self.sort {
+            Some((sorted, false)) if sorted == column => format!("▲{}", column.title()),
+            Some((sorted, true)) if sorted == column => format!("▼{}", column.title()),
+            _ => column.title().to_string(),
+        }
+    }
+
+    /// Sort the rows by `column`, or reverse the order when they are already sorted by it
+    pub fn sort_by(&mut self, column: PkgColumn) {
+        self.sort = match self.sort {
+            Some((sorted, descending)) if sorted == column => Some((column, !descending)),
+            _ => Some((column, false)),
+        };
+        self.sort_rows();
+    }
+
+    /// Apply the sort order to the rows, the cursor stays on the same package
+    pub fn sort_rows(&mut self) {
+        let (column, descending) = match self.sort {
+            Some(sort) => sort,
+            None => return,
+        };
+        let selected = self.selected().map(|pkg| pkg.name.clone());
+        self.data.sort_by(|a, b| {
+            let order = column.compare(a, b);
+            if descending {
+                order.reverse()
+            } else {
+                order
+            }
+        });
+        if let Some(name) = selected {
+            self.select_name(&name);
+        }
+    }
+
+    /// Index of the visible row drawn at the given screen position
+    pub fn row_at(&self, x: u16, y: u16) -> Option<usize> {
+        let rows = self.layout.rows;
+        if x < rows.left() || x >= rows.right() || y < rows.top() || y >= rows.bottom() {
+            return None;
+        }
+        let idx = self.offset + (y - rows.y) as usize;
+        (idx < self.visible_len()).then_some(idx)
+    }
+
+    /// Column whose title is drawn at the given screen position
+    pub fn column_at(&self, x: u16, y: u16) -> Option<PkgColumn> {
+        if y != self.layout.header {
+            return None;
+        }
+        self.layout
+            .columns
+            .iter()
+            .find(|(_, start, width)| x >= *start && x < start + width)
+            .map(|(column, _, _)| *column)
+    }
+
+    /// Iterate over the rows that match current filter
+    pub fn visible(&self) -> impl Iterator<Item = &PkgInfo> {
+        self.data
+            .iter()
+            .filter(|pkg| self.filter.matches(pkg) && pkg.name().contains(&self.query))
+    }
+
+    pub fn visible_len(&self) -> usize {
+        self.visible().count()
+    }
+
+    /// Switch to `filter` and move the cursor back to the first row
+    pub fn set_filter(&mut self, filter: PkgFilter) {
+        self.filter = filter;
+        self.cursor.select(Some(0));
+    }
+
+    /// Only show the packages whose name contains `query` and move the cursor back to the first
+    /// row
+    pub fn set_query(&mut self, query: String) {
+        self.query = query;
+        self.cursor.select(Some(0));
+    }
+
+    /// Move the cursor to the package named `name`, false when it is not a visible row
+    pub fn select_name(&mut self, name: &str) -> bool {
+        let position = self.visible().position(|pkg| pkg.name() == name);
+        match position {
+            Some(idx) => {
+                self.cursor.select(Some(idx));
+                true
+            }
+            None => false,
+        }
     }
 
     pub fn next(&mut self) {
@@ -115,7 +420,7 @@ impl PkgInfoTable {
 
         let idx = idx.unwrap();
 
-        if idx >= self.data.len() - 1 {
+        if idx + 1 >= self.visible_len() {
             self.cursor.select(Some(0))
         } else {
             self.cursor.select(Some(idx + 1))
@@ -132,17 +437,47 @@ impl PkgInfoTable {
         let idx = idx.unwrap();
 
         if idx == 0 {
-            self.cursor.select(Some(self.data.len() - 1))
+            self.cursor
+                .select(Some(self.visible_len().saturating_sub(1)))
         } else {
             self.cursor.select(Some(idx - 1))
         }
     }
 
+    /// Move the cursor by `delta` rows, stopping at the first and last row
+    pub fn step(&mut self, delta: isize) {
+        let last = self.visible_len().saturating_sub(1);
+        let idx = self.cursor.selected().unwrap_or(0);
+        let idx = idx.saturating_add_signed(delta).min(last);
+        self.cursor.select(Some(idx));
+    }
+
     pub fn beginning(&mut self) {
         self.cursor.select(Some(0))
     }
 
     pub fn end(&mut self) {
-        self.cursor.select(Some(self.data.len() - 1))
+        self.cursor
+            .select(Some(self.visible_len().saturating_sub(1)))
+    }
+
+    /// Mark or unmark the package under the cursor
+    pub fn toggle_mark(&mut self) {
+        let name = match self.selected() {
+            Some(pkg) => pkg.name.clone(),
+            None => return,
+        };
+        if !self.marked.remove(&name) {
+            self.marked.insert(name);
+        }
+    }
+
+    pub fn is_marked(&self, pkg: &PkgInfo) -> bool {
+        self.marked.contains(&pkg.name)
+    }
+
+    /// Return the package under the cursor
+    pub fn selected(&self) -> Option<&PkgInfo> {
+        self.visible().nth(self.cursor.selected().unwrap_or(0))
     }
 }
@@ -0,0 +1,79 @@
+use tui::{
+    style::{Modifier, Style},
+    widgets::{Block, Borders, List, ListItem, ListState},
+};
+
+/// A full screen, scrollable list of package names with a title
+pub struct PkgList {
+    pub title: String,
+    pub state: ListState,
+    pub items: Vec<Box<str>>,
+}
+
+impl PkgList {
+    pub fn new<T: Into<Box<str>>>(
+        title: impl ToString,
+        items: impl IntoIterator<Item = T>,
+    ) -> Self {
+        let mut state = ListState::default();
+        state.select(Some(0));
+        Self {
+            title: title.to_string(),
+            state,
+            items: items.into_iter().map(|i| i.into()).collect(),
+        }
+    }
+
+    pub fn selected(&self) -> Option<&str> {
+        self.state
+            .selected()
+            .and_then(|i| self.items.get(i))
+            .map(|i| i.as_ref())
+    }
+
+    pub fn next(&mut self) {
+        if self.items.is_empty() {
+            return;
+        }
+        let i = match self.state.selected() {
+            Some(i) if i + 1 < self.items.len() => i + 1,
+            _ => 0,
+        };
+        self.state.select(Some(i));
+    }
+
+    pub fn previous(&mut self) {
+        if self.items.is_empty() {
+            return;
+        }
+        let i = match self.state.selected() {
+            Some(0) | None => self.items.len() - 1,
+            Some(i) => i - 1,
+        };
+        self.state.select(Some(i));
+    }
+
+    pub fn beginning(&mut self) {
+        self.state.select(Some(0))
+    }
+
+    pub fn end(&mut self) {
+        self.state.select(Some(self.items.len().saturating_sub(1)))
+    }
+
+    pub fn to_renderable(&self) -> List<'static> {
+        let items: Vec<_> = self
+            .items
+            .iter()
+            .map(|item| ListItem::new(item.to_string()))
+            .collect();
+        let block = Block::default()
+            .title(format!("{} ({})", self.title, self.items.len()))
+            .borders(Borders::ALL);
+
+        List::new(items)
+            .block(block)
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+            .highlight_symbol(">> ")
+    }
+}
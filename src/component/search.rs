@@ -0,0 +1,28 @@
+use super::pkglist::PkgList;
+use crate::logs::index::Hit;
+
+/// Log lines matching a search, listed as `pkgname:line: text`
+pub struct SearchView {
+    pub list: PkgList,
+    pub hits: Vec<Hit>,
+}
+
+impl SearchView {
+    pub fn new(pattern: &str, result: anyhow::Result<Vec<Hit>>) -> Self {
+        let (title, hits) = match result {
+            Ok(hits) => (format!("Logs matching /{pattern}/"), hits),
+            Err(err) => (format!("Fail to search /{pattern}/: {err:#}"), Vec::new()),
+        };
+        let items = hits
+            .iter()
+            .map(|hit| format!("{}:{}: {}", hit.pkgname, hit.line + 1, hit.text.trim()));
+        Self {
+            list: PkgList::new(title, items),
+            hits,
+        }
+    }
+
+    pub fn selected(&self) -> Option<&Hit> {
+        self.list.state.selected().and_then(|i| self.hits.get(i))
+    }
+}
@@ -0,0 +1,81 @@
+use tui::{
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, Paragraph},
+};
+
+/// A full screen, read only text pane that scrolls line by line
+pub struct TextView {
+    pub title: String,
+    pub lines: Vec<Spans<'static>>,
+    /// Index of the first visible line
+    pub offset: usize,
+}
+
+impl TextView {
+    pub fn new(title: impl ToString, lines: Vec<Spans<'static>>) -> Self {
+        Self {
+            title: title.to_string(),
+            lines,
+            offset: 0,
+        }
+    }
+
+    pub fn plain(title: impl ToString, content: &str) -> Self {
+        let lines = content
+            .lines()
+            .map(|line| Spans::from(line.to_string()))
+            .collect();
+        Self::new(title, lines)
+    }
+
+    /// Build a view of a unified diff, with added, removed and hunk header lines colored
+    pub fn diff(title: impl ToString, content: &str) -> Self {
+        let lines = content
+            .lines()
+            .map(|line| {
+                let style = if line.starts_with("+++") || line.starts_with("---") {
+                    Style::default().add_modifier(Modifier::BOLD)
+                } else if line.starts_with('+') {
+                    Style::default().fg(Color::Green)
+                } else if line.starts_with('-') {
+                    Style::default().fg(Color::Red)
+                } else if line.starts_with("@@") {
+                    Style::default().fg(Color::Cyan)
+                } else {
+                    Style::default()
+                };
+                Spans::from(Span::styled(line.to_string(), style))
+            })
+            .collect();
+        Self::new(title, lines)
+    }
+
+    pub fn scroll_down(&mut self) {
+        if self.offset + 1 < self.lines.len() {
+            self.offset += 1;
+        }
+    }
+
+    pub fn scroll_up(&mut self) {
+        self.offset = self.offset.saturating_sub(1);
+    }
+
+    pub fn top(&mut self) {
+        self.offset = 0;
+    }
+
+    pub fn bottom(&mut self) {
+        self.offset = self.lines.len().saturating_sub(1);
+    }
+
+    pub fn to_renderable(&self) -> Paragraph<'static> {
+        let block = Block::default()
+            .title(self.title.clone())
+            .borders(Borders::ALL);
+
+        Paragraph::new(self.lines.clone())
+            .block(block)
+            .scroll((self.offset.min(u16::MAX as usize) as u16, 0))
+    }
+}
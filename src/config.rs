@@ -0,0 +1,362 @@
+use crate::{keymap::Keymap, logs::classify::Rule};
+use anyhow::Context;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+/// User configuration, read from `$XDG_CONFIG_HOME/lzr/config.toml`. Every field has a default so
+/// an absent or partial config file is fine.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Sync databases of the riscv64 repositories
+    pub syncdb: SyncDbConfig,
+    /// Sync databases of the x86_64 repositories, used to find outdated riscv64 packages
+    pub x86_64_syncdb: SyncDbConfig,
+    pub order: OrderConfig,
+    pub patches: PatchesConfig,
+    pub build: BuildConfig,
+    pub logs: LogsConfig,
+    pub serve: ServeConfig,
+    pub watch: WatchConfig,
+    pub control: ControlConfig,
+    /// Key bindings, `[keys.global]` for every display and `[keys.<display>]` for one of them,
+    /// mapping key sequences like `gg` or `<C-d>` to action names
+    pub keys: Keymap,
+}
+
+/// Where to find the pacman sync databases used to build the dependency graph
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct SyncDbConfig {
+    /// Directory containing the `<repo>.db` files, usually a copy of `/var/lib/pacman/sync`
+    pub dir: Option<PathBuf>,
+    /// Repository names to load from `dir`
+    pub repos: Vec<Box<str>>,
+}
+
+impl std::default::Default for SyncDbConfig {
+    fn default() -> Self {
+        Self {
+            dir: None,
+            repos: vec!["core".into(), "extra".into()],
+        }
+    }
+}
+
+/// Options for the rebuild order view
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct OrderConfig {
+    /// File written when exporting the rebuild order
+    pub export_path: PathBuf,
+}
+
+impl std::default::Default for OrderConfig {
+    fn default() -> Self {
+        Self {
+            export_path: PathBuf::from("rebuild-order.txt"),
+        }
+    }
+}
+
+/// Options for the local archriscv-packages clone
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct PatchesConfig {
+    /// Path to the local clone of the archriscv-packages repository
+    pub repo: Option<PathBuf>,
+    /// Where upstream packaging repositories are checked out for editing
+    pub scratch_dir: PathBuf,
+    /// Git URL of the upstream packaging repository, `{pkgname}` is replaced by the package name
+    pub upstream_url: String,
+}
+
+impl std::default::Default for PatchesConfig {
+    fn default() -> Self {
+        Self {
+            repo: None,
+            scratch_dir: cache_dir().join("scratch"),
+            upstream_url: "https://gitlab.archlinux.org/archlinux/packaging/packages/{pkgname}.git"
+                .to_string(),
+        }
+    }
+}
+
+/// Options for local builds
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct BuildConfig {
+    /// Command run by `sh -c` to build a package, `{pkgname}` is replaced by the package name
+    pub command: String,
+    /// Directory to run the command in, `{pkgname}` is replaced by the package name. Default to
+    /// the package checkout under `patches.scratch_dir`.
+    pub workdir: Option<String>,
+    /// How many queued builds may run at the same time on this machine
+    pub max_parallel: usize,
+    /// Program used to reach the remote hosts
+    pub ssh_program: String,
+    /// Remote machines to dispatch builds to, in order of preference
+    pub hosts: Vec<BuildHost>,
+    /// Where the result and the log of the latest build of each package are kept
+    pub dir: PathBuf,
+}
+
+/// A remote build machine reached over SSH, declared with `[[build.hosts]]`
+#[derive(Debug, Clone, Deserialize)]
+pub struct BuildHost {
+    /// Short name shown in the build queue
+    pub name: Box<str>,
+    /// SSH destination, like `builder@unmatched` or a `Host` alias from `~/.ssh/config`
+    pub ssh: String,
+    /// Directory on the host under which each package is uploaded and built
+    pub workdir: String,
+    /// Capabilities of the host. Packages marked `noqemu` only go to hosts labelled `board`.
+    #[serde(default)]
+    pub labels: Vec<Box<str>>,
+    /// How many builds may run on the host at the same time
+    #[serde(default = "default_host_jobs")]
+    pub jobs: usize,
+}
+
+fn default_host_jobs() -> usize {
+    1
+}
+
+impl BuildHost {
+    pub fn has_label(&self, label: &str) -> bool {
+        self.labels.iter().any(|l| l.as_ref() == label)
+    }
+}
+
+impl std::default::Default for BuildConfig {
+    fn default() -> Self {
+        Self {
+            command: "extra-riscv64-build".to_string(),
+            workdir: None,
+            max_parallel: 1,
+            ssh_program: "ssh".to_string(),
+            hosts: Vec::new(),
+            dir: cache_dir().join("builds"),
+        }
+    }
+}
+
+/// Options for build logs
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct LogsConfig {
+    /// Where downloaded FTBFS logs are kept, one `<pkgname>.log` per package
+    pub dir: PathBuf,
+    /// URL of the build log of a package, `{pkgname}` is replaced by the package name. Only used
+    /// for packages without a log link on the status page.
+    pub url: Option<String>,
+    /// Directory of x86_64 build logs named `<pkgname>.log`, to compare the riscv64 logs with
+    pub x86_64_dir: Option<PathBuf>,
+    /// How many logs are downloaded at the same time
+    pub parallel_downloads: usize,
+    /// Extra failure classification rules, tried before the builtin ones. Declared with
+    /// `[[logs.rules]]` and the `reason`, `pattern` and optional `mark` keys.
+    pub rules: Vec<Rule>,
+}
+
+impl std::default::Default for LogsConfig {
+    fn default() -> Self {
+        Self {
+            dir: cache_dir().join("logs"),
+            url: None,
+            x86_64_dir: None,
+            parallel_downloads: 8,
+            rules: Vec::new(),
+        }
+    }
+}
+
+/// Options for `lzr serve`
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct ServeConfig {
+    /// Address to listen on, overridden by `--listen`
+    pub listen: String,
+    /// Seconds between two refreshes of the package data
+    pub refresh_interval: u64,
+}
+
+impl std::default::Default for ServeConfig {
+    fn default() -> Self {
+        Self {
+            listen: "127.0.0.1:8080".to_string(),
+            refresh_interval: 600,
+        }
+    }
+}
+
+/// Options for `lzr watch`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct WatchConfig {
+    /// Seconds between two refreshes of the package data
+    pub interval: u64,
+    /// Your alias on the melon bot, for the hooks only interested in your packages
+    pub me: Option<String>,
+    /// Commands run on package changes. Declared with `[[watch.hooks]]` and the `on` and
+    /// `command` keys.
+    pub hooks: Vec<Hook>,
+}
+
+impl std::default::Default for WatchConfig {
+    fn default() -> Self {
+        Self {
+            interval: 600,
+            me: None,
+            hooks: Vec::new(),
+        }
+    }
+}
+
+/// A command run by `sh -c` with the event as JSON on its stdin
+#[derive(Debug, Clone, Deserialize)]
+pub struct Hook {
+    /// Event name, like `status_changed`, `new_ftbfs` or `mark_added`
+    pub on: String,
+    pub command: String,
+    /// Only run for packages assigned to `watch.me`
+    #[serde(default)]
+    pub mine: bool,
+    /// Only run for packages of this repository, like `core`
+    #[serde(default)]
+    pub repo: Option<String>,
+}
+
+/// Options for the control socket of the TUI
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct ControlConfig {
+    /// Accept JSON commands from other programs while the TUI runs
+    pub enabled: bool,
+    /// Path of the Unix socket
+    pub socket: PathBuf,
+}
+
+impl std::default::Default for ControlConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            socket: runtime_dir().join("lzr.sock"),
+        }
+    }
+}
+
+impl Config {
+    /// Load the config from the default location, fallback to the default config when the file
+    /// doesn't exist.
+    pub fn load() -> anyhow::Result<Self> {
+        match config_dir() {
+            Some(dir) => Self::from_file(&dir.join("config.toml")),
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub fn from_file(path: &Path) -> anyhow::Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("fail to read config file {}", path.display()))?;
+        toml::from_str(&content)
+            .with_context(|| format!("fail to parse config file {}", path.display()))
+    }
+}
+
+/// Return `$XDG_CONFIG_HOME/lzr` or `$HOME/.config/lzr`
+pub fn config_dir() -> Option<PathBuf> {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|dir| dir.join("lzr"))
+}
+
+/// Return `$XDG_RUNTIME_DIR`, or the cache directory when it is not set
+pub fn runtime_dir() -> PathBuf {
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(cache_dir)
+}
+
+/// Return `$XDG_CACHE_HOME/lzr`, `$HOME/.cache/lzr`, or a directory under the system temporary
+/// directory when neither is set.
+pub fn cache_dir() -> PathBuf {
+    std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .unwrap_or_else(std::env::temp_dir)
+        .join("lzr")
+}
+
+#[test]
+fn test_parse_partial_config() {
+    let config: Config = toml::from_str(
+        r#"
+        [syncdb]
+        dir = "/var/lib/pacman/sync"
+        "#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        config.syncdb.dir.as_deref(),
+        Some(Path::new("/var/lib/pacman/sync"))
+    );
+    assert_eq!(config.syncdb.repos.len(), 2);
+}
+
+#[test]
+fn test_parse_watch_hooks() {
+    let config: Config = toml::from_str(
+        r#"
+        [watch]
+        me = "Moody"
+
+        [[watch.hooks]]
+        on = "new_ftbfs"
+        repo = "core"
+        command = "notify-send lzr"
+        "#,
+    )
+    .unwrap();
+
+    assert_eq!(config.watch.interval, 600);
+    assert_eq!(config.watch.me.as_deref(), Some("Moody"));
+    let hook = &config.watch.hooks[0];
+    assert_eq!(hook.on, "new_ftbfs");
+    assert_eq!(hook.repo.as_deref(), Some("core"));
+    assert!(!hook.mine);
+}
+
+#[test]
+fn test_parse_build_hosts() {
+    let config: Config = toml::from_str(
+        r#"
+        [[build.hosts]]
+        name = "unmatched"
+        ssh = "builder@unmatched"
+        workdir = "/home/builder/lzr"
+        labels = ["board"]
+
+        [[build.hosts]]
+        name = "qemu-box"
+        ssh = "qemu-box"
+        workdir = "/srv/build"
+        jobs = 4
+        "#,
+    )
+    .unwrap();
+
+    let hosts = &config.build.hosts;
+    assert_eq!(hosts.len(), 2);
+    assert!(hosts[0].has_label("board"));
+    assert_eq!(hosts[0].jobs, 1);
+    assert!(!hosts[1].has_label("board"));
+    assert_eq!(hosts[1].jobs, 4);
+    assert_eq!(config.build.ssh_program, "ssh");
+}
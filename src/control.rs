@@ -0,0 +1,147 @@
+use crate::events::Events;
+use anyhow::Context;
+use serde::Deserialize;
+use std::{path::Path, sync::mpsc};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{UnixListener, UnixStream},
+    sync::oneshot,
+};
+
+/// A command sent by another program on the control socket, one JSON object per line like
+/// `{"command": "select", "package": "bear"}`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub enum Command {
+    /// Move the cursor of the package table to the package
+    Select { package: String },
+    /// Only show the packages whose name contains `query`, all of them when it is empty
+    Filter { query: String },
+    /// Update the package data now
+    Refresh,
+    /// Return the package under the cursor and the packages marked for a bulk action
+    GetSelection,
+}
+
+/// Result of a command, written back as `{"ok": true, "result": ...}` or
+/// `{"ok": false, "error": ...}`
+pub type Reply = Result<serde_json::Value, String>;
+
+/// Accept connections on `path` and forward their commands to the main loop through `tx`. A
+/// socket left behind by a crashed lzr is replaced, but not one another lzr still listens on.
+pub fn listen(path: &Path, tx: mpsc::Sender<Events>) -> anyhow::Result<()> {
+    if path.exists() {
+        if std::os::unix::net::UnixStream::connect(path).is_ok() {
+            anyhow::bail!("another lzr is listening on {}", path.display());
+        }
+        std::fs::remove_file(path)
+            .with_context(|| format!("fail to remove stale socket {}", path.display()))?;
+    }
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("fail to create directory {}", dir.display()))?;
+    }
+    let listener = UnixListener::bind(path)
+        .with_context(|| format!("fail to listen on {}", path.display()))?;
+
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            tokio::spawn(serve_client(stream, tx.clone()));
+        }
+    });
+    Ok(())
+}
+
+/// Answer the commands of one client in order until it disconnects or the main loop is gone
+async fn serve_client(stream: UnixStream, tx: mpsc::Sender<Events>) {
+    let (read, mut write) = stream.into_split();
+    let mut lines = BufReader::new(read).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let reply = match serde_json::from_str::<Command>(&line) {
+            Ok(command) => {
+                let (reply, replied) = oneshot::channel();
+                if tx.send(Events::Control { command, reply }).is_err() {
+                    return;
+                }
+                replied
+                    .await
+                    .unwrap_or_else(|_| Err("lzr is shutting down".to_string()))
+            }
+            Err(err) => Err(format!("invalid command: {err}")),
+        };
+
+        let response = match reply {
+            Ok(result) => serde_json::json!({ "ok": true, "result": result }),
+            Err(error) => serde_json::json!({ "ok": false, "error": error }),
+        };
+        let mut response = response.to_string();
+        response.push('\n');
+        if write.write_all(response.as_bytes()).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Remove the socket when the TUI exits
+pub fn remove(path: &Path) {
+    let _ = std::fs::remove_file(path);
+}
+
+#[test]
+fn test_parse_command() {
+    let parse = |json: &str| serde_json::from_str::<Command>(json);
+    assert_eq!(
+        parse(r#"{"command": "select", "package": "bear"}"#).unwrap(),
+        Command::Select {
+            package: "bear".to_string()
+        }
+    );
+    assert_eq!(
+        parse(r#"{"command": "get-selection"}"#).unwrap(),
+        Command::GetSelection
+    );
+    assert!(parse(r#"{"command": "select"}"#).is_err());
+    assert!(parse(r#"{"command": "quit"}"#).is_err());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_listen() {
+    let dir = std::env::temp_dir().join(format!("lzr-test-control-{}", std::process::id()));
+    let path = dir.join("lzr.sock");
+    let (tx, rx) = mpsc::channel();
+    listen(&path, tx.clone()).unwrap();
+    // the socket is in use now
+    assert!(listen(&path, tx).is_err());
+
+    // stand-in for the main loop
+    std::thread::spawn(move || {
+        while let Ok(Events::Control { command, reply }) = rx.recv() {
+            let _ = reply.send(match command {
+                Command::Refresh => Ok(serde_json::Value::Null),
+                _ => Err("no package".to_string()),
+            });
+        }
+    });
+
+    let stream = UnixStream::connect(&path).await.unwrap();
+    let (read, mut write) = stream.into_split();
+    let mut lines = BufReader::new(read).lines();
+    write
+        .write_all(b"{\"command\": \"refresh\"}\n\nnonsense\n{\"command\": \"select\", \"package\": \"x\"}\n")
+        .await
+        .unwrap();
+
+    let mut replies = Vec::new();
+    for _ in 0..3 {
+        replies.push(lines.next_line().await.unwrap().unwrap());
+    }
+    assert_eq!(replies[0], r#"{"ok":true,"result":null}"#);
+    assert!(replies[1].starts_with(r#"{"error":"invalid command: "#));
+    assert_eq!(replies[2], r#"{"error":"no package","ok":false}"#);
+
+    remove(&path);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
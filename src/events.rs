@@ -1,5 +1,46 @@
-use crossterm::event::KeyCode;
+use crate::{control, logs::stages::StageOutline};
+use crossterm::event::{KeyEvent, MouseEvent};
+use std::time::Duration;
+use tokio::sync::oneshot;
 
 pub enum Events {
-    KeyEvent(KeyCode),
+    KeyEvent(KeyEvent),
+    Mouse(MouseEvent),
+    /// A line printed by a running build
+    BuildOutput {
+        id: usize,
+        line: String,
+    },
+    /// A build exit. `code` is `None` when the build is killed by signal.
+    BuildFinished {
+        id: usize,
+        code: Option<i32>,
+        duration: Duration,
+    },
+    /// A build process that can't be started
+    BuildFailed {
+        id: usize,
+        error: String,
+    },
+    /// A build log download ended, successfully when `error` is `None`
+    LogDownloaded {
+        pkgname: Box<str>,
+        error: Option<String>,
+    },
+    /// Whole lines of a log being read in the background
+    LogChunk {
+        id: usize,
+        text: String,
+    },
+    /// A log has been read to the end, or until `error`
+    LogRead {
+        id: usize,
+        outline: StageOutline,
+        error: Option<String>,
+    },
+    /// A command received on the control socket, answered through `reply`
+    Control {
+        command: control::Command,
+        reply: oneshot::Sender<control::Reply>,
+    },
 }
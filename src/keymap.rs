@@ -0,0 +1,697 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Something a key sequence can be bound to. The behaviour of most actions depends on the
+/// current display, like `next` going to the next stage of a log or the next hunk of a diff.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    Up,
+    Down,
+    Top,
+    Bottom,
+    Select,
+    Back,
+    RebuildOrder,
+    ExportOrder,
+    ToggleOutdated,
+    ShowPatch,
+    ShowWarnings,
+    PreparePatch,
+    ToggleMark,
+    Build,
+    BuildQueue,
+    CancelBuild,
+    RetryBuild,
+    DownloadLogs,
+    Clusters,
+    Search,
+    ToggleFold,
+    ToggleFoldAll,
+    JumpToFailure,
+    Next,
+    Previous,
+    Help,
+}
+
+/// Names of the actions in the config file, and what they do
+const ACTIONS: [(&str, Action, &str); 27] = [
+    ("quit", Action::Quit, "Quit lzr, or close the help"),
+    ("up", Action::Up, "Move up"),
+    ("down", Action::Down, "Move down"),
+    ("top", Action::Top, "Go to the first row or line"),
+    ("bottom", Action::Bottom, "Go to the last row or line"),
+    (
+        "select",
+        Action::Select,
+        "Open the package menu, or choose the item",
+    ),
+    ("back", Action::Back, "Go back to the package table"),
+    (
+        "rebuild-order",
+        Action::RebuildOrder,
+        "Show the rebuild order of unclaimed failures",
+    ),
+    (
+        "export-order",
+        Action::ExportOrder,
+        "Write the rebuild order to a file",
+    ),
+    (
+        "toggle-outdated",
+        Action::ToggleOutdated,
+        "Show only outdated packages, or all of them",
+    ),
+    (
+        "show-patch",
+        Action::ShowPatch,
+        "Show the riscv64 patch of the package",
+    ),
+    (
+        "show-warnings",
+        Action::ShowWarnings,
+        "Show the status page rows that couldn't be read",
+    ),
+    (
+        "prepare-patch",
+        Action::PreparePatch,
+        "Prepare a riscv64 patch of the package",
+    ),
+    (
+        "toggle-mark",
+        Action::ToggleMark,
+        "Mark the package for a bulk action",
+    ),
+    (
+        "build",
+        Action::Build,
+        "Build the marked packages, or the selected one",
+    ),
+    (
+        "build-queue",
+        Action::BuildQueue,
+        "Show the local build queue",
+    ),
+    (
+        "cancel-build",
+        Action::CancelBuild,
+        "Cancel the selected build",
+    ),
+    (
+        "retry-build",
+        Action::RetryBuild,
+        "Queue the selected build again",
+    ),
+    (
+        "download-logs",
+        Action::DownloadLogs,
+        "Download the build logs of the FTBFS packages",
+    ),
+    (
+        "clusters",
+        Action::Clusters,
+        "Group the FTBFS packages by failure",
+    ),
+    ("search", Action::Search, "Search the build logs"),
+    (
+        "toggle-fold",
+        Action::ToggleFold,
+        "Fold or unfold the log stage under the cursor",
+    ),
+    (
+        "toggle-fold-all",
+        Action::ToggleFoldAll,
+        "Fold or unfold every log stage",
+    ),
+    (
+        "jump-to-failure",
+        Action::JumpToFailure,
+        "Jump to the first error of the log",
+    ),
+    (
+        "next",
+        Action::Next,
+        "Go to the next log stage or diff hunk",
+    ),
+    (
+        "previous",
+        Action::Previous,
+        "Go to the previous log stage or diff hunk",
+    ),
+    ("help", Action::Help, "Show this help"),
+];
+
+impl Action {
+    pub fn name(self) -> &'static str {
+        ACTIONS
+            .iter()
+            .find(|(_, action, _)| *action == self)
+            .map(|(name, _, _)| *name)
+            .unwrap()
+    }
+
+    pub fn description(self) -> &'static str {
+        ACTIONS
+            .iter()
+            .find(|(_, action, _)| *action == self)
+            .map(|(_, _, description)| *description)
+            .unwrap()
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        ACTIONS
+            .iter()
+            .find(|(n, _, _)| *n == name)
+            .map(|(_, action, _)| *action)
+    }
+}
+
+/// Keymap sections of the config file, `global` applies to every display and the others to the
+/// display of the same name
+pub const MODES: [&str; 15] = [
+    "global",
+    "table",
+    "menu",
+    "dependents",
+    "rebuild-order",
+    "patch",
+    "warnings",
+    "log",
+    "log-picker",
+    "log-diff",
+    "clusters",
+    "search",
+    "build",
+    "build-queue",
+    "help",
+];
+
+/// Bindings used when the config doesn't override them, all in the `global` section
+const DEFAULT_KEYS: [(&str, Action); 29] = [
+    ("q", Action::Quit),
+    ("<Up>", Action::Up),
+    ("k", Action::Up),
+    ("<Down>", Action::Down),
+    ("j", Action::Down),
+    ("gg", Action::Top),
+    ("G", Action::Bottom),
+    ("<Enter>", Action::Select),
+    ("<Esc>", Action::Back),
+    ("o", Action::RebuildOrder),
+    ("w", Action::ExportOrder),
+    ("O", Action::ToggleOutdated),
+    ("p", Action::ShowPatch),
+    ("W", Action::ShowWarnings),
+    ("e", Action::PreparePatch),
+    ("<Space>", Action::ToggleMark),
+    ("b", Action::Build),
+    ("B", Action::BuildQueue),
+    ("c", Action::CancelBuild),
+    ("r", Action::RetryBuild),
+    ("D", Action::DownloadLogs),
+    ("C", Action::Clusters),
+    ("/", Action::Search),
+    ("<Tab>", Action::ToggleFold),
+    ("z", Action::ToggleFoldAll),
+    ("f", Action::JumpToFailure),
+    ("n", Action::Next),
+    ("N", Action::Previous),
+    ("?", Action::Help),
+];
+
+/// Keys written between angle brackets, other than single characters
+const NAMED_KEYS: [(&str, KeyCode); 15] = [
+    ("Enter", KeyCode::Enter),
+    ("Esc", KeyCode::Esc),
+    ("Tab", KeyCode::Tab),
+    ("BackTab", KeyCode::BackTab),
+    ("Backspace", KeyCode::Backspace),
+    ("Delete", KeyCode::Delete),
+    ("Insert", KeyCode::Insert),
+    ("Up", KeyCode::Up),
+    ("Down", KeyCode::Down),
+    ("Left", KeyCode::Left),
+    ("Right", KeyCode::Right),
+    ("Home", KeyCode::Home),
+    ("End", KeyCode::End),
+    ("PageUp", KeyCode::PageUp),
+    ("PageDown", KeyCode::PageDown),
+];
+
+/// A key press with its modifiers. Shift is part of the character for character keys, so `G`
+/// is `G` rather than shift and `g`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Key {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl Key {
+    pub fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        let code = match code {
+            KeyCode::Tab if modifiers.contains(KeyModifiers::SHIFT) => KeyCode::BackTab,
+            code => code,
+        };
+        let mut kept = KeyModifiers::CONTROL | KeyModifiers::ALT;
+        if !matches!(code, KeyCode::Char(_) | KeyCode::BackTab) {
+            kept |= KeyModifiers::SHIFT;
+        }
+        Self {
+            code,
+            modifiers: modifiers & kept,
+        }
+    }
+}
+
+impl From<KeyEvent> for Key {
+    fn from(event: KeyEvent) -> Self {
+        Self::new(event.code, event.modifiers)
+    }
+}
+
+impl From<KeyCode> for Key {
+    fn from(code: KeyCode) -> Self {
+        Self::new(code, KeyModifiers::NONE)
+    }
+}
+
+impl std::fmt::Display for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self.code {
+            KeyCode::Char(' ') => "Space".to_string(),
+            KeyCode::Char('<') => "lt".to_string(),
+            KeyCode::Char(c) => c.to_string(),
+            KeyCode::F(n) => format!("F{n}"),
+            code => match NAMED_KEYS.iter().find(|(_, named)| *named == code) {
+                Some((name, _)) => name.to_string(),
+                None => "?".to_string(),
+            },
+        };
+        let mut modifiers = String::new();
+        for (modifier, prefix) in [
+            (KeyModifiers::CONTROL, "C-"),
+            (KeyModifiers::ALT, "A-"),
+            (KeyModifiers::SHIFT, "S-"),
+        ] {
+            if self.modifiers.contains(modifier) {
+                modifiers.push_str(prefix);
+            }
+        }
+        if modifiers.is_empty() && name.chars().count() == 1 {
+            f.write_str(&name)
+        } else {
+            write!(f, "<{modifiers}{name}>")
+        }
+    }
+}
+
+/// Read a key sequence like `gg`, `<C-d>` or `<Space>m`
+pub fn parse_keys(text: &str) -> anyhow::Result<Vec<Key>> {
+    let mut keys = Vec::new();
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        if c != '<' || rest == "<" {
+            keys.push(Key::from(KeyCode::Char(c)));
+            rest = &rest[c.len_utf8()..];
+            continue;
+        }
+        let end = rest
+            .find('>')
+            .ok_or_else(|| anyhow::anyhow!("unclosed `<` in key sequence `{text}`"))?;
+        keys.push(parse_named(&rest[1..end]).ok_or_else(|| {
+            anyhow::anyhow!("unknown key `{}` in key sequence `{text}`", &rest[..=end])
+        })?);
+        rest = &rest[end + 1..];
+    }
+    if keys.is_empty() {
+        anyhow::bail!("empty key sequence");
+    }
+    Ok(keys)
+}
+
+/// Read the inside of `<...>`, like `C-d`, `Enter` or `F5`
+fn parse_named(mut name: &str) -> Option<Key> {
+    let mut modifiers = KeyModifiers::NONE;
+    loop {
+        let modifier = match name.get(..2).map(str::to_ascii_uppercase).as_deref() {
+            Some("C-") => KeyModifiers::CONTROL,
+            Some("A-") | Some("M-") => KeyModifiers::ALT,
+            Some("S-") => KeyModifiers::SHIFT,
+            _ => break,
+        };
+        // `<C->` would be an empty key, `<C-->` is control and `-`
+        if name.len() == 2 {
+            break;
+        }
+        modifiers |= modifier;
+        name = &name[2..];
+    }
+
+    let mut chars = name.chars();
+    let code = match (chars.next(), chars.next()) {
+        (Some(c), None) if modifiers.contains(KeyModifiers::SHIFT) => {
+            modifiers.remove(KeyModifiers::SHIFT);
+            KeyCode::Char(c.to_ascii_uppercase())
+        }
+        (Some(c), None) => KeyCode::Char(c),
+        _ if name.eq_ignore_ascii_case("space") => KeyCode::Char(' '),
+        _ if name.eq_ignore_ascii_case("lt") => KeyCode::Char('<'),
+        _ => match name.strip_prefix(['F', 'f']).map(str::parse::<u8>) {
+            Some(Ok(n)) if (1..=12).contains(&n) => KeyCode::F(n),
+            _ => NAMED_KEYS
+                .iter()
+                .find(|(named, _)| named.eq_ignore_ascii_case(name))
+                .map(|(_, code)| *code)?,
+        },
+    };
+    Some(Key::new(code, modifiers))
+}
+
+/// Key sequences of a display, sharing their prefixes
+#[derive(Debug, Clone, Default)]
+struct KeyTrie {
+    action: Option<Action>,
+    children: HashMap<Key, KeyTrie>,
+}
+
+impl KeyTrie {
+    /// Every bound sequence under this node, `prefix` being the keys leading to it
+    fn bindings(&self, prefix: &mut Vec<Key>, bindings: &mut Vec<(Vec<Key>, Action)>) {
+        if let Some(action) = self.action {
+            bindings.push((prefix.clone(), action));
+        }
+        for (key, child) in self.children.iter() {
+            prefix.push(*key);
+            child.bindings(prefix, bindings);
+            prefix.pop();
+        }
+    }
+
+    fn get(&self, keys: &[Key]) -> Option<&KeyTrie> {
+        keys.iter()
+            .try_fold(self, |node, key| node.children.get(key))
+    }
+
+    /// Bind `keys`, refusing a sequence that starts with another bound one or that another bound
+    /// one starts with, as one of them could never be typed
+    fn insert(&mut self, keys: &[Key], action: Action) -> Result<(), String> {
+        let mut node = self;
+        for (i, key) in keys.iter().enumerate() {
+            if node.action.is_some() {
+                return Err(format_keys(&keys[..i]));
+            }
+            node = node.children.entry(*key).or_default();
+        }
+        if !node.children.is_empty() {
+            return Err(format_keys(keys));
+        }
+        node.action = Some(action);
+        Ok(())
+    }
+}
+
+/// What a key sequence means in a display
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lookup {
+    Action(Action),
+    /// The keys are the start of a longer sequence
+    Pending,
+    Unbound,
+}
+
+/// Key bindings of each display. Read from the `[keys.<mode>]` tables of the config, mapping
+/// key sequences to action names, on top of the defaults. Binding a key to `none` removes its
+/// default binding.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "HashMap<String, HashMap<String, String>>")]
+pub struct Keymap {
+    tries: HashMap<&'static str, KeyTrie>,
+}
+
+impl std::default::Default for Keymap {
+    fn default() -> Self {
+        Self::with_overrides(HashMap::new()).expect("invalid default key bindings")
+    }
+}
+
+impl TryFrom<HashMap<String, HashMap<String, String>>> for Keymap {
+    type Error = String;
+
+    fn try_from(sections: HashMap<String, HashMap<String, String>>) -> Result<Self, String> {
+        Self::with_overrides(sections)
+    }
+}
+
+impl Keymap {
+    fn with_overrides(sections: HashMap<String, HashMap<String, String>>) -> Result<Self, String> {
+        let mut bindings: HashMap<&'static str, HashMap<Vec<Key>, Action>> = HashMap::new();
+        let global = bindings.entry("global").or_default();
+        for (keys, action) in DEFAULT_KEYS {
+            global.insert(parse_keys(keys).unwrap(), action);
+        }
+
+        for (mode, keys) in sections {
+            let mode = MODES
+                .into_iter()
+                .find(|m| *m == mode)
+                .ok_or_else(|| format!("unknown keys section `{mode}`"))?;
+            let section = bindings.entry(mode).or_default();
+            for (sequence, action) in keys {
+                let keys = parse_keys(&sequence).map_err(|err| err.to_string())?;
+                if action == "none" {
+                    section.remove(&keys);
+                    continue;
+                }
+                let action = Action::from_name(&action)
+                    .ok_or_else(|| format!("unknown action `{action}` for `{sequence}`"))?;
+                section.insert(keys, action);
+            }
+        }
+
+        let mut tries = HashMap::new();
+        for (mode, section) in bindings {
+            // bind in a stable order so the same conflict is always reported
+            let mut section = section.into_iter().collect::<Vec<_>>();
+            section.sort_by_key(|(keys, _)| (keys.len(), format_keys(keys)));
+            let trie: &mut KeyTrie = tries.entry(mode).or_default();
+            for (keys, action) in section {
+                trie.insert(&keys, action).map_err(|prefix| {
+                    format!(
+                        "`{}` in [keys.{mode}] can't be typed, `{prefix}` is bound too",
+                        format_keys(&keys)
+                    )
+                })?;
+            }
+        }
+        Ok(Self { tries })
+    }
+
+    /// Find what `keys` are bound to in the display named `mode`. Its own bindings are tried
+    /// before the global ones.
+    pub fn lookup(&self, mode: &str, keys: &[Key]) -> Lookup {
+        for name in [mode, "global"] {
+            if let Some(node) = self.tries.get(name).and_then(|trie| trie.get(keys)) {
+                return match node.action {
+                    Some(action) => Lookup::Action(action),
+                    None => Lookup::Pending,
+                };
+            }
+        }
+        Lookup::Unbound
+    }
+}
+
+impl Keymap {
+    /// Bindings of the `mode` section, ordered by action then by length
+    pub fn bindings(&self, mode: &str) -> Vec<(String, Action)> {
+        let mut bindings = Vec::new();
+        if let Some(trie) = self.tries.get(mode) {
+            trie.bindings(&mut Vec::new(), &mut bindings);
+        }
+        let mut bindings = bindings
+            .into_iter()
+            .map(|(keys, action)| (format_keys(&keys), action))
+            .collect::<Vec<_>>();
+        bindings.sort_by_key(|(keys, action)| {
+            let order = ACTIONS.iter().position(|(_, a, _)| a == action);
+            (order, keys.chars().count(), keys.clone())
+        });
+        bindings
+    }
+
+    /// The shortest key sequence running `action` in the display named `mode`, if any
+    pub fn keys_for(&self, mode: &str, action: Action) -> Option<String> {
+        [mode, "global"]
+            .into_iter()
+            .flat_map(|section| self.bindings(section))
+            .filter(|(keys, a)| {
+                // a global binding may be hidden by one of the display
+                *a == action
+                    && parse_keys(keys)
+                        .is_ok_and(|keys| self.lookup(mode, &keys) == Lookup::Action(action))
+            })
+            .map(|(keys, _)| keys)
+            .min_by_key(|keys| keys.chars().count())
+    }
+}
+
+/// Write a key sequence back the way it is configured
+pub fn format_keys(keys: &[Key]) -> String {
+    keys.iter().map(ToString::to_string).collect()
+}
+
+#[test]
+fn test_parse_keys() {
+    let keys = parse_keys("g<C-d><Space><lt>G<S-Tab><F5><a-X>").unwrap();
+    assert_eq!(
+        keys,
+        [
+            Key::from(KeyCode::Char('g')),
+            Key::new(KeyCode::Char('d'), KeyModifiers::CONTROL),
+            Key::from(KeyCode::Char(' ')),
+            Key::from(KeyCode::Char('<')),
+            Key::from(KeyCode::Char('G')),
+            Key::from(KeyCode::BackTab),
+            Key::from(KeyCode::F(5)),
+            Key::new(KeyCode::Char('X'), KeyModifiers::ALT),
+        ]
+    );
+    assert_eq!(format_keys(&keys), "g<C-d><Space><lt>G<BackTab><F5><A-X>");
+    assert_eq!(parse_keys("<S-g>").unwrap(), parse_keys("G").unwrap());
+    assert_eq!(parse_keys("<").unwrap(), parse_keys("<lt>").unwrap());
+
+    assert!(parse_keys("").is_err());
+    assert!(parse_keys("<C-d").is_err());
+    let err = parse_keys("a<Foo>").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "unknown key `<Foo>` in key sequence `a<Foo>`"
+    );
+
+    // terminals report shifted letters with the shift modifier
+    let event = KeyEvent::new(KeyCode::Char('G'), KeyModifiers::SHIFT);
+    assert_eq!(Key::from(event), Key::from(KeyCode::Char('G')));
+    let event = KeyEvent::new(KeyCode::Up, KeyModifiers::SHIFT);
+    assert_eq!(Key::from(event).to_string(), "<S-Up>");
+}
+
+#[test]
+fn test_lookup() {
+    let config: HashMap<String, HashMap<String, String>> = toml::from_str(
+        r#"
+        [global]
+        "<C-d>" = "down"
+        "j" = "down"
+        "k" = "up"
+        "gg" = "none"
+        "ma" = "toggle-mark"
+
+        [log]
+        "j" = "next"
+        "g" = "top"
+        "#,
+    )
+    .unwrap();
+    let keymap = Keymap::try_from(config).unwrap();
+    let keys = |text| parse_keys(text).unwrap();
+
+    assert_eq!(
+        keymap.lookup("table", &keys("<C-d>")),
+        Lookup::Action(Action::Down)
+    );
+    assert_eq!(
+        keymap.lookup("table", &keys("j")),
+        Lookup::Action(Action::Down)
+    );
+    assert_eq!(keymap.lookup("table", &keys("m")), Lookup::Pending);
+    assert_eq!(
+        keymap.lookup("table", &keys("ma")),
+        Lookup::Action(Action::ToggleMark)
+    );
+    assert_eq!(keymap.lookup("table", &keys("mb")), Lookup::Unbound);
+    assert_eq!(keymap.lookup("table", &keys("g")), Lookup::Unbound);
+    // defaults are kept
+    assert_eq!(
+        keymap.lookup("table", &keys("G")),
+        Lookup::Action(Action::Bottom)
+    );
+    // display bindings come first
+    assert_eq!(
+        keymap.lookup("log", &keys("j")),
+        Lookup::Action(Action::Next)
+    );
+    assert_eq!(
+        keymap.lookup("log", &keys("g")),
+        Lookup::Action(Action::Top)
+    );
+
+    let keymap = Keymap::default();
+    assert_eq!(keymap.lookup("table", &keys("g")), Lookup::Pending);
+    assert_eq!(
+        keymap.lookup("table", &keys("gg")),
+        Lookup::Action(Action::Top)
+    );
+}
+
+#[test]
+fn test_bindings() {
+    let config: HashMap<String, HashMap<String, String>> = toml::from_str(
+        r#"
+        [log]
+        "<C-n>" = "next"
+        "n" = "down"
+        "#,
+    )
+    .unwrap();
+    let keymap = Keymap::try_from(config).unwrap();
+
+    assert_eq!(
+        keymap.bindings("log"),
+        [
+            ("n".to_string(), Action::Down),
+            ("<C-n>".to_string(), Action::Next)
+        ]
+    );
+    assert_eq!(
+        &keymap.bindings("global")[..2],
+        [
+            ("q".to_string(), Action::Quit),
+            ("k".to_string(), Action::Up)
+        ]
+    );
+    assert!(keymap.bindings("table").is_empty());
+
+    assert_eq!(keymap.keys_for("table", Action::Next).as_deref(), Some("n"));
+    assert_eq!(
+        keymap.keys_for("log", Action::Next).as_deref(),
+        Some("<C-n>")
+    );
+    assert_eq!(keymap.keys_for("log", Action::Down).as_deref(), Some("n"));
+    assert_eq!(keymap.keys_for("log", Action::Top).as_deref(), Some("gg"));
+}
+
+#[test]
+fn test_invalid_keymap() {
+    let parse = |text: &str| toml::from_str::<HashMap<String, Keymap>>(text).map(|_| ());
+
+    let err = parse("[keys.global]\nx = \"jump\"").unwrap_err();
+    assert!(
+        err.to_string().contains("unknown action `jump` for `x`"),
+        "{err}"
+    );
+    let err = parse("[keys.table]\n\"<Foo>\" = \"up\"").unwrap_err();
+    assert!(err.to_string().contains("unknown key `<Foo>`"), "{err}");
+    let err = parse("[keys.tabel]\nx = \"up\"").unwrap_err();
+    assert!(
+        err.to_string().contains("unknown keys section `tabel`"),
+        "{err}"
+    );
+    let err = parse("[keys.global]\ng = \"top\"").unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("`gg` in [keys.global] can't be typed, `g` is bound too"),
+        "{err}"
+    );
+    assert!(parse("[keys.global]\ng = \"top\"\ngg = \"none\"").is_ok());
+}
@@ -1,20 +1,34 @@
 mod app;
+mod build;
 pub mod canvas;
+/// Typed access to the status page of the riscv64 port and to the melon bot, for tools that
+/// want the same data without the TUI. The merged package rows of the table are exported as
+/// [`client::PkgInfo`].
+pub mod client;
 mod component;
+mod config;
+mod control;
 mod events;
+mod keymap;
+mod logs;
+mod metrics;
+mod pacman;
+mod patches;
 mod req;
+mod serve;
 mod tabs;
 mod types;
+mod watch;
 
 use anyhow::Context;
 use crossterm::{
-    event::KeyCode,
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use std::{
     io,
     sync::{atomic::AtomicBool, mpsc, Arc},
+    time::Duration,
 };
 use tui::{
     backend::{Backend, CrosstermBackend},
@@ -42,19 +56,33 @@ pub fn setup_crossterm_terminal() -> anyhow::Result<Terminal<CrosstermBackend<st
 }
 
 pub async fn run() -> anyhow::Result<()> {
+    // a broken config is reported before the terminal is switched to raw mode
+    let config = config::Config::load()?;
     let mut terminal = setup_crossterm_terminal()?;
-    let mut app_data = app::App::default();
+    let mut app_data = app::App::new(config);
 
     let (tx, rx) = mpsc::channel();
+    app_data.set_event_sender(tx.clone());
 
-    spawn_terminal_event_sender(tx, Arc::clone(&app_data.is_running));
+    // the TUI is still usable without its socket, the error is printed once it exits
+    let socket = app_data.config.control.socket.clone();
+    let control = match app_data.config.control.enabled {
+        true => Some(control::listen(&socket, tx.clone())),
+        false => None,
+    };
+
+    spawn_terminal_event_sender(
+        tx,
+        Arc::clone(&app_data.is_running),
+        Arc::clone(&app_data.is_suspended),
+    );
 
     let mut first_run = true;
 
     while app_data.is_running() {
         if first_run {
             canvas::draw_welcome_page(&mut terminal)?;
-            app_data.update().unwrap();
+            app_data.refresh();
             first_run = false;
         }
 
@@ -67,11 +95,82 @@ pub async fn run() -> anyhow::Result<()> {
             .recv()
             .with_context(|| "Event channel close unexpectedly")?;
         match event {
-            events::Events::KeyEvent(keycode) => app_data.handle_input(keycode),
+            events::Events::KeyEvent(key) => app_data.handle_input(key),
+            events::Events::Mouse(mouse) => app_data.handle_mouse(mouse),
+            events::Events::BuildOutput { id, line } => app_data.handle_build_output(id, line),
+            events::Events::LogChunk { id, text } => app_data.handle_log_chunk(id, text),
+            events::Events::LogRead { id, outline, error } => {
+                app_data.handle_log_read(id, outline, error)
+            }
+            events::Events::LogDownloaded { pkgname, error } => {
+                app_data.handle_log_downloaded(&pkgname, error)
+            }
+            events::Events::BuildFinished { id, code, duration } => {
+                app_data.handle_build_finished(id, code, duration)
+            }
+            events::Events::BuildFailed { id, error } => app_data.handle_build_failed(id, error),
+            events::Events::Control { command, reply } => {
+                let _ = reply.send(app_data.handle_control(command));
+            }
+        }
+
+        if let Some(pkgname) = app_data.take_pending_patch() {
+            let is_suspended = Arc::clone(&app_data.is_suspended);
+            terminal = suspend_terminal(terminal, &is_suspended, || {
+                let result = patches::scaffold(&app_data.config.patches, &pkgname);
+                app_data.finish_patch_scaffold(&pkgname, result);
+            })?;
         }
     }
 
     clean_up_terminal(&mut terminal)?;
+    match control {
+        Some(Ok(())) => control::remove(&socket),
+        Some(Err(err)) => eprintln!("control socket disabled: {err:#}"),
+        None => (),
+    }
+    Ok(())
+}
+
+/// Print the log lines matching `pattern` like grep does, prefixed by the package name and line
+/// number
+pub fn grep_logs(pattern: &str) -> anyhow::Result<()> {
+    let app_data = app::App::new(config::Config::load()?);
+    for hit in app_data.search_logs(pattern, usize::MAX)? {
+        println!("{}:{}: {}", hit.pkgname, hit.line + 1, hit.text);
+    }
+    Ok(())
+}
+
+/// Serve the package data over HTTP on `listen`, or on the configured address when `None`
+pub async fn serve(listen: Option<&str>) -> anyhow::Result<()> {
+    let config = config::Config::load()?;
+    let listen = listen.unwrap_or(&config.serve.listen);
+    let listen = listen
+        .parse()
+        .with_context(|| format!("invalid listen address {listen}"))?;
+    serve::serve(config, listen).await
+}
+
+/// Refresh the package data on an interval and run the configured hooks on the changes
+pub async fn watch() -> anyhow::Result<()> {
+    watch::watch(config::Config::load()?).await
+}
+
+/// Fetch the package data and print the suggested rebuild order to stdout, one package per line.
+/// Dependency cycles are reported on stderr.
+pub async fn print_rebuild_order() -> anyhow::Result<()> {
+    let mut app_data = app::App::new(config::Config::load()?);
+    app_data.update()?;
+
+    let order = app_data.rebuild_order();
+    for name in order.order.iter() {
+        println!("{name}");
+    }
+    for cycle in order.cycles.iter() {
+        eprintln!("dependency cycle: {}", cycle.join(" "));
+    }
+
     Ok(())
 }
 
@@ -90,29 +189,402 @@ pub fn clean_up_terminal<B: Backend + std::io::Write>(
     Ok(())
 }
 
+/// Give the terminal back to the user to run `f`, then take it again. The event sender is paused
+/// meanwhile, so key strokes go to the programs run by `f` instead of the TUI.
+fn suspend_terminal<F>(
+    mut terminal: Terminal<CrosstermBackend<std::io::Stdout>>,
+    is_suspended: &AtomicBool,
+    f: F,
+) -> anyhow::Result<Terminal<CrosstermBackend<std::io::Stdout>>>
+where
+    F: FnOnce(),
+{
+    is_suspended.store(true, std::sync::atomic::Ordering::SeqCst);
+    // let the event sender finish its current poll
+    std::thread::sleep(EVENT_POLL_INTERVAL);
+    clean_up_terminal(&mut terminal)?;
+
+    f();
+
+    let mut terminal = setup_crossterm_terminal()?;
+    terminal.clear()?;
+    is_suspended.store(false, std::sync::atomic::Ordering::SeqCst);
+    Ok(terminal)
+}
+
 fn render<B: Backend>(terminal: &mut Terminal<B>, app: &mut app::App) -> anyhow::Result<()> {
-    terminal.draw(|frame| match app.current_display() {
+    terminal.draw(|frame| {
+        draw_display(frame, app);
+        match app.update_error {
+            Some(ref err) => canvas::draw_error_bar(frame, &format!("fail to update: {err}")),
+            None => canvas::draw_hints_bar(frame, &app.hints()),
+        }
+        if let Some(ref help) = app.help {
+            canvas::draw_help_overlay(frame, help);
+        }
+        if let app::InputMode::Search(ref query) = app.input_mode {
+            canvas::draw_search_prompt(frame, query);
+        }
+        if let Some(keys) = app.pending_keys() {
+            canvas::draw_pending_keys(frame, &keys);
+        }
+    })?;
+    Ok(())
+}
+
+fn draw_display<B: Backend>(frame: &mut tui::Frame<B>, app: &mut app::App) {
+    match app.current_display_mut() {
         app::DisplayMode::ViewingPackageStatusTable => {
             canvas::draw_pkg_table_frame(frame, &mut app.pkg_info_table);
         }
         app::DisplayMode::PopUpPstMenu(items) => {
             canvas::draw_popup_menu_frame(frame, items);
         }
-    })?;
-    Ok(())
+        app::DisplayMode::ViewingDependents(list) => {
+            canvas::draw_pkg_list_frame(frame, list);
+        }
+        app::DisplayMode::ViewingRebuildOrder(view) => {
+            canvas::draw_rebuild_order_frame(frame, view);
+        }
+        app::DisplayMode::ViewingPatch(view) | app::DisplayMode::ViewingWarnings(view) => {
+            canvas::draw_text_view_frame(frame, view);
+        }
+        app::DisplayMode::ViewingLog(view) => {
+            canvas::draw_log_frame(frame, view);
+        }
+        app::DisplayMode::PickingLogDiff(picker) => {
+            canvas::draw_pkg_list_frame(frame, &mut picker.list);
+        }
+        app::DisplayMode::ViewingLogDiff(view) => {
+            canvas::draw_log_diff_frame(frame, view);
+        }
+        app::DisplayMode::ViewingClusters(view) => {
+            canvas::draw_clusters_frame(frame, view);
+        }
+        app::DisplayMode::ViewingSearch(view) => {
+            canvas::draw_pkg_list_frame(frame, &mut view.list);
+        }
+        app::DisplayMode::ViewingBuild(id) => {
+            let id = *id;
+            canvas::draw_build_frame(frame, app.build_queue.get_mut(id));
+        }
+        app::DisplayMode::ViewingBuildQueue => {
+            canvas::draw_build_queue_frame(frame, &mut app.build_queue);
+        }
+    }
 }
 
-fn spawn_terminal_event_sender(tx: mpsc::Sender<events::Events>, app_stats: Arc<AtomicBool>) {
+const EVENT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+fn spawn_terminal_event_sender(
+    tx: mpsc::Sender<events::Events>,
+    app_stats: Arc<AtomicBool>,
+    suspended: Arc<AtomicBool>,
+) {
     std::thread::spawn(move || {
         while is_running!(app_stats) {
+            if suspended.load(std::sync::atomic::Ordering::SeqCst) {
+                std::thread::sleep(EVENT_POLL_INTERVAL);
+                continue;
+            }
+            if !crossterm::event::poll(EVENT_POLL_INTERVAL).unwrap() {
+                continue;
+            }
             let event = crossterm::event::read().unwrap();
-            #[allow(clippy::single_match)]
             match event {
                 crossterm::event::Event::Key(key) => {
-                    tx.send(events::Events::KeyEvent(key.code)).unwrap()
+                    tx.send(events::Events::KeyEvent(key)).unwrap()
+                }
+                crossterm::event::Event::Mouse(mouse) => {
+                    tx.send(events::Events::Mouse(mouse)).unwrap()
                 }
                 _ => (),
             };
         }
     });
 }
+
+/// Feed `keys` to the app one by one, written like the key bindings of the config such as
+/// `gg<Down>` or `<C-d>`, then render the screen
+#[cfg(test)]
+fn press_keys(app: &mut app::App, keys: &str) -> tui::buffer::Buffer {
+    if !keys.is_empty() {
+        for key in keymap::parse_keys(keys).unwrap() {
+            app.handle_input(crossterm::event::KeyEvent::new(key.code, key.modifiers));
+        }
+    }
+
+    let mut terminal = Terminal::new(tui::backend::TestBackend::new(100, 24)).unwrap();
+    render(&mut terminal, app).unwrap();
+    terminal.backend().buffer().clone()
+}
+
+#[cfg(test)]
+fn test_app() -> app::App {
+    use component::packages::PkgInfoBuilder;
+
+    // keep the builds and logs of the developer machine out of the snapshots
+    let dir = std::env::temp_dir().join(format!("lzr-test-app-{}", std::process::id()));
+    let mut config = config::Config::default();
+    config.build.dir = dir.join("builds");
+    config.logs.dir = dir.join("logs");
+
+    let mut app = app::App::new(config);
+    app.pkg_info_table.data = ["bear", "ghc", "nodejs", "zsh"]
+        .into_iter()
+        .map(|name| {
+            PkgInfoBuilder::default()
+                .name(name.into())
+                .process("FTBFS".into())
+                .build()
+                .unwrap()
+        })
+        .collect();
+    app.pkg_info_table.cursor.select(Some(0));
+    app
+}
+
+#[test]
+fn test_key_sequences() {
+    let mut app = test_app();
+
+    let screen = press_keys(&mut app, "G");
+    canvas::assert_snapshot("keys_end", &screen);
+    assert_eq!(app.pkg_info_table.selected().unwrap().name(), "zsh");
+
+    press_keys(&mut app, "gg<Down>");
+    assert_eq!(app.pkg_info_table.selected().unwrap().name(), "ghc");
+
+    // an unknown key after the prefix cancels it
+    press_keys(&mut app, "gx<Up>");
+    assert_eq!(app.pkg_info_table.selected().unwrap().name(), "bear");
+
+    let screen = press_keys(&mut app, "<Enter>");
+    canvas::assert_snapshot("keys_menu", &screen);
+    assert!(matches!(
+        app.current_display(),
+        app::DisplayMode::PopUpPstMenu(_)
+    ));
+
+    press_keys(&mut app, "<Esc>");
+    assert!(matches!(
+        app.current_display(),
+        app::DisplayMode::ViewingPackageStatusTable
+    ));
+}
+
+#[test]
+fn test_configured_keys() {
+    let mut app = test_app();
+    app.config.keys = toml::from_str(
+        r#"
+        [global]
+        "<C-d>" = "down"
+        "ma" = "toggle-mark"
+        "G" = "none"
+
+        [menu]
+        "j" = "down"
+        "#,
+    )
+    .unwrap();
+
+    press_keys(&mut app, "<C-d><C-d>G");
+    assert_eq!(app.pkg_info_table.selected().unwrap().name(), "nodejs");
+
+    let screen = press_keys(&mut app, "m");
+    canvas::assert_snapshot("keys_pending", &screen);
+    assert_eq!(app.pending_keys().as_deref(), Some("m"));
+
+    press_keys(&mut app, "a");
+    assert!(app.pending_keys().is_none());
+    assert!(app.pkg_info_table.marked.contains("nodejs"));
+
+    // a key cancelling the sequence is dropped, the menu has its own bindings
+    press_keys(&mut app, "m<Esc><Enter>j");
+    assert_eq!(app.pending_keys(), None);
+    match app.current_display() {
+        app::DisplayMode::PopUpPstMenu(menu) => assert_eq!(menu.state.selected(), Some(1)),
+        _ => panic!("the menu is not shown"),
+    }
+}
+
+#[test]
+fn test_help_overlay() {
+    let mut app = test_app();
+
+    let screen = press_keys(&mut app, "?");
+    canvas::assert_snapshot("keys_help", &screen);
+
+    // keys scroll the help rather than the table until it is closed
+    press_keys(&mut app, "<Down><Down>");
+    assert_eq!(app.help.as_ref().unwrap().offset, 2);
+    press_keys(&mut app, "G");
+    let help = app.help.as_ref().unwrap();
+    assert_eq!(help.offset, help.lines.len() - 1);
+    assert_eq!(app.pkg_info_table.selected().unwrap().name(), "bear");
+    assert_eq!(app.hints(), "j down  k up  <Esc> back");
+
+    press_keys(&mut app, "q");
+    assert!(app.help.is_none());
+    assert!(app.is_running());
+    assert!(app
+        .hints()
+        .starts_with("<Enter> select  / search  <Space> toggle-mark"));
+}
+
+#[test]
+fn test_update_error() {
+    let mut app = test_app();
+    app.update_error = Some("fail to read sync database core.db".to_string());
+    let screen = press_keys(&mut app, "");
+    canvas::assert_snapshot("update_error", &screen);
+}
+
+#[test]
+fn test_search_prompt_keys() {
+    let mut app = test_app();
+
+    let screen = press_keys(&mut app, "/gcc: error");
+    canvas::assert_snapshot("keys_search_prompt", &screen);
+
+    press_keys(&mut app, "<Esc>q");
+    assert!(matches!(app.input_mode, app::InputMode::Normal));
+    assert!(!app.is_running());
+}
+
+#[test]
+fn test_status_warnings() {
+    let mut app = test_app();
+    app.status_warnings = vec![req::felix::ParseError::MissingCell {
+        row: 3,
+        column: req::felix::Column::Repo,
+        text: "FTBFS ghc".to_string(),
+    }];
+    app.pkg_info_table.warnings = app.status_warnings.len();
+
+    let screen = press_keys(&mut app, "W");
+    canvas::assert_snapshot("keys_status_warnings", &screen);
+    assert!(matches!(
+        app.current_display(),
+        app::DisplayMode::ViewingWarnings(_)
+    ));
+
+    let screen = press_keys(&mut app, "<Esc>");
+    canvas::assert_snapshot("keys_status_warnings_title", &screen);
+}
+
+#[test]
+fn test_control_commands() {
+    use control::Command;
+
+    let mut app = test_app();
+    press_keys(&mut app, "<Enter>");
+
+    let select = |package: &str| Command::Select {
+        package: package.to_string(),
+    };
+    assert!(app.handle_control(select("nodejs")).is_ok());
+    assert_eq!(app.pkg_info_table.selected().unwrap().name(), "nodejs");
+    assert!(matches!(
+        app.current_display(),
+        app::DisplayMode::ViewingPackageStatusTable
+    ));
+    press_keys(&mut app, " ");
+
+    let query = "g".to_string();
+    app.handle_control(Command::Filter { query }).unwrap();
+    let screen = press_keys(&mut app, "");
+    canvas::assert_snapshot("control_filter", &screen);
+    assert_eq!(
+        app.handle_control(select("zsh")).unwrap_err(),
+        "no package zsh in the table"
+    );
+    app.handle_control(select("ghc")).unwrap();
+
+    let selection = app.handle_control(Command::GetSelection).unwrap();
+    assert_eq!(selection["package"]["name"], "ghc");
+    assert_eq!(selection["marked"], serde_json::json!(["nodejs"]));
+}
+
+/// Send a mouse event at the given screen position, then render the screen
+#[cfg(test)]
+fn click(app: &mut app::App, kind: crossterm::event::MouseEventKind, x: u16, y: u16) {
+    app.handle_mouse(crossterm::event::MouseEvent {
+        kind,
+        column: x,
+        row: y,
+        modifiers: crossterm::event::KeyModifiers::NONE,
+    });
+    press_keys(app, "");
+}
+
+#[test]
+fn test_mouse_motion() {
+    use crossterm::event::{MouseButton, MouseEventKind};
+
+    let mut app = test_app();
+    press_keys(&mut app, "G");
+    press_keys(&mut app, "g");
+    click(&mut app, MouseEventKind::Moved, 50, 10);
+    click(&mut app, MouseEventKind::Drag(MouseButton::Left), 50, 11);
+    click(&mut app, MouseEventKind::Up(MouseButton::Left), 50, 11);
+    press_keys(&mut app, "g");
+    assert_eq!(app.pkg_info_table.selected().unwrap().name(), "bear");
+}
+
+#[test]
+fn test_mouse() {
+    use crossterm::event::{MouseButton, MouseEventKind};
+    let left = MouseEventKind::Down(MouseButton::Left);
+
+    let mut app = test_app();
+    app.pkg_info_table.data[1].blocks = 3;
+    app.pkg_info_table.data[3].blocks = 1;
+    press_keys(&mut app, "");
+
+    // rows start below the column titles
+    click(&mut app, left, 10, 4);
+    assert_eq!(app.pkg_info_table.selected().unwrap().name(), "ghc");
+    click(&mut app, left, 10, 12);
+    assert_eq!(app.pkg_info_table.selected().unwrap().name(), "ghc");
+
+    click(&mut app, MouseEventKind::ScrollDown, 10, 10);
+    click(&mut app, MouseEventKind::ScrollDown, 10, 10);
+    click(&mut app, MouseEventKind::ScrollDown, 10, 10);
+    assert_eq!(app.pkg_info_table.selected().unwrap().name(), "zsh");
+    click(&mut app, MouseEventKind::ScrollUp, 10, 10);
+    assert_eq!(app.pkg_info_table.selected().unwrap().name(), "nodejs");
+
+    // a click on the Blocks title sorts by it, the cursor stays on the package
+    click(&mut app, left, 92, 2);
+    let names = |app: &app::App| {
+        app.pkg_info_table
+            .visible()
+            .map(|pkg| pkg.name().to_string())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(names(&app), ["bear", "nodejs", "zsh", "ghc"]);
+    assert_eq!(app.pkg_info_table.selected().unwrap().name(), "nodejs");
+    click(&mut app, left, 92, 2);
+    assert_eq!(names(&app), ["ghc", "zsh", "nodejs", "bear"]);
+    let screen = press_keys(&mut app, "");
+    canvas::assert_snapshot("mouse_sorted", &screen);
+
+    // the menu shows two items at a time, scrolled to the one under the cursor
+    press_keys(&mut app, "<Enter>jj");
+    click(&mut app, left, 0, 0);
+    match app.current_display() {
+        app::DisplayMode::PopUpPstMenu(menu) => {
+            assert_eq!(menu.item_at(30, 10), Some(1));
+            assert_eq!(menu.item_at(30, 12), None);
+        }
+        _ => panic!("the menu is not shown"),
+    }
+    // View package details
+    click(&mut app, left, 30, 11);
+    assert!(matches!(
+        app.current_display(),
+        app::DisplayMode::ViewingPackageStatusTable
+    ));
+}
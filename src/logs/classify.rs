@@ -0,0 +1,205 @@
+use super::stages::{self, StageKind};
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize};
+
+type BoxStr = Box<str>;
+
+/// A known kind of build failure, recognized by a pattern matching one line of the log
+#[derive(Debug, Clone, Deserialize)]
+pub struct Rule {
+    /// Short description shown in the reason column
+    pub reason: BoxStr,
+    #[serde(deserialize_with = "deserialize_regex")]
+    pub pattern: Regex,
+    /// Mark to suggest for packages failing this way
+    #[serde(default)]
+    pub mark: Option<BoxStr>,
+}
+
+fn deserialize_regex<'de, D>(deserializer: D) -> Result<Regex, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let pattern = String::deserialize(deserializer)?;
+    Regex::new(&pattern).map_err(serde::de::Error::custom)
+}
+
+impl Rule {
+    fn builtin(reason: &str, pattern: &str, mark: Option<&str>) -> Self {
+        Self {
+            reason: reason.into(),
+            pattern: Regex::new(pattern).expect("invalid builtin pattern"),
+            mark: mark.map(Into::into),
+        }
+    }
+}
+
+/// Rules for the common riscv64 failures, tried after the user rules. Failures that stop the
+/// build early come first, as they hide whatever the log says later.
+fn builtin_rules() -> Vec<Rule> {
+    vec![
+        Rule::builtin(
+            "missing dependency",
+            r"error: target not found: |could not satisfy dependencies|Could not resolve all dependencies",
+            Some("missing_dep"),
+        ),
+        Rule::builtin(
+            "checksum mismatch",
+            r"One or more files did not pass the validity check",
+            None,
+        ),
+        Rule::builtin(
+            "outdated config.guess",
+            r"cannot guess build type|unable to guess system type|machine .riscv64\S*. not recognized",
+            Some("upstreamable"),
+        ),
+        Rule::builtin(
+            "missing -latomic",
+            r"undefined reference to .__atomic_\w+",
+            Some("upstreamable"),
+        ),
+        Rule::builtin(
+            "unsupported arch",
+            r"(?i)\b(unsupported|unknown|unrecognized|unrecognised) (cpu |host |target )?(architecture|arch|platform|processor)\b|riscv64\S* (is )?not supported",
+            Some("upstreamable"),
+        ),
+        Rule::builtin(
+            "test timeout",
+            r"\*\*\*Timeout|\bTIMEOUT\b|(?i:\btests?\b.*\btimed out\b)",
+            Some("noqemu"),
+        ),
+    ]
+}
+
+/// Result of classifying a build log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Classification {
+    pub reason: BoxStr,
+    pub mark: Option<BoxStr>,
+    /// Index of the log line that matched
+    pub line: usize,
+}
+
+/// Ordered list of rules, the first rule matching anywhere in the log wins. Logs matching no rule
+/// are still told apart by the makepkg function that failed.
+#[derive(Debug)]
+pub struct Classifier {
+    rules: Vec<Rule>,
+}
+
+impl std::default::Default for Classifier {
+    fn default() -> Self {
+        Self::new(&[])
+    }
+}
+
+impl Classifier {
+    /// Create a classifier trying `rules` before the builtin ones
+    pub fn new(rules: &[Rule]) -> Self {
+        let mut all = rules.to_vec();
+        all.extend(builtin_rules());
+        Self { rules: all }
+    }
+
+    pub fn classify(&self, log: &str) -> Option<Classification> {
+        let lines = log.lines().collect::<Vec<_>>();
+        let matched = self.rules.iter().find_map(|rule| {
+            let line = lines.iter().position(|line| rule.pattern.is_match(line))?;
+            Some(Classification {
+                reason: rule.reason.clone(),
+                mark: rule.mark.clone(),
+                line,
+            })
+        });
+        matched.or_else(|| {
+            let failure = stages::parse(&lines).failure?;
+            let (reason, mark) = match failure.kind {
+                StageKind::Check => ("check() failed", Some("nocheck")),
+                StageKind::Build => ("build() failed", None),
+                _ => return None,
+            };
+            Some(Classification {
+                reason: reason.into(),
+                mark: mark.map(Into::into),
+                line: failure.line,
+            })
+        })
+    }
+}
+
+#[test]
+fn test_builtin_rules() {
+    let classifier = Classifier::default();
+    let cases = [
+        (
+            "checking build system type... ./config.guess: unable to guess system type",
+            "outdated config.guess",
+        ),
+        (
+            "Invalid configuration `riscv64-unknown-linux-gnu': machine `riscv64-unknown' not recognized",
+            "outdated config.guess",
+        ),
+        (
+            "/usr/bin/ld: libfoo.so: undefined reference to `__atomic_fetch_add_1'",
+            "missing -latomic",
+        ),
+        ("#error Unsupported architecture", "unsupported arch"),
+        ("      Start 42: test_io\n42/90 Test #42: test_io ....***Timeout 1500.01 sec", "test timeout"),
+        ("error: target not found: python-foo", "missing dependency"),
+        (
+            "==> ERROR: One or more files did not pass the validity check!",
+            "checksum mismatch",
+        ),
+    ];
+
+    for (log, reason) in cases {
+        let classification = classifier.classify(log);
+        assert_eq!(
+            classification.map(|c| c.reason),
+            Some(reason.into()),
+            "{log}"
+        );
+    }
+    assert!(classifier.classify("==> Finished making: bash").is_none());
+}
+
+#[test]
+fn test_failing_function() {
+    let classifier = Classifier::default();
+    let log = "==> Starting check()...
+FAIL: test_io
+==> ERROR: A failure occurred in check().";
+    let classification = classifier.classify(log).unwrap();
+    assert_eq!(classification.reason.as_ref(), "check() failed");
+    assert_eq!(classification.mark.as_deref(), Some("nocheck"));
+    assert_eq!(classification.line, 2);
+
+    let log = "==> Starting build()...
+==> ERROR: A failure occurred in build().";
+    assert_eq!(
+        classifier.classify(log).unwrap().reason.as_ref(),
+        "build() failed"
+    );
+}
+
+#[test]
+fn test_user_rules_first() {
+    let rule: Rule = toml::from_str(
+        r#"
+        reason = "rust linker"
+        pattern = "error: linking with `cc` failed"
+        mark = "upstreamable"
+        "#,
+    )
+    .unwrap();
+    let classifier = Classifier::new(&[rule]);
+
+    let log =
+        "undefined reference to `__atomic_load_8'\nerror: linking with `cc` failed: exit status: 1";
+    let classification = classifier.classify(log).unwrap();
+    assert_eq!(classification.reason.as_ref(), "rust linker");
+    assert_eq!(classification.mark.as_deref(), Some("upstreamable"));
+    assert_eq!(classification.line, 1);
+
+    assert!(toml::from_str::<Rule>("reason = \"bad\"\npattern = \"(\"").is_err());
+}
@@ -0,0 +1,134 @@
+use regex::Regex;
+use std::{collections::HashMap, sync::OnceLock};
+
+type BoxStr = Box<str>;
+
+/// Packages failing with the same normalized error
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cluster {
+    pub signature: String,
+    pub pkgnames: Vec<BoxStr>,
+}
+
+struct Patterns {
+    error: Regex,
+    /// Error lines that only report a failure happened somewhere above
+    generic: Regex,
+    path: Regex,
+    hash: Regex,
+    number: Regex,
+    space: Regex,
+}
+
+fn patterns() -> &'static Patterns {
+    static PATTERNS: OnceLock<Patterns> = OnceLock::new();
+    PATTERNS.get_or_init(|| Patterns {
+        // compiler, linker, cargo and makepkg diagnostics, not any line mentioning "error"
+        error: Regex::new(
+            r"^\S*: (fatal )?error:|^error(\[E\d+\])?:|^==> ERROR:|^CMake Error|Error \d+$|undefined reference|\bFAILED\b",
+        )
+        .unwrap(),
+        generic: Regex::new(
+            r"^==> ERROR: (A failure occurred|Makepkg was unable)|^g?make(\[\d+\])?: \*\*\*|^FAILED: |^ninja: build stopped",
+        )
+        .unwrap(),
+        path: Regex::new(r"[\w.+~-]*(/[\w.+~-]*)+").unwrap(),
+        hash: Regex::new(r"\b[0-9a-fA-F]{8,}\b").unwrap(),
+        number: Regex::new(r"(^|[^A-Za-z0-9])\d+").unwrap(),
+        space: Regex::new(r"\s+").unwrap(),
+    })
+}
+
+/// Return the first meaningful error line of the log with paths, hashes and numbers replaced by
+/// placeholders, so the same breakage in different packages gives the same signature
+pub fn signature(log: &str) -> Option<String> {
+    let patterns = patterns();
+    let line = log
+        .lines()
+        .map(str::trim)
+        .find(|line| patterns.error.is_match(line) && !patterns.generic.is_match(line))?;
+
+    let line = patterns.path.replace_all(line, "<path>");
+    let line = patterns.hash.replace_all(&line, "<hash>");
+    let line = patterns.number.replace_all(&line, "${1}<n>");
+    let line = patterns.space.replace_all(&line, " ");
+    Some(line.trim().to_string())
+}
+
+/// Group packages by signature, biggest cluster first
+pub fn cluster(signatures: impl IntoIterator<Item = (BoxStr, String)>) -> Vec<Cluster> {
+    let mut groups: HashMap<String, Vec<BoxStr>> = HashMap::new();
+    for (pkgname, signature) in signatures {
+        groups.entry(signature).or_default().push(pkgname);
+    }
+
+    let mut clusters = groups
+        .into_iter()
+        .map(|(signature, mut pkgnames)| {
+            pkgnames.sort_unstable();
+            Cluster {
+                signature,
+                pkgnames,
+            }
+        })
+        .collect::<Vec<_>>();
+    clusters.sort_by(|a, b| {
+        b.pkgnames
+            .len()
+            .cmp(&a.pkgnames.len())
+            .then_with(|| a.signature.cmp(&b.signature))
+    });
+    clusters
+}
+
+#[test]
+fn test_signature() {
+    let log = "==> Starting build()...\n\
+               make[2]: Entering directory '/build/foo/src/foo-1.2'\n\
+               /usr/bin/ld: src/.libs/libfoo.so.1.0.3: undefined reference to `__atomic_fetch_add_1'\n\
+               collect2: error: ld returned 1 exit status\n\
+               make[2]: *** [Makefile:512: foo] Error 1\n\
+               ==> ERROR: A failure occurred in build().";
+    assert_eq!(
+        signature(log).as_deref(),
+        Some("<path>: <path>: undefined reference to `__atomic_fetch_add_<n>'")
+    );
+
+    assert_eq!(
+        signature("FAILED: obj/a.o\nerror: commit 3f2a9c1d0e is not riscv64 ready").as_deref(),
+        Some("error: commit <hash> is not riscv64 ready")
+    );
+    assert!(signature("==> Finished making: bash 5.1.016-1").is_none());
+}
+
+#[test]
+fn test_signature_skips_non_diagnostics() {
+    let log = "checking for error.h... yes
+               gcc -Wno-error -Werror=format -c foo.c
+               Compiling error-chain v0.12.4
+               foo.c:12:5: error: implicit declaration of function 'bar'";
+    assert_eq!(
+        signature(log).as_deref(),
+        Some("foo.c:<n>:<n>: error: implicit declaration of function 'bar'")
+    );
+    assert!(signature("checking for error.h... yes\n   Compiling error-chain v0.12.4").is_none());
+    assert_eq!(
+        signature("error[E0425]: cannot find value `x` in this scope").as_deref(),
+        Some("error[E0425]: cannot find value `x` in this scope")
+    );
+}
+
+#[test]
+fn test_cluster() {
+    let clusters = cluster([
+        ("zlib".into(), "b".to_string()),
+        ("bash".into(), "a".to_string()),
+        ("attr".into(), "a".to_string()),
+        ("acl".into(), "c".to_string()),
+    ]);
+
+    assert_eq!(clusters.len(), 3);
+    assert_eq!(clusters[0].signature, "a");
+    assert_eq!(clusters[0].pkgnames, vec!["attr".into(), "bash".into()]);
+    assert_eq!(clusters[1].signature, "b");
+}
@@ -0,0 +1,245 @@
+use super::stages::strip_ansi;
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// Give up aligning lines past this many differences, the rest is shown as one big change.
+/// Memory grows with its square.
+const MAX_EDITS: usize = 2000;
+
+/// A line of the side by side view, holding the index of the line on each side
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiffRow {
+    pub left: Option<usize>,
+    pub right: Option<usize>,
+    pub changed: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LogDiff {
+    pub rows: Vec<DiffRow>,
+    /// Index of the first row of each run of changed rows
+    pub hunks: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Equal,
+    Delete,
+    Insert,
+}
+
+struct Patterns {
+    timestamp: Regex,
+    path: Regex,
+    space: Regex,
+}
+
+fn patterns() -> &'static Patterns {
+    static PATTERNS: OnceLock<Patterns> = OnceLock::new();
+    PATTERNS.get_or_init(|| Patterns {
+        timestamp: Regex::new(
+            r"\d{4}-\d{2}-\d{2}[T ]\d{2}:\d{2}(:\d{2}(\.\d+)?)?(Z|[+-]\d{2}:?\d{2})?|\b\d{2}:\d{2}:\d{2}(\.\d+)?\b",
+        )
+        .unwrap(),
+        path: Regex::new(r"[\w.+~-]*(/[\w.+~-]*)+").unwrap(),
+        space: Regex::new(r"\s+").unwrap(),
+    })
+}
+
+/// Line with colors, timestamps and paths removed, as compared by [`diff`]
+pub fn normalize(line: &str) -> String {
+    let patterns = patterns();
+    let line = strip_ansi(line);
+    let line = patterns.timestamp.replace_all(&line, "<time>");
+    let line = patterns.path.replace_all(&line, "<path>");
+    patterns.space.replace_all(line.trim(), " ").into_owned()
+}
+
+/// Align two logs line by line, ignoring the differences removed by [`normalize`]
+pub fn diff<S: AsRef<str>>(left: &[S], right: &[S]) -> LogDiff {
+    let left = left
+        .iter()
+        .map(|l| normalize(l.as_ref()))
+        .collect::<Vec<_>>();
+    let right = right
+        .iter()
+        .map(|l| normalize(l.as_ref()))
+        .collect::<Vec<_>>();
+
+    let prefix = left.iter().zip(&right).take_while(|(l, r)| l == r).count();
+    let suffix = left[prefix..]
+        .iter()
+        .rev()
+        .zip(right[prefix..].iter().rev())
+        .take_while(|(l, r)| l == r)
+        .count();
+    let (middle_left, middle_right) = (
+        &left[prefix..left.len() - suffix],
+        &right[prefix..right.len() - suffix],
+    );
+
+    let mut ops = vec![Op::Equal; prefix];
+    match edit_script(middle_left, middle_right) {
+        Some(script) => ops.extend(script),
+        None => {
+            ops.extend(std::iter::repeat_n(Op::Delete, middle_left.len()));
+            ops.extend(std::iter::repeat_n(Op::Insert, middle_right.len()));
+        }
+    }
+    ops.extend(std::iter::repeat_n(Op::Equal, suffix));
+
+    to_rows(&ops)
+}
+
+/// Shortest edit script from `a` to `b` with the Myers algorithm, `None` when it is longer than
+/// [`MAX_EDITS`]
+fn edit_script(a: &[String], b: &[String]) -> Option<Vec<Op>> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = (n + m) as usize;
+    let offset = max as isize + 1;
+    let mut v = vec![0isize; 2 * max + 3];
+    // v[-d-1..=d+1] before each step d
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+
+    let mut found = None;
+    'search: for d in 0..=max.min(MAX_EDITS) as isize {
+        trace.push(v[(offset - d - 1) as usize..=(offset + d + 1) as usize].to_vec());
+        for k in (-d..=d).step_by(2) {
+            let at = |k: isize| (offset + k) as usize;
+            let mut x = if k == -d || (k != d && v[at(k - 1)] < v[at(k + 1)]) {
+                v[at(k + 1)]
+            } else {
+                v[at(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[at(k)] = x;
+            if x >= n && y >= m {
+                found = Some(d);
+                break 'search;
+            }
+        }
+    }
+    let edits = found?;
+
+    let mut ops = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for d in (0..=edits).rev() {
+        let v = &trace[d as usize];
+        let at = |k: isize| (k + d + 1) as usize;
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && v[at(k - 1)] < v[at(k + 1)]) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = if d == 0 { 0 } else { v[at(prev_k)] };
+        let prev_y = prev_x - prev_k;
+
+        while x > prev_x && y > prev_y {
+            ops.push(Op::Equal);
+            x -= 1;
+            y -= 1;
+        }
+        if d > 0 {
+            ops.push(if x == prev_x { Op::Insert } else { Op::Delete });
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+    ops.reverse();
+    Some(ops)
+}
+
+/// Turn the edit script into side by side rows, pairing the deleted and inserted lines of each
+/// change
+fn to_rows(ops: &[Op]) -> LogDiff {
+    let mut result = LogDiff::default();
+    let (mut left, mut right) = (0, 0);
+    let mut i = 0;
+
+    while i < ops.len() {
+        if ops[i] == Op::Equal {
+            result.rows.push(DiffRow {
+                left: Some(left),
+                right: Some(right),
+                changed: false,
+            });
+            left += 1;
+            right += 1;
+            i += 1;
+            continue;
+        }
+
+        let end = ops[i..]
+            .iter()
+            .position(|op| *op == Op::Equal)
+            .map_or(ops.len(), |len| i + len);
+        let deleted = ops[i..end].iter().filter(|op| **op == Op::Delete).count();
+        let inserted = end - i - deleted;
+
+        result.hunks.push(result.rows.len());
+        for row in 0..deleted.max(inserted) {
+            result.rows.push(DiffRow {
+                left: (row < deleted).then_some(left + row),
+                right: (row < inserted).then_some(right + row),
+                changed: true,
+            });
+        }
+        left += deleted;
+        right += inserted;
+        i = end;
+    }
+
+    result
+}
+
+#[test]
+fn test_normalize() {
+    assert_eq!(
+        normalize("[2022-09-10 12:00:01]  make -C /build/foo/src/foo-1.0 all"),
+        "[<time>] make -C <path> all"
+    );
+    assert_eq!(
+        normalize("\x1b[1m==> Finished at 12:03:44\x1b[m"),
+        "==> Finished at <time>"
+    );
+}
+
+#[test]
+fn test_diff() {
+    let left = ["a", "b", "c", "d", "e"];
+    let right = ["a", "x", "c", "e", "f"];
+    let diff = diff(&left, &right);
+
+    let pairs = diff
+        .rows
+        .iter()
+        .map(|row| (row.left, row.right, row.changed))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        pairs,
+        [
+            (Some(0), Some(0), false),
+            (Some(1), Some(1), true),
+            (Some(2), Some(2), false),
+            (Some(3), None, true),
+            (Some(4), Some(3), false),
+            (None, Some(4), true),
+        ]
+    );
+    assert_eq!(diff.hunks, [1, 3, 5]);
+}
+
+#[test]
+fn test_diff_ignores_paths_and_times() {
+    let left = ["12:00:00 cc /tmp/a/foo.c", "error: x"];
+    let right = ["13:10:00 cc /build/b/foo.c", "error: x"];
+    assert!(diff(&left, &right).hunks.is_empty());
+
+    let right = ["12:00:00 cc /tmp/a/foo.c"];
+    assert_eq!(diff(&left, &right).hunks, [1]);
+}
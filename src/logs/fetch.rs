@@ -0,0 +1,123 @@
+use crate::events::Events;
+use anyhow::Context;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc},
+    time::Duration,
+};
+use tokio::sync::Semaphore;
+
+type BoxStr = Box<str>;
+
+/// File remembering the URL each cached log was downloaded from
+const INDEX_FILE: &str = "urls.json";
+
+/// Progress of a bulk log download
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DownloadProgress {
+    pub done: usize,
+    pub total: usize,
+    pub failed: usize,
+}
+
+impl DownloadProgress {
+    pub fn is_done(&self) -> bool {
+        self.done >= self.total
+    }
+
+    /// Short form like `Downloaded 12/300 logs, 2 failed`
+    pub fn summary(&self) -> String {
+        let mut summary = format!("Downloaded {}/{} logs", self.done, self.total);
+        if self.failed > 0 {
+            summary.push_str(&format!(", {} failed", self.failed));
+        }
+        summary
+    }
+}
+
+/// Download the log of each `(pkgname, url)` into `dir` in the background, at most `parallel` at
+/// a time. Logs already cached from the same URL are not downloaded again. Every package is
+/// reported with a [`Events::LogDownloaded`].
+pub fn spawn_download(
+    dir: PathBuf,
+    jobs: Vec<(BoxStr, String)>,
+    parallel: usize,
+    tx: mpsc::Sender<Events>,
+) {
+    tokio::spawn(async move {
+        let mut index: HashMap<BoxStr, String> = std::fs::read(dir.join(INDEX_FILE))
+            .ok()
+            .and_then(|content| serde_json::from_slice(&content).ok())
+            .unwrap_or_default();
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(60))
+            .build()
+            .expect("Fail to create HTTP client to download build logs");
+        let semaphore = Arc::new(Semaphore::new(parallel.max(1)));
+
+        let mut handles = Vec::new();
+        for (pkgname, url) in jobs {
+            let path = super::cached_log_path(&dir, &pkgname);
+            if index.get(&pkgname) == Some(&url) && path.is_file() {
+                let _ = tx.send(Events::LogDownloaded {
+                    pkgname,
+                    error: None,
+                });
+                continue;
+            }
+
+            let permit = match semaphore.clone().acquire_owned().await {
+                Ok(permit) => permit,
+                Err(_) => break,
+            };
+            let client = client.clone();
+            let tx = tx.clone();
+            handles.push(tokio::spawn(async move {
+                let result = download(&client, &url, &path).await;
+                drop(permit);
+                let ok = result.is_ok();
+                let _ = tx.send(Events::LogDownloaded {
+                    pkgname: pkgname.clone(),
+                    error: result.err().map(|err| format!("{err:#}")),
+                });
+                (pkgname, url, ok)
+            }));
+        }
+
+        for handle in handles {
+            if let Ok((pkgname, url, true)) = handle.await {
+                index.insert(pkgname, url);
+            }
+        }
+        if let Ok(content) = serde_json::to_vec_pretty(&index) {
+            let _ = std::fs::write(dir.join(INDEX_FILE), content);
+        }
+    });
+}
+
+async fn download(client: &reqwest::Client, url: &str, path: &Path) -> anyhow::Result<()> {
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .with_context(|| format!("fail to download {url}"))?
+        .error_for_status()?;
+    let content = response
+        .bytes()
+        .await
+        .with_context(|| format!("fail to read {url}"))?;
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    // keep the previous log around to compare with, unless nothing changed
+    match std::fs::read(path) {
+        Ok(previous) if previous == content => return Ok(()),
+        Ok(_) => {
+            super::archive(path).with_context(|| format!("fail to archive {}", path.display()))?
+        }
+        Err(_) => (),
+    }
+    std::fs::write(path, content).with_context(|| format!("fail to write {}", path.display()))
+}
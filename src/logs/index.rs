@@ -0,0 +1,510 @@
+use super::read::read_log;
+use anyhow::{Context, Result};
+use regex::Regex;
+use regex_syntax::hir::{Hir, HirKind};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+type BoxStr = Box<str>;
+
+/// List of the indexed logs, the position of a log is its id in the posting lists
+const FILES: &str = "files.json";
+/// Sorted `(trigram: u32, offset: u64, len: u32)` entries, little endian, pointing into
+/// [`POSTINGS`]
+const LEXICON: &str = "lexicon.bin";
+/// Posting lists of log ids, delta and varint encoded
+const POSTINGS: &str = "postings.bin";
+const LEXICON_ENTRY_LEN: usize = 16;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct IndexedFile {
+    path: PathBuf,
+    len: u64,
+    modified: SystemTime,
+}
+
+/// A log line matching a search
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hit {
+    pub pkgname: BoxStr,
+    pub path: PathBuf,
+    /// Index of the matching line
+    pub line: usize,
+    pub text: String,
+}
+
+struct LexiconEntry {
+    trigram: u32,
+    offset: u64,
+    len: u32,
+}
+
+/// Trigram index of the `*.log` files of some directories. A search only reads the logs
+/// containing every trigram of the literal parts of the pattern.
+pub struct LogIndex {
+    dir: PathBuf,
+    /// Indexed logs by id, `None` for a log that has changed or disappeared since. Its postings
+    /// are kept until the next full rebuild.
+    files: Vec<Option<IndexedFile>>,
+    lexicon: Vec<LexiconEntry>,
+}
+
+impl LogIndex {
+    /// Open the index stored in `dir` and bring it up to date with the logs in `sources`. Only
+    /// the logs whose size or modification time changed since it was written are read.
+    pub fn open(dir: &Path, sources: &[&Path]) -> Result<Self> {
+        let current = list_logs(sources);
+        let stored = std::fs::read(dir.join(FILES))
+            .ok()
+            .and_then(|content| serde_json::from_slice(&content).ok());
+
+        let index = Self {
+            dir: dir.to_path_buf(),
+            files: Vec::new(),
+            lexicon: Vec::new(),
+        };
+        match (stored, read_lexicon(&dir.join(LEXICON))) {
+            (Some(files), Ok(lexicon)) => Self {
+                files,
+                lexicon,
+                ..index
+            }
+            .update(current),
+            _ => index.append(current, Vec::new()),
+        }
+    }
+
+    /// Drop the logs that changed since the index was written and index their new version under
+    /// new ids. The index is rebuilt from scratch once dropped ids outnumber the live ones, or
+    /// when the postings can't be read.
+    fn update(mut self, current: Vec<IndexedFile>) -> Result<Self> {
+        let mut current = current
+            .into_iter()
+            .map(|file| (file.path.clone(), file))
+            .collect::<BTreeMap<_, _>>();
+        let mut dropped = false;
+        for slot in self.files.iter_mut() {
+            if let Some(file) = slot {
+                if current.get(&file.path) == Some(file) {
+                    current.remove(&file.path);
+                } else {
+                    *slot = None;
+                    dropped = true;
+                }
+            }
+        }
+        let added = current.into_values().collect::<Vec<_>>();
+        if !dropped && added.is_empty() {
+            return Ok(self);
+        }
+
+        // a missing or truncated postings file can't be extended, the index is rebuilt then
+        let live = self.files.iter().flatten().count();
+        let postings = std::fs::read(self.dir.join(POSTINGS))
+            .ok()
+            .filter(|postings| {
+                self.lexicon
+                    .iter()
+                    .all(|entry| entry.offset + entry.len as u64 <= postings.len() as u64)
+            });
+        match postings {
+            Some(postings) if self.files.len() - live <= live + added.len() => {
+                self.append(added, postings)
+            }
+            _ => {
+                let mut files = std::mem::take(&mut self.files)
+                    .into_iter()
+                    .flatten()
+                    .chain(added)
+                    .collect::<Vec<_>>();
+                files.sort_by(|a, b| a.path.cmp(&b.path));
+                self.lexicon.clear();
+                self.append(files, Vec::new())
+            }
+        }
+    }
+
+    /// Index `added` under the next ids and write the index out. `postings` is the content of the
+    /// current postings file, its lists are kept and extended.
+    fn append(mut self, added: Vec<IndexedFile>, postings: Vec<u8>) -> Result<Self> {
+        let mut lists: BTreeMap<u32, Vec<u8>> = BTreeMap::new();
+        for entry in self.lexicon.iter() {
+            let start = entry.offset as usize;
+            let list = postings
+                .get(start..start + entry.len as usize)
+                .with_context(|| format!("corrupted index postings in {}", self.dir.display()))?;
+            lists.insert(entry.trigram, list.to_vec());
+        }
+
+        // trigram -> last id, only for the lists extended here
+        let mut last_ids: BTreeMap<u32, u32> = BTreeMap::new();
+        for file in added {
+            let id = self.files.len() as u32;
+            if let Ok(content) = read_log(&file.path).map(String::into_bytes) {
+                for trigram in trigrams(&content) {
+                    let list = lists.entry(trigram).or_default();
+                    let last = last_ids
+                        .entry(trigram)
+                        .or_insert_with(|| read_varints(list).last().copied().unwrap_or(0));
+                    write_varint(list, id - *last);
+                    *last = id;
+                }
+            }
+            self.files.push(Some(file));
+        }
+
+        std::fs::create_dir_all(&self.dir)
+            .with_context(|| format!("fail to create {}", self.dir.display()))?;
+        // removed first and written last, so an interrupted write is rebuilt on next open
+        let _ = std::fs::remove_file(self.dir.join(FILES));
+
+        self.lexicon = Vec::with_capacity(lists.len());
+        let mut lexicon_bytes = Vec::with_capacity(lists.len() * LEXICON_ENTRY_LEN);
+        let mut postings_bytes = Vec::with_capacity(postings.len());
+        for (trigram, list) in lists {
+            let entry = LexiconEntry {
+                trigram,
+                offset: postings_bytes.len() as u64,
+                len: list.len() as u32,
+            };
+            lexicon_bytes.extend_from_slice(&entry.trigram.to_le_bytes());
+            lexicon_bytes.extend_from_slice(&entry.offset.to_le_bytes());
+            lexicon_bytes.extend_from_slice(&entry.len.to_le_bytes());
+            postings_bytes.extend_from_slice(&list);
+            self.lexicon.push(entry);
+        }
+
+        std::fs::write(self.dir.join(POSTINGS), postings_bytes)?;
+        std::fs::write(self.dir.join(LEXICON), lexicon_bytes)?;
+        std::fs::write(self.dir.join(FILES), serde_json::to_vec(&self.files)?)?;
+
+        Ok(self)
+    }
+
+    /// Return the lines matching the `pattern` regex, at most `limit` of them, ordered by log
+    /// path then line
+    pub fn search(&self, pattern: &str, limit: usize) -> Result<Vec<Hit>> {
+        let regex = Regex::new(pattern)?;
+        let mut hits = Vec::new();
+
+        for file in self.candidates(pattern)? {
+            let content = match read_log(&file.path).map(String::into_bytes) {
+                Ok(content) => content,
+                Err(_) => continue,
+            };
+            let pkgname: BoxStr = file
+                .path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into())
+                .unwrap_or_default();
+
+            for (line, text) in String::from_utf8_lossy(&content).lines().enumerate() {
+                if hits.len() >= limit {
+                    return Ok(hits);
+                }
+                if regex.is_match(text) {
+                    hits.push(Hit {
+                        pkgname: pkgname.clone(),
+                        path: file.path.clone(),
+                        line,
+                        text: text.to_string(),
+                    });
+                }
+            }
+        }
+
+        Ok(hits)
+    }
+
+    /// Logs that may match `pattern`, sorted by path
+    fn candidates(&self, pattern: &str) -> Result<Vec<&IndexedFile>> {
+        let mut files = self
+            .candidate_ids(pattern)?
+            .into_iter()
+            .filter_map(|id| self.files.get(id)?.as_ref())
+            .collect::<Vec<_>>();
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(files)
+    }
+
+    /// Ids of the logs that may match `pattern`, dropped ids included
+    fn candidate_ids(&self, pattern: &str) -> Result<Vec<usize>> {
+        let wanted = required_literals(pattern)
+            .iter()
+            .flat_map(|literal| trigrams(literal))
+            .collect::<HashSet<_>>();
+        if wanted.is_empty() {
+            return Ok((0..self.files.len()).collect());
+        }
+
+        let mut postings = File::open(self.dir.join(POSTINGS))?;
+        let mut result: Option<Vec<u32>> = None;
+        for trigram in wanted {
+            let entry = match self
+                .lexicon
+                .binary_search_by_key(&trigram, |entry| entry.trigram)
+            {
+                Ok(i) => &self.lexicon[i],
+                Err(_) => return Ok(Vec::new()),
+            };
+
+            let mut buf = vec![0; entry.len as usize];
+            postings.seek(SeekFrom::Start(entry.offset))?;
+            postings.read_exact(&mut buf)?;
+            let ids = read_varints(&buf);
+
+            result = Some(match result {
+                Some(previous) => intersect(&previous, &ids),
+                None => ids,
+            });
+            if result.as_ref().is_some_and(|ids| ids.is_empty()) {
+                break;
+            }
+        }
+
+        Ok(result
+            .unwrap_or_default()
+            .into_iter()
+            .map(|id| id as usize)
+            .collect())
+    }
+}
+
+/// Every `*.log` file of `sources`, sorted by path
+fn list_logs(sources: &[&Path]) -> Vec<IndexedFile> {
+    let mut files = sources
+        .iter()
+        .filter_map(|dir| std::fs::read_dir(dir).ok())
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            if path.extension()? != "log" {
+                return None;
+            }
+            let meta = path.metadata().ok()?;
+            Some(IndexedFile {
+                len: meta.len(),
+                modified: meta.modified().ok()?,
+                path,
+            })
+        })
+        .collect::<Vec<_>>();
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    files
+}
+
+fn read_lexicon(path: &Path) -> Result<Vec<LexiconEntry>> {
+    let bytes = std::fs::read(path)?;
+    if bytes.len() % LEXICON_ENTRY_LEN != 0 {
+        anyhow::bail!("corrupted index lexicon {}", path.display());
+    }
+    Ok(bytes
+        .chunks_exact(LEXICON_ENTRY_LEN)
+        .map(|entry| LexiconEntry {
+            trigram: u32::from_le_bytes(entry[0..4].try_into().unwrap()),
+            offset: u64::from_le_bytes(entry[4..12].try_into().unwrap()),
+            len: u32::from_le_bytes(entry[12..16].try_into().unwrap()),
+        })
+        .collect())
+}
+
+/// Distinct trigrams of `content` within lines, with ASCII letters lowercased so that the index
+/// also serves case insensitive searches
+fn trigrams(content: &[u8]) -> HashSet<u32> {
+    content
+        .windows(3)
+        .filter(|window| !window.contains(&b'\n'))
+        .map(|window| {
+            let [a, b, c] = [0, 1, 2].map(|i| window[i].to_ascii_lowercase() as u32);
+            a << 16 | b << 8 | c
+        })
+        .collect()
+}
+
+/// Literal strings that any match of `pattern` must contain. Alternations, classes and optional
+/// parts cut the literals, so the result may be empty but never wrong.
+fn required_literals(pattern: &str) -> Vec<Vec<u8>> {
+    let hir = match regex_syntax::Parser::new().parse(pattern) {
+        Ok(hir) => hir,
+        Err(_) => return Vec::new(),
+    };
+
+    fn collect(hir: &Hir, run: &mut Vec<u8>, literals: &mut Vec<Vec<u8>>) {
+        match hir.kind() {
+            HirKind::Literal(literal) => run.extend_from_slice(&literal.0),
+            HirKind::Concat(subs) => subs.iter().for_each(|sub| collect(sub, run, literals)),
+            HirKind::Capture(capture) => collect(&capture.sub, run, literals),
+            HirKind::Look(_) | HirKind::Empty => (),
+            HirKind::Repetition(repetition) if repetition.min > 0 => {
+                literals.push(std::mem::take(run));
+                collect(&repetition.sub, run, literals);
+                literals.push(std::mem::take(run));
+            }
+            _ => literals.push(std::mem::take(run)),
+        }
+    }
+
+    let mut literals = Vec::new();
+    let mut run = Vec::new();
+    collect(&hir, &mut run, &mut literals);
+    literals.push(run);
+    literals.retain(|literal| literal.len() >= 3);
+    literals
+}
+
+fn write_varint(buf: &mut Vec<u8>, mut value: u32) {
+    while value >= 0x80 {
+        buf.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+/// Decode a delta encoded posting list
+fn read_varints(buf: &[u8]) -> Vec<u32> {
+    let mut ids = Vec::new();
+    let (mut last, mut value, mut shift) = (0, 0, 0);
+    for byte in buf {
+        value |= ((byte & 0x7f) as u32) << shift;
+        if byte & 0x80 == 0 {
+            last += value;
+            ids.push(last);
+            value = 0;
+            shift = 0;
+        } else {
+            shift += 7;
+        }
+    }
+    ids
+}
+
+fn intersect(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let (mut i, mut j) = (0, 0);
+    let mut result = Vec::new();
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                result.push(a[i]);
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    result
+}
+
+#[test]
+fn test_required_literals() {
+    let literals = |pattern| {
+        required_literals(pattern)
+            .into_iter()
+            .map(|literal| String::from_utf8(literal).unwrap())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(literals("__atomic_fetch"), ["__atomic_fetch"]);
+    assert_eq!(
+        literals(r"undefined reference to .__atomic_\w+"),
+        ["undefined reference to ", "__atomic_"]
+    );
+    assert_eq!(literals("foo|barbaz"), Vec::<String>::new());
+    assert_eq!(literals("(?:abc)+def"), ["abc", "def"]);
+    assert_eq!(literals("ab?cde"), ["cde"]);
+}
+
+#[test]
+fn test_varints() {
+    let mut buf = Vec::new();
+    for delta in [3, 0, 200, 70000] {
+        write_varint(&mut buf, delta);
+    }
+    assert_eq!(read_varints(&buf), [3, 3, 203, 70203]);
+}
+
+#[test]
+fn test_search() {
+    let root = std::env::temp_dir().join(format!("lzr-test-index-{}", std::process::id()));
+    let logs = root.join("logs");
+    std::fs::create_dir_all(&logs).unwrap();
+    std::fs::write(
+        logs.join("foo.log"),
+        "==> Starting build()\nundefined reference to `__atomic_fetch_add_1'\n",
+    )
+    .unwrap();
+    std::fs::write(
+        logs.join("bar.log"),
+        "checking for gcc... gcc\nAll tests passed\n",
+    )
+    .unwrap();
+    std::fs::write(logs.join("notes.txt"), "undefined reference").unwrap();
+
+    let index = LogIndex::open(&root.join("index"), &[&logs]).unwrap();
+    assert_eq!(index.files.len(), 2);
+    assert_eq!(
+        index.search("gcc|build", 10).unwrap()[0].pkgname.as_ref(),
+        "bar"
+    );
+
+    let hits = index.search("__atomic_fetch", 10).unwrap();
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].pkgname.as_ref(), "foo");
+    assert_eq!(hits[0].line, 1);
+
+    assert_eq!(index.search("(?i)ALL TESTS", 10).unwrap().len(), 1);
+    assert_eq!(index.search("gcc|build", 10).unwrap().len(), 2);
+    assert_eq!(index.search("gcc|build", 1).unwrap().len(), 1);
+    assert!(index.search("not in any log", 10).unwrap().is_empty());
+    assert!(index.search("(", 10).is_err());
+
+    // a changed log is indexed again under a new id, the other keeps its postings
+    std::fs::write(
+        logs.join("bar.log"),
+        "==> ERROR: checksum mismatch for the tarball\n",
+    )
+    .unwrap();
+    std::fs::write(logs.join("baz.log"), "undefined reference to `dlopen'\n").unwrap();
+    let index = LogIndex::open(&root.join("index"), &[&logs]).unwrap();
+    assert_eq!(index.files.len(), 4);
+    assert!(index.files[0].is_none());
+    assert_eq!(index.search("checksum mismatch", 10).unwrap().len(), 1);
+    assert!(index.search("All tests", 10).unwrap().is_empty());
+    let hits = index.search("undefined reference", 10).unwrap();
+    assert_eq!(
+        hits.iter()
+            .map(|hit| hit.pkgname.as_ref())
+            .collect::<Vec<_>>(),
+        ["baz", "foo"]
+    );
+
+    // once most ids are dropped the index is rebuilt
+    std::fs::remove_file(logs.join("foo.log")).unwrap();
+    std::fs::remove_file(logs.join("baz.log")).unwrap();
+    let index = LogIndex::open(&root.join("index"), &[&logs]).unwrap();
+    assert_eq!(index.files.len(), 1);
+    assert_eq!(
+        index.search("checksum", 10).unwrap()[0].pkgname.as_ref(),
+        "bar"
+    );
+
+    // a lost postings file is rebuilt rather than failing every search
+    std::fs::write(
+        logs.join("zlib.log"),
+        "configure: error: C compiler cannot create executables\n",
+    )
+    .unwrap();
+    std::fs::remove_file(root.join("index").join(POSTINGS)).unwrap();
+    let index = LogIndex::open(&root.join("index"), &[&logs]).unwrap();
+    assert_eq!(index.files.len(), 2);
+    assert_eq!(index.search("checksum", 10).unwrap().len(), 1);
+    assert_eq!(index.search("C compiler", 10).unwrap().len(), 1);
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
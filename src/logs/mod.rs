@@ -0,0 +1,133 @@
+pub mod classify;
+pub mod cluster;
+pub mod diff;
+pub mod fetch;
+pub mod index;
+pub mod read;
+pub mod stages;
+
+use crate::{build::queue::BuildQueue, config::LogsConfig};
+use std::{
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// A log of a package and where it comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogSource {
+    pub label: String,
+    pub path: PathBuf,
+}
+
+/// Path of the downloaded FTBFS log of `pkgname` in the log cache `dir`
+pub fn cached_log_path(dir: &Path, pkgname: &str) -> PathBuf {
+    dir.join(format!("{pkgname}.log"))
+}
+
+/// Directory holding the previously downloaded logs of `pkgname`
+pub fn history_dir(dir: &Path, pkgname: &str) -> PathBuf {
+    dir.join("history").join(pkgname)
+}
+
+/// Move the downloaded log at `path` into the history of its package, named after its
+/// modification time
+pub fn archive(path: &Path) -> std::io::Result<()> {
+    let (dir, pkgname) = match (path.parent(), path.file_stem()) {
+        (Some(dir), Some(stem)) => (dir, stem.to_string_lossy()),
+        _ => return Ok(()),
+    };
+    let modified = path.metadata()?.modified()?;
+    let history = history_dir(dir, &pkgname);
+    std::fs::create_dir_all(&history)?;
+    std::fs::rename(path, history.join(format!("{}.log", format_time(modified))))
+}
+
+/// Format a time as `YYYY-MM-DDTHH-MM-SS` in UTC, safe for file names and sorted by date
+fn format_time(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs() as i64);
+    let (days, rem) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+
+    // civil from days, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}-{:02}-{:02}",
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+/// Every known log of `pkgname`, newest first: the downloaded riscv64 log and its history, the
+/// latest local build, and the x86_64 log when a directory of them is configured
+pub fn log_sources(config: &LogsConfig, builds: &BuildQueue, pkgname: &str) -> Vec<LogSource> {
+    let mut sources = vec![
+        LogSource {
+            label: "riscv64".to_string(),
+            path: cached_log_path(&config.dir, pkgname),
+        },
+        LogSource {
+            label: "local build".to_string(),
+            path: builds.log_path(pkgname),
+        },
+    ];
+    if let Ok(entries) = std::fs::read_dir(history_dir(&config.dir, pkgname)) {
+        sources.extend(entries.filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let label = format!("riscv64 {}", path.file_stem()?.to_string_lossy());
+            Some(LogSource { label, path })
+        }));
+    }
+    if let Some(ref dir) = config.x86_64_dir {
+        sources.push(LogSource {
+            label: "x86_64".to_string(),
+            path: cached_log_path(dir, pkgname),
+        });
+    }
+
+    let mut sources = sources
+        .into_iter()
+        .filter_map(|source| {
+            let modified = source
+                .path
+                .metadata()
+                .and_then(|meta| meta.modified())
+                .ok()?;
+            Some((modified, source))
+        })
+        .collect::<Vec<_>>();
+    sources.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+    sources.into_iter().map(|(_, source)| source).collect()
+}
+
+/// Find the newest log of `pkgname` between the downloaded FTBFS log and the latest local build
+pub fn find_log(config: &LogsConfig, builds: &BuildQueue, pkgname: &str) -> Option<PathBuf> {
+    [
+        cached_log_path(&config.dir, pkgname),
+        builds.log_path(pkgname),
+    ]
+    .into_iter()
+    .filter_map(|path| {
+        let modified = path.metadata().and_then(|meta| meta.modified()).ok()?;
+        Some((modified, path))
+    })
+    .max_by_key(|(modified, _)| *modified)
+    .map(|(_, path)| path)
+}
+
+#[test]
+fn test_format_time() {
+    let time = UNIX_EPOCH + std::time::Duration::from_secs(1662813005);
+    assert_eq!(format_time(time), "2022-09-10T12-30-05");
+    assert_eq!(format_time(UNIX_EPOCH), "1970-01-01T00-00-00");
+}
@@ -0,0 +1,248 @@
+use super::stages::StageParser;
+use crate::events::Events;
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, Read},
+    path::{Path, PathBuf},
+    sync::mpsc,
+};
+use tokio::sync::oneshot;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+/// Bytes read before a chunk of lines is handed to the viewer
+const CHUNK_LEN: usize = 1 << 20;
+
+/// Read a build log, decompressing it when it is gzip or zstd compressed. Invalid UTF-8 is
+/// replaced rather than failing the whole log.
+pub fn read_log(path: &Path) -> io::Result<String> {
+    let mut bytes = Vec::new();
+    open_log(path)?.read_to_end(&mut bytes)?;
+    Ok(match String::from_utf8(bytes) {
+        Ok(text) => text,
+        Err(err) => String::from_utf8_lossy(err.as_bytes()).into_owned(),
+    })
+}
+
+/// Open a build log for reading, decompressing it on the fly when needed
+pub fn open_log(path: &Path) -> io::Result<Box<dyn Read + Send>> {
+    let file = BufReader::new(File::open(path)?);
+    decoder(file, path.extension().and_then(|ext| ext.to_str()))
+}
+
+/// Wrap `reader` in a decompressor according to its magic number, or to the file `extension`
+/// when it has none
+fn decoder<'a, R>(mut reader: R, extension: Option<&str>) -> io::Result<Box<dyn Read + Send + 'a>>
+where
+    R: BufRead + Send + 'a,
+{
+    let magic = reader.fill_buf()?;
+    if magic.starts_with(GZIP_MAGIC) || extension == Some("gz") {
+        Ok(Box::new(flate2::bufread::MultiGzDecoder::new(reader)))
+    } else if magic.starts_with(ZSTD_MAGIC) || extension == Some("zst") {
+        Ok(Box::new(zstd::stream::read::Decoder::with_buffer(reader)?))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+/// Read the log at `path` in the background. Its lines are sent in chunks with
+/// [`Events::LogChunk`] as they are decompressed, so the start of a large log shows right away,
+/// and its stages are parsed on the way and sent with the final [`Events::LogRead`]. Reading
+/// stops when the returned sender is fired or dropped.
+pub fn spawn_read(id: usize, path: PathBuf, tx: mpsc::Sender<Events>) -> oneshot::Sender<()> {
+    let (cancel, mut cancelled) = oneshot::channel::<()>();
+    tokio::task::spawn_blocking(move || {
+        let mut parser = StageParser::default();
+        let mut send_chunk = |bytes: &[u8]| {
+            let text = String::from_utf8_lossy(bytes).into_owned();
+            for line in text.lines() {
+                parser.push(line);
+            }
+            tx.send(Events::LogChunk { id, text }).is_ok()
+        };
+
+        let result = open_log(&path).and_then(|mut reader| {
+            let mut pending = Vec::new();
+            let mut buf = vec![0; CHUNK_LEN];
+            loop {
+                if !matches!(
+                    cancelled.try_recv(),
+                    Err(oneshot::error::TryRecvError::Empty)
+                ) {
+                    return Ok(false);
+                }
+                let len = reader.read(&mut buf)?;
+                if len == 0 {
+                    return Ok(pending.is_empty() || send_chunk(&pending));
+                }
+                pending.extend_from_slice(&buf[..len]);
+
+                // only whole lines are sent, a newline never splits a UTF-8 sequence
+                if pending.len() >= CHUNK_LEN {
+                    if let Some(end) = pending.iter().rposition(|&b| b == b'\n') {
+                        let rest = pending.split_off(end + 1);
+                        if !send_chunk(&pending) {
+                            return Ok(false);
+                        }
+                        pending = rest;
+                    }
+                }
+            }
+        });
+
+        match result {
+            Ok(false) => (),
+            Ok(true) => {
+                let outline = parser.finish();
+                let _ = tx.send(Events::LogRead {
+                    id,
+                    outline,
+                    error: None,
+                });
+            }
+            Err(err) => {
+                let _ = tx.send(Events::LogRead {
+                    id,
+                    outline: parser.finish(),
+                    error: Some(err.to_string()),
+                });
+            }
+        }
+    });
+    cancel
+}
+
+/// A log kept as a single string with the offsets of its lines, so that multi-megabyte logs
+/// don't cost an allocation per line and are only sliced when shown
+#[derive(Debug, Clone, Default)]
+pub struct LogText {
+    text: String,
+    /// Byte offset of the start of each line
+    starts: Vec<usize>,
+}
+
+impl LogText {
+    pub fn new(text: String) -> Self {
+        let mut log = Self::default();
+        log.push_str(&text);
+        log
+    }
+
+    /// Append the next part of the log
+    pub fn push_str(&mut self, chunk: &str) {
+        let base = self.text.len();
+        if !chunk.is_empty() && (base == 0 || self.text.ends_with('\n')) {
+            self.starts.push(base);
+        }
+        self.text.push_str(chunk);
+        // like `str::lines`, a trailing newline doesn't start another line
+        let len = self.text.len();
+        self.starts.extend(
+            chunk
+                .match_indices('\n')
+                .map(|(i, _)| base + i + 1)
+                .filter(|&start| start < len),
+        );
+    }
+
+    pub fn len(&self) -> usize {
+        self.starts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.starts.is_empty()
+    }
+
+    /// Line `i` without its line ending, panics when out of range
+    pub fn line(&self, i: usize) -> &str {
+        let end = self
+            .starts
+            .get(i + 1)
+            .map_or(self.text.len(), |next| next - 1);
+        let line = &self.text[self.starts[i]..end];
+        let line = line.strip_suffix('\n').unwrap_or(line);
+        line.strip_suffix('\r').unwrap_or(line)
+    }
+
+    pub fn lines(&self) -> impl Iterator<Item = &str> {
+        (0..self.len()).map(|i| self.line(i))
+    }
+}
+
+#[test]
+fn test_log_text() {
+    let text = LogText::new("a\r\n\nccc\n".to_string());
+    assert_eq!(text.lines().collect::<Vec<_>>(), ["a", "", "ccc"]);
+    assert_eq!(text.len(), "a\r\n\nccc\n".lines().count());
+
+    let text = LogText::new("a\nb".to_string());
+    assert_eq!(text.lines().collect::<Vec<_>>(), ["a", "b"]);
+    assert!(LogText::new(String::new()).is_empty());
+
+    let mut text = LogText::new("a\nb".to_string());
+    text.push_str("c\n");
+    text.push_str("d\n");
+    assert_eq!(text.lines().collect::<Vec<_>>(), ["a", "bc", "d"]);
+}
+
+#[test]
+fn test_decompress() {
+    use std::io::Write;
+
+    let log = b"==> Starting build()...\nmake\n".to_vec();
+    let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    gzip.write_all(&log).unwrap();
+    let gzip = gzip.finish().unwrap();
+    let zstd = zstd::encode_all(log.as_slice(), 0).unwrap();
+
+    let decompress = |bytes: &[u8], extension| {
+        let mut output = Vec::new();
+        decoder(bytes, extension)?.read_to_end(&mut output)?;
+        io::Result::Ok(output)
+    };
+    assert_eq!(decompress(&gzip, None).unwrap(), log);
+    assert_eq!(decompress(&zstd, Some("log")).unwrap(), log);
+    assert_eq!(decompress(&log, Some("log")).unwrap(), log);
+    assert!(decompress(&log, Some("gz")).is_err());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_spawn_read() {
+    use std::io::Write;
+
+    let path = std::env::temp_dir().join(format!("lzr-test-read-{}.log.gz", std::process::id()));
+    let log = (0..100_000)
+        .map(|i| format!("line {i}\n"))
+        .chain(["==> Starting build()...\n".to_string(), "make".to_string()])
+        .collect::<String>();
+    let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    gzip.write_all(log.as_bytes()).unwrap();
+    std::fs::write(&path, gzip.finish().unwrap()).unwrap();
+
+    let (tx, rx) = mpsc::channel();
+    let _cancel = spawn_read(4, path.clone(), tx);
+    let mut text = LogText::default();
+    let mut chunks = 0;
+    let outline = loop {
+        match rx.recv_timeout(std::time::Duration::from_secs(10)).unwrap() {
+            Events::LogChunk { id: 4, text: chunk } => {
+                chunks += 1;
+                text.push_str(&chunk);
+            }
+            Events::LogRead {
+                id: 4,
+                outline,
+                error: None,
+            } => break outline,
+            _ => unreachable!(),
+        }
+    };
+
+    assert!(chunks > 1);
+    assert_eq!(text.len(), 100_002);
+    assert_eq!(text.line(100_001), "make");
+    assert_eq!(outline.stages[0].start, 100_000);
+    assert_eq!(outline.stages[0].end, 100_002);
+    std::fs::remove_file(&path).unwrap();
+}
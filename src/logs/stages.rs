@@ -0,0 +1,300 @@
+use regex::Regex;
+use std::{sync::OnceLock, time::Duration};
+
+/// Step of a makepkg run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StageKind {
+    Sources,
+    Prepare,
+    Build,
+    Check,
+    Package,
+    Other,
+}
+
+impl StageKind {
+    fn from_title(title: &str) -> Self {
+        if title.starts_with("Starting build()") {
+            StageKind::Build
+        } else if title.starts_with("Starting check()") {
+            StageKind::Check
+        } else if title.starts_with("Starting prepare()") {
+            StageKind::Prepare
+        } else if title.starts_with("Starting package")
+            || title.starts_with("Entering fakeroot")
+            || title.starts_with("Tidying install")
+            || title.starts_with("Checking for packaging issues")
+            || title.starts_with("Creating package")
+        {
+            StageKind::Package
+        } else if title.starts_with("Retrieving sources")
+            || title.starts_with("Validating source")
+            || title.starts_with("Verifying source")
+            || title.starts_with("Extracting sources")
+        {
+            StageKind::Sources
+        } else {
+            StageKind::Other
+        }
+    }
+
+    /// Function named in `==> ERROR: A failure occurred in <function>().`
+    fn from_function(function: &str) -> Self {
+        match function {
+            "build" => StageKind::Build,
+            "check" => StageKind::Check,
+            "prepare" => StageKind::Prepare,
+            f if f.starts_with("package") => StageKind::Package,
+            _ => StageKind::Other,
+        }
+    }
+}
+
+/// A `==>` section of the log
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stage {
+    /// Header text without the `==>` marker
+    pub title: String,
+    pub kind: StageKind,
+    /// Index of the header line
+    pub start: usize,
+    /// Index after the last line of the stage
+    pub end: usize,
+    /// Time between this stage and the next one, when lines are timestamped
+    pub duration: Option<Duration>,
+    pub failed: bool,
+}
+
+/// Where a makepkg run stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Failure {
+    pub kind: StageKind,
+    /// Index of the stage holding the error
+    pub stage: usize,
+    /// Index of the `==> ERROR:` line
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StageOutline {
+    pub stages: Vec<Stage>,
+    pub failure: Option<Failure>,
+}
+
+struct Patterns {
+    ansi: Regex,
+    failure: Regex,
+    timestamp: Regex,
+}
+
+fn patterns() -> &'static Patterns {
+    static PATTERNS: OnceLock<Patterns> = OnceLock::new();
+    PATTERNS.get_or_init(|| Patterns {
+        // CSI, charset designations like the `(B` of `tput sgr0`, and OSC
+        ansi: Regex::new(r"\x1b(\[[0-9;?]*[A-Za-z]|[()][0-9A-Za-z]|\][^\x07\x1b]*(\x07|\x1b\\))")
+            .unwrap(),
+        failure: Regex::new(r"^==> ERROR: A failure occurred in (\w+)\(\)").unwrap(),
+        timestamp: Regex::new(r"^\[?(\d{4})-(\d{2})-(\d{2})[T ](\d{2}):(\d{2}):(\d{2})").unwrap(),
+    })
+}
+
+/// Drop the terminal color sequences makepkg prints around its messages
+pub fn strip_ansi(line: &str) -> std::borrow::Cow<'_, str> {
+    patterns().ansi.replace_all(line, "")
+}
+
+/// Split the log into its `==>` stages. `==> ERROR:` and `==> WARNING:` lines stay in the
+/// current stage.
+pub fn parse<S: AsRef<str>>(lines: &[S]) -> StageOutline {
+    let mut parser = StageParser::default();
+    for line in lines {
+        parser.push(line.as_ref());
+    }
+    parser.finish()
+}
+
+/// Line by line form of [`parse`], for logs read in chunks
+#[derive(Debug, Default)]
+pub struct StageParser {
+    outline: StageOutline,
+    /// Timestamp of the first and last timestamped lines of each stage
+    times: Vec<(Option<u64>, Option<u64>)>,
+    lines: usize,
+}
+
+impl StageParser {
+    pub fn push(&mut self, line: &str) {
+        let patterns = patterns();
+        let i = self.lines;
+        self.lines += 1;
+
+        let line = strip_ansi(line);
+        let timestamp = parse_timestamp(&line);
+        let message = match timestamp {
+            // keep what follows the timestamp, like `[2022-09-10 12:00:00] ==> ...`
+            Some(_) => patterns
+                .timestamp
+                .replace(&line, "")
+                .trim_start_matches(']')
+                .trim_start()
+                .to_string(),
+            None => line.to_string(),
+        };
+
+        let outline = &mut self.outline;
+        if let Some(captures) = patterns.failure.captures(&message) {
+            if let Some(stage) = outline.stages.last_mut() {
+                stage.failed = true;
+            }
+            outline.failure = Some(Failure {
+                kind: StageKind::from_function(&captures[1]),
+                stage: outline.stages.len().saturating_sub(1),
+                line: i,
+            });
+        } else if let Some(title) = message.strip_prefix("==> ") {
+            if !title.starts_with("ERROR:") && !title.starts_with("WARNING:") {
+                if let Some(stage) = outline.stages.last_mut() {
+                    stage.end = i;
+                }
+                outline.stages.push(Stage {
+                    title: title.trim().to_string(),
+                    kind: StageKind::from_title(title),
+                    start: i,
+                    // set when the next stage starts or the log ends
+                    end: i + 1,
+                    duration: None,
+                    failed: false,
+                });
+                self.times.push((None, None));
+            }
+        }
+
+        if let (Some(time), Some(last)) = (timestamp, self.times.last_mut()) {
+            last.0.get_or_insert(time);
+            last.1 = Some(time);
+        }
+    }
+
+    /// End the log and return its stages
+    pub fn finish(mut self) -> StageOutline {
+        let times = &self.times;
+        let outline = &mut self.outline;
+        if let Some(stage) = outline.stages.last_mut() {
+            stage.end = self.lines;
+        }
+
+        // a stage lasts until the next one starts, or until its last line for the final stage
+        for i in 0..outline.stages.len() {
+            let start = times[i].0;
+            let end = times.get(i + 1).and_then(|next| next.0).or(times[i].1);
+            if let (Some(start), Some(end)) = (start, end) {
+                outline.stages[i].duration = Some(Duration::from_secs(end.saturating_sub(start)));
+            }
+        }
+
+        self.outline
+    }
+}
+
+/// Seconds since the epoch of a leading `YYYY-MM-DD HH:MM:SS` timestamp, in UTC
+fn parse_timestamp(line: &str) -> Option<u64> {
+    let captures = patterns().timestamp.captures(line)?;
+    let field = |i: usize| captures[i].parse::<i64>().ok();
+    let (year, month, day) = (field(1)?, field(2)?, field(3)?);
+    let (hour, minute, second) = (field(4)?, field(5)?, field(6)?);
+
+    // days from civil, see http://howardhinnant.github.io/date_algorithms.html
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+
+    u64::try_from(days * 86400 + hour * 3600 + minute * 60 + second).ok()
+}
+
+#[test]
+fn test_parse_timestamp() {
+    assert_eq!(parse_timestamp("1970-01-01 00:00:00 foo"), Some(0));
+    assert_eq!(
+        parse_timestamp("[2022-09-10T12:30:05] ==> foo"),
+        Some(1662813005)
+    );
+    assert_eq!(parse_timestamp("==> foo"), None);
+}
+
+#[test]
+fn test_parse_stages() {
+    let log = [
+        "\x1b[1m\x1b[32m==>\x1b[m\x1b[1m Making package: foo 1.0-1\x1b[m",
+        "==> Retrieving sources...",
+        "  -> Downloading foo-1.0.tar.gz...",
+        "==> Starting build()...",
+        "gcc -c foo.c",
+        "==> Starting check()...",
+        "FAIL: test_io",
+        "==> ERROR: A failure occurred in check().",
+        "    Aborting...",
+    ];
+    let outline = parse(&log);
+
+    let kinds = outline.stages.iter().map(|s| s.kind).collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        [
+            StageKind::Other,
+            StageKind::Sources,
+            StageKind::Build,
+            StageKind::Check
+        ]
+    );
+    assert_eq!(outline.stages[0].title, "Making package: foo 1.0-1");
+    assert_eq!((outline.stages[2].start, outline.stages[2].end), (3, 5));
+    assert_eq!(outline.stages[3].end, log.len());
+    assert!(outline.stages[3].failed);
+    assert_eq!(
+        outline.failure,
+        Some(Failure {
+            kind: StageKind::Check,
+            stage: 3,
+            line: 7
+        })
+    );
+}
+
+#[test]
+fn test_parse_tput_colors() {
+    // makepkg colors from `tput bold`, `tput setaf 2` and `tput sgr0` on an xterm
+    let log = [
+        "\x1b[1m\x1b[32m==>\x1b(B\x1b[m\x1b[1m Making package: foo 1.0-1\x1b(B\x1b[m",
+        "\x1b[1m\x1b[32m==>\x1b(B\x1b[m\x1b[1m Starting build()...\x1b(B\x1b[m",
+        "make",
+        "\x1b[1m\x1b[31m==> ERROR:\x1b(B\x1b[m\x1b[1m A failure occurred in build().\x1b(B\x1b[m",
+    ];
+    let outline = parse(&log);
+
+    assert_eq!(outline.stages.len(), 2);
+    assert_eq!(outline.stages[0].title, "Making package: foo 1.0-1");
+    assert_eq!(outline.stages[1].kind, StageKind::Build);
+    assert_eq!(outline.failure.map(|failure| failure.line), Some(3));
+    assert_eq!(strip_ansi("\x1b]0;makepkg\x07done"), "done");
+}
+
+#[test]
+fn test_stage_durations() {
+    let log = [
+        "2022-09-10 12:00:00 ==> Starting build()...",
+        "2022-09-10 12:03:10 make",
+        "2022-09-10 12:05:00 ==> Starting check()...",
+        "2022-09-10 12:05:42 ok",
+    ];
+    let outline = parse(&log);
+
+    assert_eq!(outline.stages[0].kind, StageKind::Build);
+    assert_eq!(outline.stages[0].duration, Some(Duration::from_secs(300)));
+    assert_eq!(outline.stages[1].duration, Some(Duration::from_secs(42)));
+    assert!(parse(&["==> Starting build()..."]).stages[0]
+        .duration
+        .is_none());
+}
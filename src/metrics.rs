@@ -0,0 +1,182 @@
+use crate::component::packages::PkgInfo;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Porting progress in the Prometheus text format, computed from the merged package table
+pub(crate) fn render(
+    packages: &[PkgInfo],
+    warnings: usize,
+    last_fetch: &HashMap<&'static str, SystemTime>,
+) -> String {
+    let mut marks = BTreeMap::new();
+    let mut assignees = BTreeMap::new();
+    for pkg in packages {
+        for mark in pkg.marks().iter() {
+            *marks.entry(*mark).or_insert(0) += 1;
+        }
+        if pkg.has_assignee() {
+            *assignees.entry(pkg.assignee()).or_insert(0) += 1;
+        }
+    }
+    let count =
+        |predicate: fn(&PkgInfo) -> bool| packages.iter().filter(|pkg| predicate(pkg)).count();
+
+    let mut metrics = String::new();
+    gauge(
+        &mut metrics,
+        "lzr_packages",
+        "Packages in the table",
+        [(None, packages.len())],
+    );
+    gauge(
+        &mut metrics,
+        "lzr_ftbfs_packages",
+        "Packages failing to build from source",
+        [(None, count(PkgInfo::is_ftbfs))],
+    );
+    gauge(
+        &mut metrics,
+        "lzr_leaf_packages",
+        "Leaf packages of the status page",
+        [(None, count(PkgInfo::is_leaf))],
+    );
+    gauge(
+        &mut metrics,
+        "lzr_unclaimed_ftbfs_packages",
+        "Packages failing to build that nobody is working on",
+        [(None, count(|pkg| pkg.is_ftbfs() && !pkg.has_assignee()))],
+    );
+    gauge(
+        &mut metrics,
+        "lzr_outdated_packages",
+        "Packages older than their x86_64 version",
+        [(None, count(PkgInfo::is_outdated))],
+    );
+    gauge(
+        &mut metrics,
+        "lzr_marked_packages",
+        "Packages having the mark",
+        marks.into_iter().map(|(mark, n)| (Some(("mark", mark)), n)),
+    );
+    gauge(
+        &mut metrics,
+        "lzr_assigned_packages",
+        "Packages claimed by the packager",
+        assignees
+            .into_iter()
+            .map(|(assignee, n)| (Some(("assignee", assignee)), n)),
+    );
+    gauge(
+        &mut metrics,
+        "lzr_status_warnings",
+        "Rows of the status page that couldn't be read",
+        [(None, warnings)],
+    );
+
+    let mut sources = last_fetch.iter().collect::<Vec<_>>();
+    sources.sort();
+    gauge(
+        &mut metrics,
+        "lzr_last_fetch_timestamp_seconds",
+        "Unix time of the last successful fetch of the source",
+        sources.into_iter().map(|(source, time)| {
+            let seconds = time
+                .duration_since(UNIX_EPOCH)
+                .map(|time| time.as_secs())
+                .unwrap_or_default();
+            (Some(("source", *source)), seconds as usize)
+        }),
+    );
+    metrics
+}
+
+/// Write a gauge with one sample per label, the header is written even without sample
+fn gauge<'a>(
+    metrics: &mut String,
+    name: &str,
+    help: &str,
+    samples: impl IntoIterator<Item = (Option<(&'a str, &'a str)>, usize)>,
+) {
+    let _ = writeln!(metrics, "# HELP {name} {help}");
+    let _ = writeln!(metrics, "# TYPE {name} gauge");
+    for (label, value) in samples {
+        match label {
+            Some((key, label)) => {
+                let label = label
+                    .replace('\\', r"\\")
+                    .replace('"', r#"\""#)
+                    .replace('\n', r"\n");
+                let _ = writeln!(metrics, "{name}{{{key}=\"{label}\"}} {value}");
+            }
+            None => {
+                let _ = writeln!(metrics, "{name} {value}");
+            }
+        }
+    }
+}
+
+#[test]
+fn test_render() {
+    use crate::{component::packages::PkgInfoBuilder, types::Mark};
+    use std::time::Duration;
+
+    let mark = |name: &str| Mark {
+        name: name.into(),
+        by: "Moody".into(),
+        comment: "".into(),
+    };
+    let packages = [
+        PkgInfoBuilder::default()
+            .name("bear".into())
+            .process("FTBFS".into())
+            .assignee("Moody".into())
+            .marks(vec![mark("noqemu"), mark("upstreamed")])
+            .build()
+            .unwrap(),
+        PkgInfoBuilder::default()
+            .name("ghc".into())
+            .process("FTBFS".into())
+            .marks(vec![mark("noqemu")])
+            .build()
+            .unwrap(),
+        PkgInfoBuilder::default()
+            .name("zsh".into())
+            .process("Leaf package".into())
+            .assignee("a \"b\"".into())
+            .build()
+            .unwrap(),
+    ];
+    let last_fetch = HashMap::from([("felix", UNIX_EPOCH + Duration::from_secs(1662813005))]);
+
+    let metrics = render(&packages, 1, &last_fetch);
+    let samples = metrics
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        samples,
+        [
+            "lzr_packages 3",
+            "lzr_ftbfs_packages 2",
+            "lzr_leaf_packages 1",
+            "lzr_unclaimed_ftbfs_packages 1",
+            "lzr_outdated_packages 0",
+            r#"lzr_marked_packages{mark="noqemu"} 2"#,
+            r#"lzr_marked_packages{mark="upstreamed"} 1"#,
+            r#"lzr_assigned_packages{assignee="Moody"} 1"#,
+            r#"lzr_assigned_packages{assignee="a \"b\""} 1"#,
+            "lzr_status_warnings 1",
+            r#"lzr_last_fetch_timestamp_seconds{source="felix"} 1662813005"#,
+        ]
+    );
+    assert!(metrics.starts_with(
+        "# HELP lzr_packages Packages in the table\n# TYPE lzr_packages gauge\nlzr_packages 3\n"
+    ));
+
+    // an empty table still declares every metric
+    let metrics = render(&[], 0, &HashMap::new());
+    assert_eq!(metrics.matches("# TYPE").count(), 9);
+}
@@ -0,0 +1,147 @@
+use super::syncdb::DbPackage;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+type BoxStr = Box<str>;
+
+/// Dependency graph between the packages of the sync databases. Edges are stored in reverse: for
+/// each package we know which packages need it to build or install.
+#[derive(Debug, Default)]
+pub struct DepGraph {
+    /// pkgname -> pkgnames that depend on it directly
+    dependents: HashMap<BoxStr, Vec<BoxStr>>,
+    /// pkgbase -> pkgnames built from it
+    bases: HashMap<BoxStr, Vec<BoxStr>>,
+}
+
+impl DepGraph {
+    pub fn from_packages(pkgs: &[DbPackage]) -> Self {
+        // A dependency can be satisfied by a package with the same name or by any package that
+        // provides it.
+        let mut providers: HashMap<&str, Vec<&str>> = HashMap::new();
+        for pkg in pkgs {
+            providers.entry(&pkg.name).or_default().push(&pkg.name);
+            for provide in &pkg.provides {
+                providers.entry(provide).or_default().push(&pkg.name);
+            }
+        }
+
+        let mut graph = Self::default();
+        for pkg in pkgs {
+            graph
+                .bases
+                .entry(pkg.base.clone())
+                .or_default()
+                .push(pkg.name.clone());
+
+            let mut resolved = HashSet::new();
+            for dep in pkg.all_depends() {
+                let candidates = match providers.get(dep) {
+                    Some(candidates) => candidates,
+                    None => continue,
+                };
+                // prefer the real package when the name is also provided by others
+                if candidates.contains(&dep) {
+                    resolved.insert(dep);
+                } else {
+                    resolved.extend(candidates.iter().copied());
+                }
+            }
+            resolved.remove(pkg.name.as_ref());
+
+            for dep in resolved {
+                graph
+                    .dependents
+                    .entry(dep.into())
+                    .or_default()
+                    .push(pkg.name.clone());
+            }
+        }
+
+        graph
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bases.is_empty()
+    }
+
+    /// Resolve a name from the status page, which can be either a pkgbase or a pkgname, into the
+    /// pkgnames it produces.
+    pub fn pkgnames<'a>(&'a self, name: &'a str) -> Vec<&'a str> {
+        match self.bases.get(name) {
+            Some(names) => names.iter().map(|n| n.as_ref()).collect(),
+            None if self.dependents.contains_key(name) => vec![name],
+            None => Vec::new(),
+        }
+    }
+
+    /// Return all packages that directly or indirectly depend on `name`, in breadth-first order.
+    /// Packages built from the same pkgbase are not included.
+    pub fn dependents(&self, name: &str) -> Vec<&str> {
+        let seeds = self.pkgnames(name);
+        let mut visited: HashSet<&str> = seeds.iter().copied().collect();
+        let mut queue: VecDeque<&str> = seeds.into_iter().collect();
+        let mut result = Vec::new();
+
+        while let Some(current) = queue.pop_front() {
+            let next = match self.dependents.get(current) {
+                Some(next) => next,
+                None => continue,
+            };
+            for pkg in next {
+                if visited.insert(pkg) {
+                    result.push(pkg.as_ref());
+                    queue.push_back(pkg);
+                }
+            }
+        }
+
+        result
+    }
+
+    /// Number of packages that can't be built or installed while `name` is broken
+    pub fn blocks(&self, name: &str) -> usize {
+        self.dependents(name).len()
+    }
+}
+
+#[cfg(test)]
+pub(super) fn pkg(name: &str, depends: &[&str]) -> DbPackage {
+    DbPackage {
+        name: name.into(),
+        base: name.into(),
+        depends: depends.iter().map(|&d| d.into()).collect(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_transitive_dependents() {
+    let mut bash = pkg("bash", &["readline", "glibc"]);
+    bash.provides.push("sh".into());
+    let pkgs = vec![
+        pkg("glibc", &[]),
+        pkg("readline", &["glibc", "ncurses"]),
+        pkg("ncurses", &["glibc"]),
+        bash,
+        pkg("autoconf", &["sh"]),
+        pkg("unrelated", &[]),
+    ];
+    let graph = DepGraph::from_packages(&pkgs);
+
+    let mut dependents = graph.dependents("ncurses");
+    dependents.sort_unstable();
+    assert_eq!(dependents, ["autoconf", "bash", "readline"]);
+    assert_eq!(graph.blocks("glibc"), 4);
+    assert_eq!(graph.blocks("unrelated"), 0);
+    assert_eq!(graph.blocks("not-in-db"), 0);
+}
+
+#[test]
+fn test_split_package_base() {
+    let mut python_foo = pkg("python-foo", &[]);
+    python_foo.base = "foo".into();
+    let pkgs = vec![pkg("foo", &[]), python_foo, pkg("bar", &["python-foo"])];
+    let graph = DepGraph::from_packages(&pkgs);
+
+    assert_eq!(graph.dependents("foo"), ["bar"]);
+}
@@ -0,0 +1,5 @@
+pub mod depgraph;
+pub mod order;
+pub mod outdated;
+pub mod syncdb;
+pub mod vercmp;
@@ -0,0 +1,205 @@
+use super::depgraph::DepGraph;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+type BoxStr = Box<str>;
+
+/// A suggested order to rebuild a set of packages
+#[derive(Debug, Default)]
+pub struct RebuildOrder {
+    /// Every package of the set, packages with no broken dependencies first. Members of a cycle
+    /// are placed next to each other.
+    pub order: Vec<BoxStr>,
+    /// Groups of packages that depend on each other
+    pub cycles: Vec<Vec<BoxStr>>,
+}
+
+/// Sort `names` so that each package comes after the packages of the set it depends on, directly
+/// or through other packages. Names can be either pkgbase or pkgname, like the status page shows.
+pub fn rebuild_order<S: AsRef<str>>(graph: &DepGraph, names: &[S]) -> RebuildOrder {
+    let names: Vec<&str> = names.iter().map(|n| n.as_ref()).collect();
+
+    // pkgname -> index of the set member that produces it
+    let mut owner: HashMap<&str, usize> = HashMap::new();
+    for (i, name) in names.iter().enumerate() {
+        owner.insert(name, i);
+        for pkgname in graph.pkgnames(name) {
+            owner.insert(pkgname, i);
+        }
+    }
+
+    // edges[i] contains the members that must be built after member i
+    let edges: Vec<Vec<usize>> = names
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            let blocked: HashSet<usize> = graph
+                .dependents(name)
+                .into_iter()
+                .filter_map(|pkgname| owner.get(pkgname).copied())
+                .filter(|&j| j != i)
+                .collect();
+            let mut blocked: Vec<usize> = blocked.into_iter().collect();
+            blocked.sort_unstable();
+            blocked
+        })
+        .collect();
+
+    let components = strongly_connected_components(&edges);
+    let mut component_of = vec![0; names.len()];
+    for (c, members) in components.iter().enumerate() {
+        for &m in members {
+            component_of[m] = c;
+        }
+    }
+
+    // Kahn's algorithm over the condensed graph, which has no cycle
+    let mut indegree = vec![0; components.len()];
+    let mut condensed: Vec<BTreeSet<usize>> = vec![BTreeSet::new(); components.len()];
+    for (i, blocked) in edges.iter().enumerate() {
+        for &j in blocked {
+            let (from, to) = (component_of[i], component_of[j]);
+            if from != to && condensed[from].insert(to) {
+                indegree[to] += 1;
+            }
+        }
+    }
+
+    let sort_key = |c: usize| (names[components[c][0]], c);
+    let mut ready: BTreeSet<(&str, usize)> = (0..components.len())
+        .filter(|&c| indegree[c] == 0)
+        .map(sort_key)
+        .collect();
+
+    let mut result = RebuildOrder::default();
+    while let Some(first) = ready.iter().next().copied() {
+        ready.remove(&first);
+        let c = first.1;
+
+        let members: Vec<BoxStr> = components[c].iter().map(|&m| names[m].into()).collect();
+        if members.len() > 1 {
+            result.cycles.push(members.clone());
+        }
+        result.order.extend(members);
+
+        for &next in &condensed[c] {
+            indegree[next] -= 1;
+            if indegree[next] == 0 {
+                ready.insert(sort_key(next));
+            }
+        }
+    }
+
+    result
+}
+
+/// Tarjan's algorithm. Each returned component is sorted by index.
+fn strongly_connected_components(edges: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    struct State<'a> {
+        edges: &'a [Vec<usize>],
+        counter: usize,
+        index: Vec<Option<usize>>,
+        lowlink: Vec<usize>,
+        on_stack: Vec<bool>,
+        stack: Vec<usize>,
+        components: Vec<Vec<usize>>,
+    }
+
+    fn connect(state: &mut State, v: usize) {
+        state.index[v] = Some(state.counter);
+        state.lowlink[v] = state.counter;
+        state.counter += 1;
+        state.stack.push(v);
+        state.on_stack[v] = true;
+
+        for &w in &state.edges[v] {
+            match state.index[w] {
+                None => {
+                    connect(state, w);
+                    state.lowlink[v] = state.lowlink[v].min(state.lowlink[w]);
+                }
+                Some(index) if state.on_stack[w] => {
+                    state.lowlink[v] = state.lowlink[v].min(index);
+                }
+                _ => (),
+            }
+        }
+
+        if Some(state.lowlink[v]) == state.index[v] {
+            let mut component = Vec::new();
+            while let Some(w) = state.stack.pop() {
+                state.on_stack[w] = false;
+                component.push(w);
+                if w == v {
+                    break;
+                }
+            }
+            component.sort_unstable();
+            state.components.push(component);
+        }
+    }
+
+    let len = edges.len();
+    let mut state = State {
+        edges,
+        counter: 0,
+        index: vec![None; len],
+        lowlink: vec![0; len],
+        on_stack: vec![false; len],
+        stack: Vec::new(),
+        components: Vec::new(),
+    };
+    for v in 0..len {
+        if state.index[v].is_none() {
+            connect(&mut state, v);
+        }
+    }
+
+    state.components
+}
+
+#[cfg(test)]
+use super::depgraph::pkg;
+
+#[test]
+fn test_rebuild_order() {
+    let graph = DepGraph::from_packages(&[
+        pkg("glibc", &[]),
+        pkg("zlib", &["glibc"]),
+        pkg("fine", &["glibc"]),
+        pkg("curl", &["fine", "openssl", "zlib"]),
+        pkg("openssl", &["glibc"]),
+        pkg("git", &["curl"]),
+    ]);
+
+    let order = rebuild_order(&graph, &["git", "zlib", "openssl", "curl"]);
+    assert_eq!(
+        order.order,
+        vec!["openssl".into(), "zlib".into(), "curl".into(), "git".into()]
+    );
+    assert!(order.cycles.is_empty());
+}
+
+#[test]
+fn test_rebuild_order_with_cycle() {
+    let graph = DepGraph::from_packages(&[
+        pkg("freetype2", &["harfbuzz"]),
+        pkg("harfbuzz", &["freetype2"]),
+        pkg("cairo", &["freetype2"]),
+        pkg("leaf", &[]),
+    ]);
+
+    let order = rebuild_order(&graph, &["cairo", "harfbuzz", "freetype2", "leaf"]);
+    assert_eq!(
+        order.order,
+        vec![
+            "harfbuzz".into(),
+            "freetype2".into(),
+            "cairo".into(),
+            "leaf".into()
+        ]
+    );
+    assert_eq!(
+        order.cycles,
+        vec![vec![Box::from("harfbuzz"), Box::from("freetype2")]]
+    );
+}
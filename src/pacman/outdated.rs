@@ -0,0 +1,62 @@
+use super::{syncdb::DbPackage, vercmp::vercmp};
+use serde::{Deserialize, Serialize};
+use std::{cmp::Ordering, collections::HashMap};
+
+type BoxStr = Box<str>;
+
+/// A pkgbase whose riscv64 version lags behind x86_64
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Outdated {
+    pub riscv64: BoxStr,
+    pub x86_64: BoxStr,
+}
+
+/// Compare the riscv64 and x86_64 packages by pkgbase and return those that are older on
+/// riscv64, keyed by pkgbase. Packages missing from either side are ignored.
+pub fn find_outdated(riscv64: &[DbPackage], x86_64: &[DbPackage]) -> HashMap<BoxStr, Outdated> {
+    let upstream: HashMap<&str, &str> = x86_64
+        .iter()
+        .map(|pkg| (pkg.base.as_ref(), pkg.version.as_ref()))
+        .collect();
+
+    riscv64
+        .iter()
+        .filter_map(|pkg| {
+            let x86_version = upstream.get(pkg.base.as_ref())?;
+            if vercmp(&pkg.version, x86_version) != Ordering::Less {
+                return None;
+            }
+
+            let outdated = Outdated {
+                riscv64: pkg.version.clone(),
+                x86_64: (*x86_version).into(),
+            };
+            Some((pkg.base.clone(), outdated))
+        })
+        .collect()
+}
+
+#[test]
+fn test_find_outdated() {
+    let pkg = |name: &str, version: &str| DbPackage {
+        name: name.into(),
+        base: name.into(),
+        version: version.into(),
+        ..Default::default()
+    };
+
+    let riscv64 = [
+        pkg("bash", "5.1.016-1"),
+        pkg("zlib", "1:1.2.12-2"),
+        pkg("riscv-only", "1.0-1"),
+    ];
+    let x86_64 = [
+        pkg("bash", "5.1.016-3"),
+        pkg("zlib", "1:1.2.12-1"),
+        pkg("x86-only", "1.0-1"),
+    ];
+
+    let outdated = find_outdated(&riscv64, &x86_64);
+    assert_eq!(outdated.len(), 1);
+    assert_eq!(outdated["bash"].x86_64.as_ref(), "5.1.016-3");
+}
@@ -0,0 +1,176 @@
+use anyhow::{Context, Result};
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Read},
+    path::Path,
+};
+
+type BoxStr = Box<str>;
+
+/// A package entry read from a pacman sync database
+#[derive(Debug, Default, Clone)]
+pub struct DbPackage {
+    pub name: BoxStr,
+    pub base: BoxStr,
+    pub version: BoxStr,
+    pub depends: Vec<BoxStr>,
+    pub makedepends: Vec<BoxStr>,
+    pub checkdepends: Vec<BoxStr>,
+    pub provides: Vec<BoxStr>,
+}
+
+impl DbPackage {
+    /// Iterate over every dependency that is needed to build or install this package
+    pub fn all_depends(&self) -> impl Iterator<Item = &str> {
+        self.depends
+            .iter()
+            .chain(self.makedepends.iter())
+            .chain(self.checkdepends.iter())
+            .map(|dep| dep.as_ref())
+    }
+
+    /// Parse the content of a `desc` or `depends` file into this package. Both files share the
+    /// same `%KEY%` followed by values format, so an entry can be built by feeding them in any
+    /// order.
+    pub fn parse_entry(&mut self, content: &str) {
+        let mut key = "";
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if line.len() > 2 && line.starts_with('%') && line.ends_with('%') {
+                key = &line[1..line.len() - 1];
+                continue;
+            }
+
+            match key {
+                "NAME" => self.name = line.into(),
+                "BASE" => self.base = line.into(),
+                "VERSION" => self.version = line.into(),
+                "DEPENDS" => self.depends.push(strip_version(line).into()),
+                "MAKEDEPENDS" => self.makedepends.push(strip_version(line).into()),
+                "CHECKDEPENDS" => self.checkdepends.push(strip_version(line).into()),
+                "PROVIDES" => self.provides.push(strip_version(line).into()),
+                _ => (),
+            }
+        }
+    }
+}
+
+/// Strip the version constraint from a dependency string, `glibc>=2.36` become `glibc`
+pub fn strip_version(dep: &str) -> &str {
+    dep.split(['<', '>', '=']).next().unwrap_or(dep)
+}
+
+/// Read all packages from a sync database file. The database is a tarball, which may be gzip
+/// compressed, with one `<pkgname>-<pkgver>/` directory for each package.
+pub fn read_db(path: &Path) -> Result<Vec<DbPackage>> {
+    let file = std::fs::File::open(path)
+        .with_context(|| format!("fail to open sync database {}", path.display()))?;
+    let mut reader = BufReader::new(file);
+
+    // peek the magic number without consuming it
+    let is_gzip = reader.fill_buf()?.starts_with(&[0x1f, 0x8b]);
+    let reader: Box<dyn Read> = if is_gzip {
+        Box::new(flate2::read::GzDecoder::new(reader))
+    } else {
+        Box::new(reader)
+    };
+
+    parse_db(reader).with_context(|| format!("fail to parse sync database {}", path.display()))
+}
+
+/// Parse an uncompressed sync database tarball
+pub fn parse_db<R: Read>(reader: R) -> Result<Vec<DbPackage>> {
+    let mut archive = tar::Archive::new(reader);
+    let mut packages: HashMap<String, DbPackage> = HashMap::new();
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+
+        let path = entry.path()?;
+        let dir = match path.parent().and_then(|p| p.to_str()) {
+            Some(dir) if !dir.is_empty() => dir.to_string(),
+            _ => continue,
+        };
+
+        let mut content = String::new();
+        entry.read_to_string(&mut content)?;
+        packages.entry(dir).or_default().parse_entry(&content);
+    }
+
+    let packages = packages
+        .into_values()
+        .filter(|pkg| !pkg.name.is_empty())
+        .map(|mut pkg| {
+            if pkg.base.is_empty() {
+                pkg.base = pkg.name.clone();
+            }
+            pkg
+        })
+        .collect();
+
+    Ok(packages)
+}
+
+/// Read `<dir>/<repo>.db` for each of the given repositories
+pub fn load_repos<S: AsRef<str>>(dir: &Path, repos: &[S]) -> Result<Vec<DbPackage>> {
+    let mut packages = Vec::new();
+    for repo in repos {
+        let path = dir.join(format!("{}.db", repo.as_ref()));
+        packages.extend(read_db(&path)?);
+    }
+
+    Ok(packages)
+}
+
+#[test]
+fn test_parse_entry() {
+    let desc = "%FILENAME%\nbash-5.1.016-1-riscv64.pkg.tar.zst\n\n%NAME%\nbash\n\n%BASE%\nbash\n\n\
+                %VERSION%\n5.1.016-1\n\n%DEPENDS%\nreadline>=7.0\nglibc\nncurses\n\n\
+                %PROVIDES%\nsh\n\n%MAKEDEPENDS%\nautoconf\n";
+    let mut pkg = DbPackage::default();
+    pkg.parse_entry(desc);
+
+    assert_eq!(pkg.name.as_ref(), "bash");
+    assert_eq!(pkg.version.as_ref(), "5.1.016-1");
+    assert_eq!(
+        pkg.all_depends().collect::<Vec<_>>(),
+        ["readline", "glibc", "ncurses", "autoconf"]
+    );
+    assert_eq!(pkg.provides[0].as_ref(), "sh");
+}
+
+#[test]
+fn test_parse_db() {
+    let mut builder = tar::Builder::new(Vec::new());
+    let mut append = |path: &str, content: &str| {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, path, content.as_bytes())
+            .unwrap();
+    };
+    append(
+        "zlib-1.2.12-1/desc",
+        "%NAME%\nzlib\n\n%VERSION%\n1:1.2.12-1\n",
+    );
+    append("file-5.42-1/desc", "%NAME%\nfile\n\n%VERSION%\n5.42-1\n");
+    append("file-5.42-1/depends", "%DEPENDS%\nzlib\n");
+    let tarball = builder.into_inner().unwrap();
+
+    let mut pkgs = parse_db(tarball.as_slice()).unwrap();
+    pkgs.sort_by(|a, b| a.name.cmp(&b.name));
+
+    assert_eq!(pkgs.len(), 2);
+    assert_eq!(pkgs[0].name.as_ref(), "file");
+    assert_eq!(pkgs[0].base.as_ref(), "file");
+    assert_eq!(pkgs[0].depends, vec!["zlib".into()]);
+}
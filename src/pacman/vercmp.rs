@@ -0,0 +1,155 @@
+use std::cmp::Ordering;
+
+/// Compare two full package versions in `[epoch:]pkgver[-pkgrel]` form, the same way as
+/// `vercmp(8)` from pacman does.
+pub fn vercmp(a: &str, b: &str) -> Ordering {
+    if a == b {
+        return Ordering::Equal;
+    }
+
+    let (epoch1, ver1, rel1) = parse_evr(a);
+    let (epoch2, ver2, rel2) = parse_evr(b);
+
+    rpmvercmp(epoch1, epoch2)
+        .then_with(|| rpmvercmp(ver1, ver2))
+        .then_with(|| match (rel1, rel2) {
+            (Some(rel1), Some(rel2)) => rpmvercmp(rel1, rel2),
+            _ => Ordering::Equal,
+        })
+}
+
+/// Split a version into epoch, pkgver and pkgrel. The epoch default to `0` when absent.
+fn parse_evr(evr: &str) -> (&str, &str, Option<&str>) {
+    let digits = evr.bytes().take_while(|c| c.is_ascii_digit()).count();
+    let (epoch, rest) = match evr[digits..].strip_prefix(':') {
+        Some(rest) if digits > 0 => (&evr[..digits], rest),
+        Some(rest) => ("0", rest),
+        None => ("0", evr),
+    };
+
+    match rest.rfind('-') {
+        Some(idx) => (epoch, &rest[..idx], Some(&rest[idx + 1..])),
+        None => (epoch, rest, None),
+    }
+}
+
+/// Compare two version strings segment by segment. Numeric segments are compared as numbers,
+/// alphabetic segments as strings, and a numeric segment is always newer than an alphabetic one.
+fn rpmvercmp(a: &str, b: &str) -> Ordering {
+    if a == b {
+        return Ordering::Equal;
+    }
+
+    let (one, two) = (a.as_bytes(), b.as_bytes());
+    let (mut i, mut j) = (0, 0);
+
+    while i < one.len() && j < two.len() {
+        let (start1, start2) = (i, j);
+        while i < one.len() && !one[i].is_ascii_alphanumeric() {
+            i += 1;
+        }
+        while j < two.len() && !two[j].is_ascii_alphanumeric() {
+            j += 1;
+        }
+
+        if i >= one.len() || j >= two.len() {
+            break;
+        }
+
+        // a longer run of separators means a newer version, `1..0` is newer than `1.0`
+        let (sep1, sep2) = (i - start1, j - start2);
+        if sep1 != sep2 {
+            return sep1.cmp(&sep2);
+        }
+
+        let is_num = one[i].is_ascii_digit();
+        let segment = |s: &[u8], mut k: usize| {
+            let begin = k;
+            while k < s.len()
+                && (if is_num {
+                    s[k].is_ascii_digit()
+                } else {
+                    s[k].is_ascii_alphabetic()
+                })
+            {
+                k += 1;
+            }
+            (begin, k)
+        };
+        let (begin1, end1) = segment(one, i);
+        let (begin2, end2) = segment(two, j);
+
+        // segments of different types, numeric one is newer
+        if begin2 == end2 {
+            return if is_num {
+                Ordering::Greater
+            } else {
+                Ordering::Less
+            };
+        }
+
+        let (mut seg1, mut seg2) = (&one[begin1..end1], &two[begin2..end2]);
+        if is_num {
+            seg1 = trim_leading_zeros(seg1);
+            seg2 = trim_leading_zeros(seg2);
+            let by_len = seg1.len().cmp(&seg2.len());
+            if by_len != Ordering::Equal {
+                return by_len;
+            }
+        }
+
+        let by_content = seg1.cmp(seg2);
+        if by_content != Ordering::Equal {
+            return by_content;
+        }
+
+        i = end1;
+        j = end2;
+    }
+
+    let (rest1, rest2) = (one.get(i), two.get(j));
+    match (rest1, rest2) {
+        (None, None) => Ordering::Equal,
+        // we never want a remaining alpha string to beat an empty string
+        (None, Some(c)) if !c.is_ascii_alphabetic() => Ordering::Less,
+        (Some(c), _) if c.is_ascii_alphabetic() => Ordering::Less,
+        _ => Ordering::Greater,
+    }
+}
+
+fn trim_leading_zeros(s: &[u8]) -> &[u8] {
+    let zeros = s.iter().take_while(|&&c| c == b'0').count();
+    &s[zeros..]
+}
+
+#[test]
+fn test_vercmp() {
+    use Ordering::*;
+
+    let cases = [
+        ("1.0", "1.0", Equal),
+        ("1.0", "1.1", Less),
+        ("1.1", "1.0", Greater),
+        ("1.0a", "1.0", Less),
+        ("1.0", "1.0a", Greater),
+        ("1.0.1", "1.0", Greater),
+        ("1.01", "1.1", Equal),
+        ("1.0.1", "1.01", Less),
+        ("1..0", "1.0", Greater),
+        ("1.0alpha", "1.0beta", Less),
+        ("1.0rc1", "1.0", Less),
+        ("1.0", "1.0.a", Less),
+        ("1.5-1", "1.5-2", Less),
+        ("1.5-1", "1.5", Equal),
+        ("1:1.0-1", "2.0-1", Greater),
+        ("0:1.0-1", "1.0-1", Equal),
+        (":1.0", "1.0", Equal),
+        ("1.0_1", "1.0.1", Equal),
+        ("20220101", "2.0", Greater),
+        ("1.2.3-1", "1.2.3-1.1", Less),
+    ];
+
+    for (a, b, expect) in cases {
+        assert_eq!(vercmp(a, b), expect, "vercmp({a}, {b})");
+    }
+}
@@ -0,0 +1,255 @@
+use crate::{config::PatchesConfig, types::check_pkgname};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+type BoxStr = Box<str>;
+
+/// Name of the patch file inside each package directory of the archriscv-packages repository
+pub const PATCH_FILE: &str = "riscv64.patch";
+
+/// A riscv64 patch found in the local archriscv-packages clone
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PatchInfo {
+    pub path: PathBuf,
+    /// Date of the last commit touching the patch, in `YYYY-MM-DD` form
+    pub last_commit: Option<BoxStr>,
+}
+
+/// Find every `<pkgname>/riscv64.patch` in the repository and the date of its last commit, keyed
+/// by pkgname.
+pub fn scan(repo: &Path) -> Result<HashMap<BoxStr, PatchInfo>> {
+    let entries = std::fs::read_dir(repo)
+        .with_context(|| format!("fail to read archriscv-packages at {}", repo.display()))?;
+
+    let mut patches = HashMap::new();
+    for entry in entries {
+        let entry = entry?;
+        let path = entry.path().join(PATCH_FILE);
+        if !path.is_file() {
+            continue;
+        }
+
+        let pkgname = entry.file_name().to_string_lossy().into();
+        patches.insert(
+            pkgname,
+            PatchInfo {
+                path,
+                last_commit: None,
+            },
+        );
+    }
+
+    // A missing git binary or a plain copy of the repository only lose the dates
+    if let Ok(dates) = last_commit_dates(repo) {
+        for (pkgname, date) in dates {
+            if let Some(patch) = patches.get_mut(&pkgname) {
+                patch.last_commit = Some(date);
+            }
+        }
+    }
+
+    Ok(patches)
+}
+
+/// Walk the git history once and return the newest commit date of each patch file
+fn last_commit_dates(repo: &Path) -> Result<HashMap<BoxStr, BoxStr>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(["log", "--format=%x00%cs", "--name-only", "--"])
+        .arg(format!("*/{PATCH_FILE}"))
+        .output()
+        .with_context(|| "fail to execute git")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "git log exit with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    Ok(parse_git_log(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Parse the `git log --format=%x00%cs --name-only` output. Commits are listed newest first, so
+/// only the first date seen for a package is kept.
+fn parse_git_log(log: &str) -> HashMap<BoxStr, BoxStr> {
+    let mut dates: HashMap<BoxStr, BoxStr> = HashMap::new();
+    let mut current_date = "";
+
+    for line in log.lines() {
+        if let Some(date) = line.strip_prefix('\0') {
+            current_date = date.trim();
+            continue;
+        }
+
+        let pkgname = match line.trim().strip_suffix(PATCH_FILE) {
+            Some(dir) => dir.trim_end_matches('/'),
+            None => continue,
+        };
+        if pkgname.is_empty() || pkgname.contains('/') {
+            continue;
+        }
+
+        dates
+            .entry(pkgname.into())
+            .or_insert_with(|| current_date.into());
+    }
+
+    dates
+}
+
+/// Prepare a workspace to fix `pkgname`, let the user edit the upstream PKGBUILD with `$EDITOR`,
+/// then write the changes into `<repo>/<pkgname>/riscv64.patch`. Git and the editor talk to the
+/// user directly, so this must be called while the TUI has released the terminal.
+///
+/// Return the path of the written patch, or `None` when the PKGBUILD was left untouched.
+pub fn scaffold(config: &PatchesConfig, pkgname: &str) -> Result<Option<PathBuf>> {
+    check_pkgname(pkgname)?;
+    let repo = config
+        .repo
+        .as_ref()
+        .with_context(|| "archriscv-packages repository is not configured")?;
+    let patch_dir = repo.join(pkgname);
+    let workdir = checkout_upstream(config, pkgname)?;
+
+    // continue from the existing patch instead of starting over
+    let patch = patch_dir.join(PATCH_FILE);
+    if patch.is_file() {
+        run(Command::new("patch")
+            .current_dir(&workdir)
+            .arg("-Np0")
+            .arg("-i")
+            .arg(&patch))?;
+    }
+
+    let editor = std::env::var("EDITOR").unwrap_or_else(|_| "vi".to_string());
+    run(Command::new("sh")
+        .arg("-c")
+        .arg(format!("{editor} \"$1\""))
+        .arg("sh")
+        .arg(workdir.join("PKGBUILD")))?;
+
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(&workdir)
+        .args(["diff", "--no-prefix", "--no-color"])
+        .output()
+        .with_context(|| "fail to execute git")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "git diff exit with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let diff = strip_git_headers(&String::from_utf8_lossy(&output.stdout));
+    if diff.trim().is_empty() {
+        return Ok(None);
+    }
+
+    // the package directory is only created once there is a patch to keep in it
+    std::fs::create_dir_all(&patch_dir)
+        .with_context(|| format!("fail to create {}", patch_dir.display()))?;
+    std::fs::write(&patch, diff).with_context(|| format!("fail to write {}", patch.display()))?;
+    Ok(Some(patch))
+}
+
+/// Clone the upstream packaging repository into the scratch directory, or reset the existing
+/// clone. Changes from a previous session are already saved in the riscv64 patch.
+fn checkout_upstream(config: &PatchesConfig, pkgname: &str) -> Result<PathBuf> {
+    let workdir = config.scratch_dir.join(pkgname);
+
+    if workdir.join(".git").is_dir() {
+        run(Command::new("git")
+            .arg("-C")
+            .arg(&workdir)
+            .args(["checkout", "--", "."]))?;
+    } else {
+        std::fs::create_dir_all(&config.scratch_dir)
+            .with_context(|| format!("fail to create {}", config.scratch_dir.display()))?;
+        let url = config.upstream_url.replace("{pkgname}", pkgname);
+        let existed = workdir.exists();
+        let cloned = run(Command::new("git")
+            .args(["clone", "--depth", "1", &url])
+            .arg(&workdir));
+        // a directory left by a failed clone would make the next attempt fail too, but one that
+        // was there before belongs to the user
+        if cloned.is_err() && !existed && workdir.exists() {
+            let _ = std::fs::remove_dir_all(&workdir);
+        }
+        cloned?;
+    }
+
+    Ok(workdir)
+}
+
+fn run(command: &mut Command) -> Result<()> {
+    let status = command
+        .status()
+        .with_context(|| format!("fail to execute {command:?}"))?;
+    if !status.success() {
+        anyhow::bail!("{command:?} exit with {status}");
+    }
+    Ok(())
+}
+
+/// Drop the git specific lines from `git diff` output, leaving a plain unified diff like the
+/// other patches in archriscv-packages.
+fn strip_git_headers(diff: &str) -> String {
+    diff.lines()
+        .filter(|line| !line.starts_with("diff --git ") && !line.starts_with("index "))
+        .map(|line| format!("{line}\n"))
+        .collect()
+}
+
+#[test]
+fn test_strip_git_headers() {
+    let diff = "diff --git PKGBUILD PKGBUILD\nindex 1234567..89abcde 100644\n--- PKGBUILD\n\
+                +++ PKGBUILD\n@@ -1 +1 @@\n-arch=(x86_64)\n+arch=(x86_64 riscv64)\n";
+    assert_eq!(
+        strip_git_headers(diff),
+        "--- PKGBUILD\n+++ PKGBUILD\n@@ -1 +1 @@\n-arch=(x86_64)\n+arch=(x86_64 riscv64)\n"
+    );
+}
+
+#[test]
+fn test_failed_clone() {
+    let dir = std::env::temp_dir().join(format!("lzr-test-scaffold-{}", std::process::id()));
+    let config = PatchesConfig {
+        repo: Some(dir.join("archriscv-packages")),
+        scratch_dir: dir.join("scratch"),
+        upstream_url: dir.join("missing/{pkgname}").display().to_string(),
+    };
+    // not a clone, so git refuses to clone into it
+    std::fs::create_dir_all(dir.join("scratch/bear")).unwrap();
+    std::fs::write(dir.join("scratch/bear/notes"), "keep me").unwrap();
+
+    assert!(scaffold(&config, "bear").is_err());
+    assert!(dir.join("scratch/bear/notes").is_file());
+    assert!(!dir.join("archriscv-packages/bear").exists());
+
+    assert!(scaffold(&config, "zlib").is_err());
+    assert!(!dir.join("scratch/zlib").exists());
+
+    assert!(scaffold(&config, "..").is_err());
+    assert!(dir.join("scratch").is_dir());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_parse_git_log() {
+    let log = "\x002022-09-10\n\nbash/riscv64.patch\nzlib/riscv64.patch\n\
+               \x002022-08-01\n\nbash/riscv64.patch\nnested/dir/riscv64.patch\n";
+    let dates = parse_git_log(log);
+
+    assert_eq!(dates.len(), 2);
+    assert_eq!(dates["bash"].as_ref(), "2022-09-10");
+    assert_eq!(dates["zlib"].as_ref(), "2022-09-10");
+}
@@ -1,71 +1,354 @@
 use anyhow::{Context, Result};
-use scraper::{Html, Selector};
+use scraper::{ElementRef, Html, Selector};
+use serde::{Deserialize, Serialize};
 
-const END_POINT: &str = "https://archriscv.felixc.at/.status/status.htm";
+pub(crate) const END_POINT: &str = "https://archriscv.felixc.at/.status/status.htm";
 
 type BoxStr = Box<str>;
 
-#[derive(Debug)]
+/// A FTBFS or leaf package of the status page
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct PackageStatus {
     pub repo: BoxStr,
     pub pkgname: BoxStr,
     pub status: BoxStr,
+    /// Absolute URL of the build log linked from the status cell
+    pub log: Option<BoxStr>,
 }
 
-impl PackageStatus {
-    fn from(html_elems: &[&str]) -> PackageStatus {
-        if html_elems.len() < 3 {
-            panic!("unexpected html elems length, got elem: {:?}", html_elems)
+/// A column of the status page
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Column {
+    Repo,
+    Package,
+    Status,
+}
+
+impl std::fmt::Display for Column {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Column::Repo => "repo",
+            Column::Package => "package",
+            Column::Status => "status",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A row of the status page that can't be read as a package. `row` counts the table rows from
+/// 1 and `text` is the text of the whole row.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ParseError {
+    /// The row has fewer cells than the column needs
+    MissingCell {
+        row: usize,
+        column: Column,
+        text: String,
+    },
+    EmptyCell {
+        row: usize,
+        column: Column,
+        text: String,
+    },
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::MissingCell { row, column, text } => {
+                write!(f, "row {row} \"{text}\": no {column} cell")
+            }
+            ParseError::EmptyCell { row, column, text } => {
+                write!(f, "row {row} \"{text}\": empty {column} cell")
+            }
         }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// The packages of the status page, and the rows skipped because they couldn't be read
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct StatusPage {
+    pub packages: Vec<PackageStatus>,
+    pub warnings: Vec<ParseError>,
+}
+
+/// Position of the cells of each column in a row
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Columns {
+    repo: usize,
+    pkgname: usize,
+    status: usize,
+}
 
+impl Default for Columns {
+    fn default() -> Self {
         Self {
-            repo: html_elems[0].into(),
-            pkgname: html_elems[1].into(),
-            status: html_elems[2].into(),
+            repo: 0,
+            pkgname: 1,
+            status: 2,
         }
     }
+}
+
+impl Columns {
+    /// Find the columns by their header, `None` when one of them is missing
+    fn from_header(headers: &[String]) -> Option<Self> {
+        let find = |names: &[&str]| {
+            headers.iter().position(|header| {
+                let header = header.to_lowercase();
+                names.iter().any(|name| header.contains(name))
+            })
+        };
+        Some(Self {
+            repo: find(&["repo"])?,
+            pkgname: find(&["package", "pkgname", "name"])?,
+            status: find(&["status"])?,
+        })
+    }
+}
+
+/// Text of an element with whitespace runs collapsed
+fn text(element: ElementRef) -> String {
+    element
+        .text()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
 
-    pub async fn download() -> Result<Vec<Self>> {
-        let client = reqwest::Client::builder()
-            .timeout(std::time::Duration::from_secs(30))
-            .build()
-            .expect("Fail to create HTTP client to download status from felixc.at");
+/// Text directly inside a cell, leaving out its links, or all of its text when it only has links
+fn own_text(cell: ElementRef) -> String {
+    let own = cell
+        .children()
+        .filter_map(|node| node.value().as_text())
+        .map(|text| text.trim())
+        .filter(|text| !text.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    if own.is_empty() {
+        text(cell)
+    } else {
+        own
+    }
+}
 
+impl PackageStatus {
+    /// Download and parse the status page at `url`
+    pub async fn download_from(client: &reqwest::Client, url: &str) -> Result<StatusPage> {
         let response = client
-            .get(END_POINT)
+            .get(url)
             .send()
             .await
-            .with_context(|| "fail to download status from felixc.at")?
+            .and_then(reqwest::Response::error_for_status)
+            .with_context(|| format!("fail to download status from {url}"))?
             .text()
             .await
             .with_context(|| "fail to parse response into UTF-8 string")?;
 
-        let fragment = Html::parse_fragment(&response);
-        let selector = Selector::parse("tr").expect("invalid selector");
-        let selected = fragment.select(&selector);
+        let base = reqwest::Url::parse(url).with_context(|| format!("invalid url {url}"))?;
+        Ok(Self::parse(&response, &base))
+    }
 
-        let parsed = selected
-            .into_iter()
-            .filter(|elem| {
-                // remove those package which is neither FTBFS or leaf
-                let parts = elem.text().collect::<Vec<_>>();
-                let status = parts[2];
-                let is_ftbfs_pkg = status.contains("FTBFS");
-                let is_leaf_pkg = status.contains("Leaf package");
+    /// Parse the FTBFS and leaf packages of the status page, resolving log links against `base`.
+    /// Cells are taken by position, found from the header row when there is one, and extra
+    /// columns are ignored.
+    fn parse(html: &str, base: &reqwest::Url) -> StatusPage {
+        let fragment = Html::parse_fragment(html);
+        let row_selector = Selector::parse("tr").expect("invalid selector");
+        let link_selector = Selector::parse("a").expect("invalid selector");
 
-                is_ftbfs_pkg || is_leaf_pkg
-            })
-            .map(|element| {
-                let raw_str = element.text().collect::<Vec<_>>();
-                Self::from(&raw_str)
-            })
-            .collect();
+        let mut page = StatusPage::default();
+        let mut columns = Columns::default();
+        for (i, row) in fragment.select(&row_selector).enumerate() {
+            let cells = row
+                .children()
+                .filter_map(ElementRef::wrap)
+                .filter(|cell| matches!(cell.value().name(), "td" | "th"))
+                .collect::<Vec<_>>();
+            if cells.is_empty() {
+                continue;
+            }
+            if cells.iter().all(|cell| cell.value().name() == "th") {
+                let headers = cells.into_iter().map(text).collect::<Vec<_>>();
+                columns = Columns::from_header(&headers).unwrap_or(columns);
+                continue;
+            }
+
+            match Self::parse_row(i + 1, row, &cells, columns) {
+                Ok(Some(mut status)) => {
+                    status.log = row
+                        .select(&link_selector)
+                        .filter_map(|link| link.value().attr("href"))
+                        .find(|href| href.contains("log"))
+                        .and_then(|href| base.join(href).ok())
+                        .map(|url| url.as_str().into());
+                    page.packages.push(status);
+                }
+                Ok(None) => (),
+                Err(err) => page.warnings.push(err),
+            }
+        }
 
-        Ok(parsed)
+        page
     }
+
+    /// Read a row, `None` when the package is neither FTBFS nor a leaf package
+    fn parse_row(
+        row_number: usize,
+        row: ElementRef,
+        cells: &[ElementRef],
+        columns: Columns,
+    ) -> Result<Option<Self>, ParseError> {
+        let cell = |index: usize, column: Column| {
+            let content = match cells.get(index) {
+                Some(cell) => own_text(*cell),
+                None => {
+                    return Err(ParseError::MissingCell {
+                        row: row_number,
+                        column,
+                        text: text(row),
+                    })
+                }
+            };
+            if content.is_empty() {
+                return Err(ParseError::EmptyCell {
+                    row: row_number,
+                    column,
+                    text: text(row),
+                });
+            }
+            Ok(content)
+        };
+
+        let status = cell(columns.status, Column::Status)?;
+        if !status.contains("FTBFS") && !status.contains("Leaf package") {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            repo: cell(columns.repo, Column::Repo)?.into(),
+            pkgname: cell(columns.pkgname, Column::Package)?.into(),
+            status: status.into(),
+            log: None,
+        }))
+    }
+}
+
+#[cfg(test)]
+const FIXTURE: &str = include_str!("../../tests/fixtures/felix_status.htm");
+
+#[test]
+fn test_parser() {
+    let base = reqwest::Url::parse(END_POINT).unwrap();
+    let page = PackageStatus::parse(FIXTURE, &base);
+    assert!(page.warnings.is_empty());
+    let parsed = page.packages;
+
+    let names = parsed
+        .iter()
+        .map(|p| p.pkgname.as_ref())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["bear", "ghc", "zsh-theme-powerlevel10k"]);
+    assert_eq!(parsed[0].repo.as_ref(), "extra");
+    assert_eq!(parsed[0].status.as_ref(), "FTBFS");
+    assert_eq!(
+        parsed[0].log.as_deref(),
+        Some("https://archriscv.felixc.at/.status/logs/bear/bear-3.0.20-1.log")
+    );
+    // the issue link is not mistaken for the log
+    assert_eq!(
+        parsed[1].log.as_deref(),
+        Some("https://archriscv.felixc.at/.status/logs/ghc/ghc-9.0.2-3.log")
+    );
+    assert!(parsed[2].log.is_none());
+}
+
+#[test]
+fn test_parse_malformed_rows() {
+    let html = r#"<table>
+<tr><th>Status</th><th>Package</th><th>Notes</th><th>Repo</th></tr>
+<tr><td>FTBFS <a href="logs/bear.log">log</a></td><td>bear</td><td>x</td><td>extra</td><td>new</td></tr>
+<tr><td>FTBFS</td><td>ghc</td></tr>
+<tr><td>FTBFS</td><td> </td><td></td><td>extra</td></tr>
+<tr><td>Built</td></tr>
+<tr></tr>
+</table>"#;
+    let base = reqwest::Url::parse(END_POINT).unwrap();
+    let page = PackageStatus::parse(html, &base);
+
+    assert_eq!(page.packages.len(), 1);
+    assert_eq!(page.packages[0].pkgname.as_ref(), "bear");
+    assert_eq!(page.packages[0].repo.as_ref(), "extra");
+    assert!(page.packages[0].log.is_some());
+    assert_eq!(
+        page.warnings,
+        [
+            ParseError::MissingCell {
+                row: 3,
+                column: Column::Repo,
+                text: "FTBFS ghc".to_string()
+            },
+            ParseError::EmptyCell {
+                row: 4,
+                column: Column::Package,
+                text: "FTBFS extra".to_string()
+            },
+        ]
+    );
+    assert_eq!(
+        page.warnings[0].to_string(),
+        "row 3 \"FTBFS ghc\": no repo cell"
+    );
+}
+
+#[tokio::test]
+async fn test_download() {
+    use super::mock::{MockResponse, MockServer};
+
+    let server = MockServer::start(MockResponse::ok(FIXTURE));
+    let url = server.url("/.status/status.htm");
+    let parsed = PackageStatus::download_from(&reqwest::Client::new(), &url)
+        .await
+        .unwrap()
+        .packages;
+    assert_eq!(parsed.len(), 3);
+    assert_eq!(
+        parsed[0].log.as_deref(),
+        Some(server.url("/.status/logs/bear/bear-3.0.20-1.log").as_str())
+    );
+
+    let server = MockServer::start(MockResponse::status(503));
+    let err = PackageStatus::download_from(&reqwest::Client::new(), &server.url("/"))
+        .await
+        .unwrap_err();
+    assert!(format!("{err:#}").contains("503"), "{err:#}");
+
+    // an unrelated page has no package
+    let server = MockServer::start(MockResponse::ok("<html>maintenance</html>"));
+    let page = PackageStatus::download_from(&reqwest::Client::new(), &server.url("/"))
+        .await
+        .unwrap();
+    assert!(page.packages.is_empty() && page.warnings.is_empty());
 }
 
 #[tokio::test]
-async fn test_parser() {
-    println!("{:?}", PackageStatus::download().await.unwrap())
+async fn test_download_timeout() {
+    use super::mock::{MockResponse, MockServer};
+    use std::time::Duration;
+
+    let server = MockServer::start(MockResponse::ok(FIXTURE).delayed(Duration::from_secs(2)));
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_millis(100))
+        .build()
+        .unwrap();
+    let err = PackageStatus::download_from(&client, &server.url("/"))
+        .await
+        .unwrap_err();
+    let err = err.downcast_ref::<reqwest::Error>().unwrap();
+    assert!(err.is_timeout());
 }
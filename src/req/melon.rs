@@ -2,23 +2,26 @@
 use crate::types::MarkList;
 use std::collections::HashMap;
 
-use serde::{Deserialize, Deserializer};
+use serde::{Deserialize, Deserializer, Serialize};
 
-pub async fn fetch() -> anyhow::Result<Response> {
-    let endpoint = "https://plct-arv.de2670dd.top/pkg";
-    let client = reqwest::Client::new();
+pub(crate) const END_POINT: &str = "https://plct-arv.de2670dd.top/pkg";
+
+/// Fetch and parse the work and mark lists at `endpoint`
+pub async fn fetch_from(client: &reqwest::Client, endpoint: &str) -> anyhow::Result<Response> {
     let response = client
         .get(endpoint)
         .header("user-agent", "PLCT::ArchRV.StatusWorker")
         .send()
-        .await?;
+        .await?
+        .error_for_status()?;
     let response = response.bytes().await?;
     let response: Response = serde_json::from_slice(&response)?;
 
     Ok(response)
 }
 
-#[derive(Deserialize)]
+/// Work and mark lists of the melon bot
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Response {
     #[serde(rename = "workList")]
     pub worklist: Vec<WorkList>,
@@ -26,8 +29,80 @@ pub struct Response {
     pub marklist: Vec<MarkList>,
 }
 
-#[derive(Deserialize)]
+/// Packages claimed by a packager
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WorkList {
     pub alias: Box<str>,
     pub packages: Vec<Box<str>>,
 }
+
+#[cfg(test)]
+const FIXTURE: &str = include_str!("../../tests/fixtures/melon_pkg.json");
+
+#[test]
+fn test_parse_response() {
+    let response: Response = serde_json::from_str(FIXTURE).unwrap();
+    assert_eq!(response.worklist[0].alias.as_ref(), "Moody");
+    assert_eq!(response.worklist[0].packages.len(), 2);
+    assert!(response.worklist[1].packages.is_empty());
+    assert_eq!(response.marklist[1].name.as_ref(), "ghc");
+    assert_eq!(response.marklist[1].marks[0].name.as_ref(), "upstreamable");
+}
+
+#[tokio::test]
+async fn test_fetch() {
+    use super::mock::{MockResponse, MockServer};
+
+    let server = MockServer::start(MockResponse::ok(FIXTURE));
+    let response = fetch_from(&reqwest::Client::new(), &server.url("/pkg"))
+        .await
+        .unwrap();
+    assert_eq!(response.marklist.len(), 2);
+    let request = &server.requests()[0];
+    assert!(request.starts_with("GET /pkg "));
+    assert!(request
+        .to_lowercase()
+        .contains("user-agent: plct::archrv.statusworker"));
+
+    let server = MockServer::start(MockResponse::status(404));
+    let err = fetch_from(&reqwest::Client::new(), &server.url("/pkg"))
+        .await
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("404"), "{err}");
+}
+
+#[tokio::test]
+async fn test_fetch_malformed() {
+    use super::mock::{MockResponse, MockServer};
+
+    let bodies = [
+        "",
+        "<html>502 Bad Gateway</html>",
+        r#"{"workList": []}"#,
+        r#"{"workList": [], "markList": [{"name": "bear", "marks": [{"name": "failing", "by": {}, "comment": ""}]}]}"#,
+    ];
+    for body in bodies {
+        let server = MockServer::start(MockResponse::ok(body));
+        let result = fetch_from(&reqwest::Client::new(), &server.url("/pkg")).await;
+        let err = result.err().unwrap();
+        assert!(err.is::<serde_json::Error>(), "{body}: {err}");
+    }
+}
+
+#[tokio::test]
+async fn test_fetch_timeout() {
+    use super::mock::{MockResponse, MockServer};
+    use std::time::Duration;
+
+    let server = MockServer::start(MockResponse::ok(FIXTURE).delayed(Duration::from_secs(2)));
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_millis(100))
+        .build()
+        .unwrap();
+    let err = fetch_from(&client, &server.url("/pkg"))
+        .await
+        .err()
+        .unwrap();
+    assert!(err.downcast_ref::<reqwest::Error>().unwrap().is_timeout());
+}
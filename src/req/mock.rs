@@ -0,0 +1,98 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// The canned reply of a [`MockServer`]
+#[derive(Clone)]
+pub struct MockResponse {
+    pub status: u16,
+    pub body: Vec<u8>,
+    /// Wait before replying, to trigger client timeouts
+    pub delay: Duration,
+}
+
+impl MockResponse {
+    pub fn ok(body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status: 200,
+            body: body.into(),
+            delay: Duration::ZERO,
+        }
+    }
+
+    pub fn status(status: u16) -> Self {
+        Self {
+            status,
+            ..Self::ok("")
+        }
+    }
+
+    pub fn delayed(self, delay: Duration) -> Self {
+        Self { delay, ..self }
+    }
+}
+
+/// A local HTTP stand-in to test the clients without network. It serves the same response to
+/// every request until the test process exits.
+pub struct MockServer {
+    addr: SocketAddr,
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl MockServer {
+    pub fn start(response: MockResponse) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("fail to bind mock server");
+        let addr = listener.local_addr().unwrap();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        let recorded = requests.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = match stream {
+                    Ok(stream) => stream,
+                    Err(_) => continue,
+                };
+                let response = response.clone();
+                let recorded = recorded.clone();
+                std::thread::spawn(move || {
+                    // keep the request line and headers, the clients never send a body
+                    let mut head = String::new();
+                    let mut reader = BufReader::new(&stream);
+                    loop {
+                        let mut line = String::new();
+                        match reader.read_line(&mut line) {
+                            Ok(0) | Err(_) => return,
+                            Ok(_) if line == "\r\n" => break,
+                            Ok(_) => head.push_str(&line),
+                        }
+                    }
+                    recorded.lock().unwrap().push(head);
+
+                    std::thread::sleep(response.delay);
+                    let header = format!(
+                        "HTTP/1.1 {} Mock\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                        response.status,
+                        response.body.len()
+                    );
+                    let _ = stream
+                        .write_all(header.as_bytes())
+                        .and_then(|_| stream.write_all(&response.body));
+                });
+            }
+        });
+
+        Self { addr, requests }
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("http://{}{path}", self.addr)
+    }
+
+    /// Request line and headers of each request received so far
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+}
@@ -1,2 +1,4 @@
 pub mod felix;
 pub mod melon;
+#[cfg(test)]
+pub(crate) mod mock;
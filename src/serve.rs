@@ -0,0 +1,404 @@
+use crate::{app::App, client::ParseError, component::packages::PkgInfo, config::Config, metrics};
+use anyhow::Context;
+use hyper::{
+    header,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    fmt::Write,
+    net::SocketAddr,
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Data served until the next refresh
+#[derive(Debug, Default)]
+struct Snapshot {
+    packages: Vec<PkgInfo>,
+    warnings: Vec<ParseError>,
+    /// Unix timestamp of the last successful refresh
+    updated_at: Option<u64>,
+    /// Why the last refresh failed, the previous data is still served
+    error: Option<String>,
+    /// Time of the last successful fetch of each source, updated even by failed refreshes
+    last_fetch: HashMap<&'static str, SystemTime>,
+}
+
+type State = Arc<RwLock<Snapshot>>;
+
+#[derive(Serialize)]
+struct Status<'a> {
+    updated_at: Option<u64>,
+    error: Option<&'a str>,
+    packages: usize,
+    warnings: usize,
+}
+
+/// Package filters taken from the query string, shared by the JSON API and the HTML page
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Filter {
+    /// Part of the package name
+    name: String,
+    /// Part of the status, like `FTBFS`
+    status: String,
+    mark: String,
+    assignee: String,
+    outdated: bool,
+}
+
+impl Filter {
+    fn parse(query: &str) -> Self {
+        let mut filter = Self::default();
+        for (key, value) in form_urlencoded::parse(query.as_bytes()) {
+            let value = value.trim().to_string();
+            match key.as_ref() {
+                "q" => filter.name = value,
+                "status" => filter.status = value,
+                "mark" => filter.mark = value,
+                "assignee" => filter.assignee = value,
+                "outdated" => filter.outdated = matches!(value.as_str(), "1" | "true" | "on"),
+                _ => (),
+            }
+        }
+        filter
+    }
+
+    fn matches(&self, pkg: &PkgInfo) -> bool {
+        let contains = |text: &str, part: &str| text.to_lowercase().contains(&part.to_lowercase());
+        contains(pkg.name(), &self.name)
+            && contains(pkg.current_process(), &self.status)
+            && (self.mark.is_empty() || pkg.marks().contains(&self.mark.as_str()))
+            && (self.assignee.is_empty() || pkg.assignee() == self.assignee)
+            && (!self.outdated || pkg.is_outdated())
+    }
+}
+
+/// Serve the package table over HTTP on `listen`, refreshing it every
+/// `config.serve.refresh_interval` seconds
+pub async fn serve(config: Config, listen: SocketAddr) -> anyhow::Result<()> {
+    let interval = Duration::from_secs(config.serve.refresh_interval.max(1));
+    let state = State::default();
+    spawn_refresh(App::new(config), state.clone(), interval);
+
+    let refresh = interval.as_secs();
+    let make_service = make_service_fn(move |_| {
+        let state = state.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let response = handle(&request, &state, refresh);
+                async move { Ok::<_, Infallible>(response) }
+            }))
+        }
+    });
+
+    let server = Server::try_bind(&listen)
+        .with_context(|| format!("fail to listen on {listen}"))?
+        .serve(make_service);
+    eprintln!("serving on http://{}", server.local_addr());
+    server.await.with_context(|| "server stopped")
+}
+
+/// Update the package data now and then every `interval`, keeping the previous data when an
+/// update fails
+fn spawn_refresh(mut app: App, state: State, interval: Duration) {
+    tokio::spawn(async move {
+        loop {
+            let result = app.update();
+            store(&state, &app, result);
+            tokio::time::sleep(interval).await;
+        }
+    });
+}
+
+fn store(state: &State, app: &App, result: anyhow::Result<()>) {
+    let mut snapshot = state.write().unwrap();
+    snapshot.last_fetch = app.last_fetch.clone();
+    match result {
+        Ok(()) => {
+            snapshot.packages = app.pkg_info_table.data.clone();
+            snapshot.packages.sort_by(|a, b| a.name.cmp(&b.name));
+            snapshot.warnings = app.status_warnings.clone();
+            snapshot.updated_at = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .ok()
+                .map(|now| now.as_secs());
+            snapshot.error = None;
+        }
+        Err(err) => snapshot.error = Some(format!("{err:#}")),
+    }
+}
+
+fn handle(request: &Request<Body>, state: &State, refresh: u64) -> Response<Body> {
+    if request.method() != Method::GET {
+        return text(StatusCode::METHOD_NOT_ALLOWED, "only GET is supported");
+    }
+    let snapshot = state.read().unwrap();
+    let filter = Filter::parse(request.uri().query().unwrap_or_default());
+    let filtered = || snapshot.packages.iter().filter(|pkg| filter.matches(pkg));
+
+    match request.uri().path() {
+        "/" => html(render_page(&snapshot, &filter, refresh)),
+        "/api/packages" => json(&filtered().collect::<Vec<_>>()),
+        "/api/warnings" => json(&snapshot.warnings),
+        "/metrics" => Response::builder()
+            .header(header::CONTENT_TYPE, "text/plain; version=0.0.4")
+            .body(Body::from(metrics::render(
+                &snapshot.packages,
+                snapshot.warnings.len(),
+                &snapshot.last_fetch,
+            )))
+            .unwrap(),
+        "/api/status" => json(&Status {
+            updated_at: snapshot.updated_at,
+            error: snapshot.error.as_deref(),
+            packages: snapshot.packages.len(),
+            warnings: snapshot.warnings.len(),
+        }),
+        path => match path.strip_prefix("/api/packages/") {
+            Some(name) => match snapshot.packages.iter().find(|pkg| pkg.name() == name) {
+                Some(pkg) => json(pkg),
+                None => text(StatusCode::NOT_FOUND, &format!("no package {name}")),
+            },
+            None => text(StatusCode::NOT_FOUND, "not found"),
+        },
+    }
+}
+
+fn text(status: StatusCode, body: &str) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "text/plain; charset=utf-8")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+fn html(body: String) -> Response<Body> {
+    Response::builder()
+        .header(header::CONTENT_TYPE, "text/html; charset=utf-8")
+        .body(Body::from(body))
+        .unwrap()
+}
+
+fn json<T: Serialize + ?Sized>(value: &T) -> Response<Body> {
+    match serde_json::to_vec(value) {
+        Ok(body) => Response::builder()
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body))
+            .unwrap(),
+        Err(err) => text(StatusCode::INTERNAL_SERVER_ERROR, &err.to_string()),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// The package table with a filter form, reloaded by the browser after each refresh
+fn render_page(snapshot: &Snapshot, filter: &Filter, refresh: u64) -> String {
+    let mut page = String::new();
+    let _ = write!(
+        page,
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta http-equiv="refresh" content="{refresh}">
+<title>Arch Linux RISC-V Packages Status</title>
+<style>
+body {{ font-family: sans-serif; }}
+table {{ border-collapse: collapse; }}
+th, td {{ padding: 2px 8px; text-align: left; }}
+tr:nth-child(even) {{ background: #f0f0f0; }}
+.error {{ color: #b00; }}
+</style>
+</head>
+<body>
+<h1>Arch Linux RISC-V Packages Status</h1>
+<form>
+<input name="q" placeholder="package" value="{}">
+<input name="status" placeholder="status" value="{}">
+<input name="mark" placeholder="mark" value="{}">
+<input name="assignee" placeholder="assignee" value="{}">
+<label><input type="checkbox" name="outdated"{}> outdated</label>
+<button>Filter</button>
+</form>
+"#,
+        escape(&filter.name),
+        escape(&filter.status),
+        escape(&filter.mark),
+        escape(&filter.assignee),
+        if filter.outdated { " checked" } else { "" },
+    );
+
+    match snapshot.updated_at {
+        Some(time) => {
+            let _ = writeln!(
+                page,
+                r#"<p>Updated at <span class="time">{time}</span></p>"#
+            );
+        }
+        None => page.push_str("<p>Fetching the package data...</p>\n"),
+    }
+    if let Some(ref error) = snapshot.error {
+        let _ = writeln!(
+            page,
+            r#"<p class="error">Last refresh failed: {}</p>"#,
+            escape(error)
+        );
+    }
+    if !snapshot.warnings.is_empty() {
+        let _ = writeln!(
+            page,
+            r#"<p class="error"><a href="/api/warnings">{} rows</a> of the status page couldn't be read</p>"#,
+            snapshot.warnings.len()
+        );
+    }
+
+    page.push_str(
+        "<table>\n<tr><th>Pkgname</th><th>Status</th><th>Reason</th><th>Local</th>\
+         <th>Assignee</th><th>Marks</th><th>Blocks</th><th>Outdated</th><th>Patch</th></tr>\n",
+    );
+    for pkg in snapshot.packages.iter().filter(|pkg| filter.matches(pkg)) {
+        let status = match pkg.log_url {
+            Some(ref url) => format!(
+                r#"{} <a href="{}">log</a>"#,
+                escape(pkg.current_process()),
+                escape(url)
+            ),
+            None => escape(pkg.current_process()),
+        };
+        let _ = writeln!(
+            page,
+            "<tr><td>{}</td><td>{status}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td>\
+             <td>{}</td><td>{}</td><td>{}</td></tr>",
+            escape(pkg.name()),
+            escape(&pkg.reason()),
+            escape(&pkg.local_build()),
+            escape(pkg.assignee()),
+            escape(&pkg.marks().join(" ")),
+            pkg.blocks(),
+            escape(&pkg.outdated()),
+            escape(pkg.patch_status()),
+        );
+    }
+    page.push_str("</table>\n</body>\n</html>\n");
+    page
+}
+
+#[cfg(test)]
+fn test_state() -> State {
+    use crate::{component::packages::PkgInfoBuilder, types::Mark};
+
+    let packages = vec![
+        PkgInfoBuilder::default()
+            .name("bear".into())
+            .process("FTBFS".into())
+            .assignee("Moody".into())
+            .marks(vec![Mark {
+                name: "noqemu".into(),
+                by: "Moody".into(),
+                comment: "".into(),
+            }])
+            .log_url("https://example.org/bear.log?a=1&b=2".into())
+            .build()
+            .unwrap(),
+        PkgInfoBuilder::default()
+            .name("<script>".into())
+            .process("Leaf package".into())
+            .build()
+            .unwrap(),
+    ];
+    Arc::new(RwLock::new(Snapshot {
+        packages,
+        updated_at: Some(1662813005),
+        ..Snapshot::default()
+    }))
+}
+
+#[cfg(test)]
+async fn get(state: &State, uri: &str) -> (StatusCode, String) {
+    let request = Request::get(uri).body(Body::empty()).unwrap();
+    let response = handle(&request, state, 600);
+    let status = response.status();
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[test]
+fn test_filter() {
+    let filter = Filter::parse("q=Be&status=&mark=noqemu&outdated=on&x=1");
+    assert_eq!(
+        filter,
+        Filter {
+            name: "Be".to_string(),
+            mark: "noqemu".to_string(),
+            outdated: true,
+            ..Filter::default()
+        }
+    );
+
+    let state = test_state();
+    let packages = &state.read().unwrap().packages;
+    assert!(Filter::parse("q=BEA&status=ftbfs&assignee=Moody").matches(&packages[0]));
+    assert!(!Filter::parse("mark=noqemu").matches(&packages[1]));
+    assert!(!Filter::parse("outdated=1").matches(&packages[0]));
+    assert!(Filter::parse("").matches(&packages[1]));
+}
+
+#[tokio::test]
+async fn test_api() {
+    let state = test_state();
+
+    let (status, body) = get(&state, "/api/packages?mark=noqemu").await;
+    assert_eq!(status, StatusCode::OK);
+    let packages: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(packages.as_array().unwrap().len(), 1);
+    assert_eq!(packages[0]["name"], "bear");
+    assert_eq!(packages[0]["marks"][0]["by"]["alias"], "Moody");
+
+    let (status, body) = get(&state, "/api/packages/bear").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains(r#""assignee":"Moody""#));
+    let (status, _) = get(&state, "/api/packages/ghc").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, body) = get(&state, "/metrics").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains(r#"lzr_marked_packages{mark="noqemu"} 1"#));
+    assert!(body.contains("lzr_leaf_packages 1"));
+
+    let (_, body) = get(&state, "/api/status").await;
+    assert_eq!(
+        body,
+        r#"{"updated_at":1662813005,"error":null,"packages":2,"warnings":0}"#
+    );
+
+    let request = Request::post("/api/packages").body(Body::empty()).unwrap();
+    assert_eq!(
+        handle(&request, &state, 600).status(),
+        StatusCode::METHOD_NOT_ALLOWED
+    );
+}
+
+#[tokio::test]
+async fn test_page() {
+    let state = test_state();
+
+    let (status, body) = get(&state, "/?status=FTBFS").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("<td>bear</td>"));
+    assert!(body.contains(r#"<a href="https://example.org/bear.log?a=1&amp;b=2">log</a>"#));
+    assert!(body.contains(r#"<input name="status" placeholder="status" value="FTBFS">"#));
+    assert!(!body.contains("script"));
+
+    let (_, body) = get(&state, "/").await;
+    assert!(body.contains("<td>&lt;script&gt;</td>"));
+    assert!(body.contains(r#"<meta http-equiv="refresh" content="600">"#));
+}
@@ -1,29 +1,72 @@
-use serde::{Deserialize, Deserializer};
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
 use std::collections::HashMap;
 
-#[derive(Deserialize)]
+/// Marks of a package
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MarkList {
     pub name: Box<str>,
     pub marks: Vec<Mark>,
 }
 
-#[derive(Debug, Deserialize)]
+/// A mark like `noqemu` or `upstreamable` put on a package, with who put it
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Mark {
     pub name: Box<str>,
-    #[serde(deserialize_with = "flatten")]
+    #[serde(deserialize_with = "flatten", serialize_with = "unflatten")]
     pub by: Box<str>,
     pub comment: Box<str>,
 }
 
+/// Check that `name` is a valid Arch package name before it is joined into a path or a command.
+/// Names come from scraped pages and the melon API, so they are not trusted.
+pub fn check_pkgname(name: &str) -> anyhow::Result<()> {
+    let valid = !name.is_empty()
+        && !name.starts_with(['.', '-'])
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "@._+-".contains(c));
+    if !valid {
+        anyhow::bail!("invalid package name {name:?}");
+    }
+    Ok(())
+}
+
 fn flatten<'de, D>(d: D) -> Result<Box<str>, D::Error>
 where
     D: Deserializer<'de>,
 {
     let s: HashMap<String, String> = HashMap::deserialize(d)?;
 
-    let alias: Box<str> = s["alias"].as_str().into();
+    match s.get("alias") {
+        Some(alias) => Ok(alias.as_str().into()),
+        None => Err(D::Error::missing_field("alias")),
+    }
+}
 
-    Ok(alias)
+/// Write `by` back as the `{"alias": ...}` object it is read from
+fn unflatten<S>(alias: &str, s: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    HashMap::from([("alias", alias)]).serialize(s)
+}
+
+#[test]
+fn test_check_pkgname() {
+    for name in [
+        "bash",
+        "lib32-gcc-libs",
+        "gtk2+extra",
+        "python-foo.bar",
+        "a@b_c",
+    ] {
+        assert!(check_pkgname(name).is_ok(), "{name}");
+    }
+    for name in [
+        "", ".", "..", "../etc", "-rf", ".hidden", "a/b", "a b", "$(id)", "a;b",
+    ] {
+        assert!(check_pkgname(name).is_err(), "{name}");
+    }
 }
 
 #[test]
@@ -52,3 +95,10 @@ fn test_flatten() {
     let marker = &marklist.marks[1].by;
     assert_eq!(marker.as_ref(), "Moody");
 }
+
+#[test]
+fn test_unflatten() {
+    let raw = r#"{"name":"noqemu","by":{"alias":"Moody"},"comment":""}"#;
+    let mark: Mark = serde_json::from_str(raw).unwrap();
+    assert_eq!(serde_json::to_string(&mark).unwrap(), raw);
+}
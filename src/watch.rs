@@ -0,0 +1,415 @@
+use crate::{
+    app::App,
+    component::packages::PkgInfo,
+    config::{Config, Hook, WatchConfig},
+    types::Mark,
+};
+use anyhow::Context;
+use serde::Serialize;
+use std::{collections::HashMap, process::Stdio, time::Duration};
+use tokio::io::AsyncWriteExt;
+
+/// Names accepted by the `on` key of the hooks
+const EVENTS: [&str; 5] = [
+    "status_changed",
+    "new_ftbfs",
+    "assignee_changed",
+    "mark_added",
+    "mark_removed",
+];
+
+/// A hook still running after this long is killed so it can't stall the watch loop
+const HOOK_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// What changed on a package between two refreshes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub(crate) enum Change {
+    /// The status page shows another status, `None` when the package is not on it
+    StatusChanged {
+        old: Option<Box<str>>,
+        new: Option<Box<str>>,
+    },
+    /// The package fails to build and didn't before
+    NewFtbfs,
+    AssigneeChanged {
+        old: Option<Box<str>>,
+        new: Option<Box<str>>,
+    },
+    MarkAdded {
+        mark: Box<str>,
+        by: Box<str>,
+    },
+    MarkRemoved {
+        mark: Box<str>,
+    },
+}
+
+impl Change {
+    fn name(&self) -> &'static str {
+        match self {
+            Change::StatusChanged { .. } => "status_changed",
+            Change::NewFtbfs => "new_ftbfs",
+            Change::AssigneeChanged { .. } => "assignee_changed",
+            Change::MarkAdded { .. } => "mark_added",
+            Change::MarkRemoved { .. } => "mark_removed",
+        }
+    }
+}
+
+/// A change of a package, written as JSON to the stdin of the hooks
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub(crate) struct Event {
+    pub package: Box<str>,
+    pub repo: Option<Box<str>>,
+    /// Assignee after the change, or before it when the package left the table
+    pub assignee: Option<Box<str>>,
+    #[serde(flatten)]
+    pub change: Change,
+}
+
+impl std::fmt::Display for Event {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let or_none = |value: &Option<Box<str>>| value.as_deref().unwrap_or("none").to_string();
+        write!(f, "{}: ", self.package)?;
+        match self.change {
+            Change::StatusChanged { ref old, ref new } => {
+                write!(f, "status {} -> {}", or_none(old), or_none(new))
+            }
+            Change::NewFtbfs => write!(f, "new FTBFS"),
+            Change::AssigneeChanged { ref old, ref new } => {
+                write!(f, "assignee {} -> {}", or_none(old), or_none(new))
+            }
+            Change::MarkAdded { ref mark, ref by } => write!(f, "mark {mark} added by {by}"),
+            Change::MarkRemoved { ref mark } => write!(f, "mark {mark} removed"),
+        }
+    }
+}
+
+/// Compare two refreshes of the package table, the events are ordered by package name
+pub(crate) fn diff(old: &[PkgInfo], new: &[PkgInfo]) -> Vec<Event> {
+    let old = old
+        .iter()
+        .map(|pkg| (pkg.name(), pkg))
+        .collect::<HashMap<_, _>>();
+    let new = new
+        .iter()
+        .map(|pkg| (pkg.name(), pkg))
+        .collect::<HashMap<_, _>>();
+    let mut names = old.keys().chain(new.keys()).copied().collect::<Vec<_>>();
+    names.sort_unstable();
+    names.dedup();
+
+    let process = |pkg: Option<&PkgInfo>| pkg.and_then(|pkg| pkg.process.clone());
+    let assignee = |pkg: Option<&PkgInfo>| pkg.and_then(|pkg| pkg.assignee.clone());
+    let is_ftbfs = |pkg: Option<&PkgInfo>| pkg.is_some_and(PkgInfo::is_ftbfs);
+    fn marks(pkg: Option<&PkgInfo>) -> &[Mark] {
+        pkg.map(|pkg| pkg.marks.as_slice()).unwrap_or_default()
+    }
+
+    let mut events = Vec::new();
+    for name in names {
+        let before = old.get(name).copied();
+        let after = new.get(name).copied();
+        let latest = after.or(before).unwrap();
+        let mut push = |change| {
+            events.push(Event {
+                package: name.into(),
+                repo: latest.repo.clone(),
+                assignee: latest.assignee.clone(),
+                change,
+            })
+        };
+
+        if process(before) != process(after) {
+            push(Change::StatusChanged {
+                old: process(before),
+                new: process(after),
+            });
+        }
+        if is_ftbfs(after) && !is_ftbfs(before) {
+            push(Change::NewFtbfs);
+        }
+        if assignee(before) != assignee(after) {
+            push(Change::AssigneeChanged {
+                old: assignee(before),
+                new: assignee(after),
+            });
+        }
+        for mark in marks(after) {
+            if !marks(before).iter().any(|m| m.name == mark.name) {
+                push(Change::MarkAdded {
+                    mark: mark.name.clone(),
+                    by: mark.by.clone(),
+                });
+            }
+        }
+        for mark in marks(before) {
+            if !marks(after).iter().any(|m| m.name == mark.name) {
+                push(Change::MarkRemoved {
+                    mark: mark.name.clone(),
+                });
+            }
+        }
+    }
+    events
+}
+
+/// Reject the hooks that could never run, rather than silently ignoring them
+fn check_hooks(config: &WatchConfig) -> anyhow::Result<()> {
+    for hook in config.hooks.iter() {
+        if !EVENTS.contains(&hook.on.as_str()) {
+            anyhow::bail!(
+                "unknown event `{}` in watch hook, expected one of: {}",
+                hook.on,
+                EVENTS.join(", ")
+            );
+        }
+        if hook.mine && config.me.is_none() {
+            anyhow::bail!(
+                "watch hook on `{}` only runs for your packages but `watch.me` is not set",
+                hook.on
+            );
+        }
+    }
+    Ok(())
+}
+
+fn matches(hook: &Hook, event: &Event, me: Option<&str>) -> bool {
+    hook.on == event.change.name()
+        && (!hook.mine || me.is_some_and(|me| event.assignee.as_deref() == Some(me)))
+        && (hook.repo.is_none() || hook.repo.as_deref() == event.repo.as_deref())
+}
+
+/// Run the hook command with `sh -c`, passing the event as JSON on stdin and its name in
+/// `$LZR_EVENT`
+async fn run_hook(hook: &Hook, event: &Event) -> anyhow::Result<()> {
+    let mut json = serde_json::to_vec(event)?;
+    json.push(b'\n');
+
+    let mut child = tokio::process::Command::new("sh")
+        .arg("-c")
+        .arg(&hook.command)
+        .env("LZR_EVENT", event.change.name())
+        .stdin(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .with_context(|| format!("fail to execute `{}`", hook.command))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // the hook doesn't have to read its stdin
+        let _ = stdin.write_all(&json).await;
+    }
+
+    let status = tokio::time::timeout(HOOK_TIMEOUT, child.wait())
+        .await
+        .with_context(|| format!("`{}` timed out", hook.command))?
+        .with_context(|| format!("fail to wait for `{}`", hook.command))?;
+    if !status.success() {
+        anyhow::bail!("`{}` exited with {status}", hook.command);
+    }
+    Ok(())
+}
+
+/// Refresh the package data every `watch.interval` seconds and run the hooks on the changes.
+/// The first refresh only records the current state.
+pub async fn watch(config: Config) -> anyhow::Result<()> {
+    let watch = config.watch.clone();
+    check_hooks(&watch)?;
+    let interval = Duration::from_secs(watch.interval.max(1));
+
+    let mut app = App::new(config);
+    let mut previous: Option<Vec<PkgInfo>> = None;
+    loop {
+        match app.update() {
+            Ok(()) => {
+                let current = app.pkg_info_table.data.clone();
+                for event in previous
+                    .as_deref()
+                    .map_or(Vec::new(), |old| diff(old, &current))
+                {
+                    println!("{event}");
+                    for hook in watch.hooks.iter() {
+                        if !matches(hook, &event, watch.me.as_deref()) {
+                            continue;
+                        }
+                        if let Err(err) = run_hook(hook, &event).await {
+                            eprintln!("hook on {}: {err:#}", hook.on);
+                        }
+                    }
+                }
+                previous = Some(current);
+            }
+            Err(err) => eprintln!("fail to refresh: {err:#}"),
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+#[cfg(test)]
+fn test_hook(on: &str, command: &str) -> Hook {
+    Hook {
+        on: on.to_string(),
+        command: command.to_string(),
+        mine: false,
+        repo: None,
+    }
+}
+
+#[test]
+fn test_diff() {
+    use crate::component::packages::PkgInfoBuilder;
+
+    let mark = |name: &str| Mark {
+        name: name.into(),
+        by: "Moody".into(),
+        comment: "".into(),
+    };
+    let old = [
+        PkgInfoBuilder::default()
+            .name("bear".into())
+            .repo("extra".into())
+            .process("FTBFS".into())
+            .assignee("Moody".into())
+            .marks(vec![mark("noqemu")])
+            .build()
+            .unwrap(),
+        PkgInfoBuilder::default()
+            .name("ghc".into())
+            .process("Leaf package".into())
+            .build()
+            .unwrap(),
+    ];
+    let new = [
+        PkgInfoBuilder::default()
+            .name("ghc".into())
+            .repo("extra".into())
+            .process("FTBFS".into())
+            .assignee("Moody".into())
+            .marks(vec![mark("upstreamed")])
+            .build()
+            .unwrap(),
+        PkgInfoBuilder::default()
+            .name("bear".into())
+            .repo("extra".into())
+            .assignee("Moody".into())
+            .marks(vec![mark("noqemu")])
+            .build()
+            .unwrap(),
+    ];
+
+    assert!(diff(&old, &old).is_empty());
+    let events = diff(&old, &new);
+    let lines = events.iter().map(ToString::to_string).collect::<Vec<_>>();
+    assert_eq!(
+        lines,
+        [
+            "bear: status FTBFS -> none",
+            "ghc: status Leaf package -> FTBFS",
+            "ghc: new FTBFS",
+            "ghc: assignee none -> Moody",
+            "ghc: mark upstreamed added by Moody",
+        ]
+    );
+
+    let json = serde_json::to_value(&events[1]).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({
+            "event": "status_changed",
+            "package": "ghc",
+            "repo": "extra",
+            "assignee": "Moody",
+            "old": "Leaf package",
+            "new": "FTBFS",
+        })
+    );
+
+    // a package leaving the table keeps its last assignee
+    let events = diff(&new, &[]);
+    assert_eq!(events.len(), 5);
+    assert!(events
+        .iter()
+        .all(|e| e.assignee.as_deref() == Some("Moody")));
+    assert_eq!(
+        events[4].change,
+        Change::MarkRemoved {
+            mark: "upstreamed".into()
+        }
+    );
+}
+
+#[test]
+fn test_hook_matches() {
+    let event = Event {
+        package: "bash".into(),
+        repo: Some("core".into()),
+        assignee: Some("Moody".into()),
+        change: Change::NewFtbfs,
+    };
+
+    let mut hook = test_hook("new_ftbfs", "true");
+    assert!(matches(&hook, &event, None));
+    hook.repo = Some("core".to_string());
+    assert!(matches(&hook, &event, None));
+    hook.mine = true;
+    assert!(matches(&hook, &event, Some("Moody")));
+    assert!(!matches(&hook, &event, Some("Ast")));
+    assert!(!matches(&hook, &event, None));
+    hook.repo = Some("extra".to_string());
+    assert!(!matches(&hook, &event, Some("Moody")));
+    assert!(!matches(
+        &test_hook("mark_added", "true"),
+        &event,
+        Some("Moody")
+    ));
+
+    let mut config = WatchConfig {
+        hooks: vec![test_hook("new_ftbfs", "true")],
+        ..WatchConfig::default()
+    };
+    assert!(check_hooks(&config).is_ok());
+    config.hooks[0].mine = true;
+    assert!(check_hooks(&config).is_err());
+    config.hooks = vec![test_hook("ftbfs", "true")];
+    let err = check_hooks(&config).unwrap_err();
+    assert!(err.to_string().contains("unknown event `ftbfs`"), "{err}");
+}
+
+#[tokio::test]
+async fn test_run_hook() {
+    let dir = std::env::temp_dir().join(format!("lzr-test-hook-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let out = dir.join("event.json");
+    let event = Event {
+        package: "bash".into(),
+        repo: Some("core".into()),
+        assignee: None,
+        change: Change::MarkAdded {
+            mark: "noqemu".into(),
+            by: "Moody".into(),
+        },
+    };
+
+    let hook = test_hook(
+        "mark_added",
+        &format!("{{ echo $LZR_EVENT; cat; }} > {}", out.display()),
+    );
+    run_hook(&hook, &event).await.unwrap();
+    let written = std::fs::read_to_string(&out).unwrap();
+    let (name, json) = written.split_once('\n').unwrap();
+    assert_eq!(name, "mark_added");
+    let json: serde_json::Value = serde_json::from_str(json).unwrap();
+    assert_eq!(json["mark"], "noqemu");
+    assert_eq!(json["assignee"], serde_json::Value::Null);
+
+    // hooks may ignore stdin, but a failing hook is reported
+    run_hook(&test_hook("mark_added", "true"), &event)
+        .await
+        .unwrap();
+    let err = run_hook(&test_hook("mark_added", "exit 3"), &event)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("exit status: 3"), "{err}");
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -1,10 +1,21 @@
+use tui::{backend::TestBackend, style::Color, Terminal};
+
 #[test]
 fn test_draw_welcome_page() {
-    let mut terminal = lazyarchbuild::setup_crossterm_terminal().unwrap();
+    let mut terminal = Terminal::new(TestBackend::new(100, 30)).unwrap();
 
     lazyarchbuild::canvas::draw_welcome_page(&mut terminal).unwrap();
 
-    std::thread::sleep(std::time::Duration::from_secs(2));
-
-    lazyarchbuild::clean_up_terminal(&mut terminal).unwrap();
+    let buffer = terminal.backend().buffer();
+    let screen = buffer
+        .content()
+        .iter()
+        .map(|cell| cell.symbol.as_str())
+        .collect::<String>();
+    assert!(screen.contains("Fetching Package"));
+    // the box is drawn in the middle of the screen
+    let corner = buffer.get(40, 12);
+    assert_eq!(corner.symbol, "╭");
+    assert_eq!(corner.bg, Color::White);
+    assert_eq!(buffer.get(0, 0).symbol, " ");
 }